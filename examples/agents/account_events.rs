@@ -0,0 +1,28 @@
+use std::{env, sync::Arc, time::Duration};
+
+use agent_trading_kit::tools::*;
+
+/// Prints account events as they happen. Pass a JSON lines fixture path to replay it instead
+/// of subscribing to the live account, e.g. `examples/fixtures/account_events.jsonl`.
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let stream = EventStream::new().on_event(|event| {
+        if let AccountEvent::LiquidationWarning { ratio, .. } = event {
+            eprintln!("!! Margin ratio down to {:.2}x maintenance", ratio);
+        }
+    });
+
+    let mut events = match env::args().nth(1) {
+        Some(fixture) => stream.spawn(ReplayEventSource::from_file(fixture)?.with_delay(Duration::from_millis(500))),
+        None => {
+            let client = Arc::new(init_drift_client().await?);
+            stream.spawn(WebsocketEventSource::subscribe(client, None).await?)
+        }
+    };
+
+    // An agent loop would turn each event into a prompt; here we just print it.
+    while let Ok(event) = events.recv().await {
+        println!("[{}] {}", serde_json::to_string(&event)?, event.describe());
+    }
+    Ok(())
+}
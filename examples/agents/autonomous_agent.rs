@@ -1,118 +1,55 @@
-use std::{env, str::FromStr};
+use anyhow::Result;
+use rig::providers;
 
-use solana_sdk::pubkey::Pubkey;
 use agent_trading_kit::tools::*;
-use drift_rs::{types::Context, DriftClient, Wallet};
-use tokio;
+use agent_trading_kit::data::load_market_registry;
+use agent_trading_kit::scheduler::{LoggedTool, Scheduler, SchedulerConfig};
 
+/// Prompts the agent on AGENT_SCHEDULE (default every 15 minutes) with a fresh portfolio and
+/// market snapshot. Every prompt, tool call and decision is appended to AGENT_ACTION_LOG.
 #[tokio::main]
-async fn main() {
-    let rpc_url = "https://api.mainnet-beta.solana.com".to_string();
-        let agent_vault = env::var("AGENT_VAULT").expect("Missing AGENT_VAULT env variable");
-        let agent_vault_clone = agent_vault.clone();
-        let secret_key_str = env::var("AGENT_KEYPAIR").expect("Missing AGENT_KEYPAIR env variable");
-        let secret_key_bytes: Vec<u8> = secret_key_str
-            .split(',')
-            .map(|s| s.parse::<u8>().expect("Invalid byte"))
-            .collect();
-        let keypair = solana_sdk::signer::keypair::Keypair::from_bytes(&secret_key_bytes)
-            .expect("Invalid private key");
-        let secret_key_base58 = bs58::encode(keypair.to_bytes()).into_string();
-        let mut wallet = Wallet::try_from_str(&secret_key_base58).expect("Failed to load wallet");
-        let agent_vault_pubkey = Pubkey::from_str(&agent_vault_clone).unwrap();
-        wallet.to_delegated(agent_vault_pubkey);
-
-        let client = DriftClient::new(
-            Context::MainNet,
-            solana_client::nonblocking::rpc_client::RpcClient::new(rpc_url.to_string()),
-            wallet.clone(),
-        ).await.unwrap();
-
-    // // Deposit
-    // let deposit_result = Deposit::get_tx_data(DepositArgs {
-    //     amount: 10_000,
-    //     spot_market_index: 1,
-    //     user_token_account: "EXAMPLE_PUBLIC_KEY".to_string(),
-    //     reduce_only: Some(false),
-    // }).await;
-
-    // match deposit_result {
-    //     Ok(tx) => println!("Deposit transaction data: {}", tx),
-    //     Err(_) => println!("Deposit failed!"),
-    // }
-
-    // // Withdrawal
-    // let withdraw_result = Withdraw::get_tx_data(WithdrawArgs {
-    //     amount: 5_000, 
-    //     spot_market_index: 1, 
-    //     user_token_account: "EXAMPLE_PUBLIC_KEY".to_string(),
-    //     reduce_only: Some(false),
-    // }).await;
-
-    // match withdraw_result {
-    //     Ok(tx) => println!("Withdraw transaction data: {}", tx),
-    //     Err(_) => println!("Withdrawal failed!"),
-    // }
-
-    // // Get Drift Info for market
-    // let market_index = 6; // ARB-PERP
-    // let drift_market_info = DriftInfo::fetch_drift_info(market_index).await;
-
-    // match drift_market_info {
-    //     Ok(market_info) => println!("Market info for market index {}: {}", market_index, market_info),
-    //     Err(_) => println!("Query failed!"),
-    // }
-
-    // // Place perp order on Drift
-    // let perp_response = DriftPlaceComplexPerpOrders::place_complex_perp_orders(DriftPlaceComplexPerpOrdersArgs{
-    //     orders: vec![
-    //         PerpOrder {
-    //             market_index: 7,   // DOGE market index
-    //             price: None,
-    //             amount: 50,
-    //             post_only: Some(false), // Explicitly set post_only
-    //         },
-    //         PerpOrder {
-    //             market_index: 6,   // ARB market index
-    //             amount: -5,        // Short 5
-    //             price: None,
-    //             post_only: Some(false), // Explicitly set post_only
-    //         },
-    //     ],
-    // }
-    // ).await;
-        
-    // match perp_response {
-    //     Ok(perp_id) => println!("Perp response: {}", perp_id),
-    //     Err(_) => println!("Order failed!"),
-    // }
-
-    // Get info on open positions and orders
-    let open_positions = DriftGetOpenPositions::get_open_positions(GetOpenPositionsArgs {
-        account_pubkey: None, // Optional (Defaults to agent vault from env)
-        market_index: None, // optional filter
-        position_type: None // optional filter
-        
-    }, &client).await;
-
-    match open_positions {
-        Ok(open_positions_data) => println!("Perp positions: {:?}, Spot positions: {:?}, Open orders: {:?}", open_positions_data.perp_positions, open_positions_data.spot_positions, open_positions_data.open_orders),
-        Err( drift_data_error ) => println!("Failed to query info on open positions: {}", drift_data_error),
+async fn main() -> Result<(), anyhow::Error> {
+    const MAX_DEBUG_LEVEL:tracing::Level = tracing::Level::ERROR;
+    tracing_subscriber::fmt()
+        .with_max_level(MAX_DEBUG_LEVEL)
+        .with_target(false)
+        .init();
+
+    let drift_client = init_drift_client().await?;
+    // Market names for the agent context come from the Drift program, or the bundled snapshot.
+    let market_context = load_market_registry(&drift_client).context();
+    let resume_report = resume_jobs(&drift_client).await?;
+    println!("Resumed jobs: {:?}", resume_report.resumed);
+    for (job_id, reason) in &resume_report.flagged {
+        println!("Flagged job {}: {}", job_id, reason);
     }
 
-
-    // // Get info from Drift vault
-    // let drift_vault_params = DriftVaultInfoArgs {
-    //     vault_address: None, // optional, defaults to Agent vault
-    //     requested_fields: None, // Optional, defaults to all
-    // };
-
-    // let drift_vault_response = DriftVaultInfo::fetch_vault_info(drift_vault_params).await;
-    // match drift_vault_response {
-    //     Ok(drift_vault_data) => println!("Vault data {}", drift_vault_data),
-    //     Err( drift_data_error ) => println!("Failed to query drift vault data: {}", drift_data_error),
-    // }
-
-    
-
+    let config = SchedulerConfig::from_env()?;
+    let openai_client = providers::openai::Client::from_env();
+
+    // Trading tools are wrapped with `LoggedTool::action` so they count against the per-cycle limit.
+    let drift_agent = openai_client
+        .agent(providers::openai::GPT_4O)
+        .preamble(concat!("You are an autonomous trading agent and the delegate/executor for a Drift Vault. ",
+                          "You are prompted on a schedule with a snapshot of the vault's account and of the markets it holds or watches. ",
+                          "Decide whether to trade using your tools, keep within the action limit you are given, and always end with a short summary of your decision."))
+        .max_tokens(1024)
+        .context(&market_context)
+        .tool(LoggedTool::query(DriftInfo))
+        .tool(LoggedTool::query(DriftGetOpenPositions))
+        .tool(LoggedTool::query(DriftPositionSize))
+        .tool(LoggedTool::query(DriftMarginInfo))
+        .tool(LoggedTool::query(DriftFundingRates))
+        .tool(LoggedTool::query(DriftOrderbook))
+        .tool(LoggedTool::query(DriftTradeHistory))
+        .tool(LoggedTool::action(DriftPlacePerpOrders))
+        .tool(LoggedTool::action(DriftTWAPOrders))
+        .tool(LoggedTool::action(DriftTrailingStopOrders))
+        .tool(LoggedTool::action(DriftClosePerpPosition))
+        .tool(LoggedTool::action(DriftSettlePnl))
+        .build();
+
+    println!("[Autonomous Agent] schedule: {:?}, max actions per cycle: {}", config.schedule, config.max_actions_per_cycle);
+    Scheduler::new(drift_agent, config).run(&drift_client).await;
+    Ok(())
 }
@@ -1,4 +1,4 @@
-use std::io;
+use std::{io, sync::Arc};
 use anyhow::Result;
 use rig::{
     completion::Prompt,
@@ -8,7 +8,7 @@ pub use solana_sdk::{address_lookup_table::AddressLookupTableAccount, pubkey::Pu
 use std::io::Write;
 
 use agent_trading_kit::tools::*;
-use agent_trading_kit::data::MARKET_DATA;
+use agent_trading_kit::data::load_market_registry;
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
@@ -18,18 +18,36 @@ async fn main() -> Result<(), anyhow::Error> {
         .with_target(false)
         .init();
 
+    // Pick up TWAP/VWAP/trailing stop jobs that were running when the agent last stopped.
+    let drift_client = init_drift_client().await?;
+    // Market names for the agent context come from the Drift program, or the bundled snapshot.
+    let market_context = load_market_registry(&drift_client).context();
+    let resume_report = resume_jobs(&drift_client).await?;
+    println!("Resumed jobs: {:?}", resume_report.resumed);
+    for (job_id, reason) in &resume_report.flagged {
+        println!("Flagged job {}: {}", job_id, reason);
+    }
+    // Start the daily loss limit from each sub-account's equity now, not from its first order.
+    risk_guard().record_day_starts(&drift_client).await?;
+
     let openai_client = providers::openai::Client::from_env();
 
-    let drift_agent = openai_client
+    let mut drift_agent = openai_client
         .agent(providers::openai::GPT_4O)
         .preamble(concat!("You are an agent designed to make autonomous trades based on user prompts. You are the delegate/executor for a Drift Vault. ",
                           "Users can prompt you to open and close simple/mixed perpetual and spot token orders, or more complex orders involving strategies implemented in your tools",
                           "You also have some knowledge of of existing TWAP and VWAP orders via your tools."))
         .max_tokens(1024)
-        .context(MARKET_DATA.as_str())
+        .context(&market_context)
         .tool(Deposit)
         .tool(Withdraw)
+        .tool(DriftDepositorWithdraw)
         .tool(DriftVaultInfo)
+        .tool(DriftVaultDepositors)
+        .tool(DriftVaultDepositor)
+        .tool(DriftVaultPerformance)
+        .tool(DriftInsuranceStake)
+        .tool(DriftInsuranceStakeInfo)
         .tool(DriftInfo)
         .tool(DriftPlacePerpOrders)
         .tool(DriftTWAPOrders)
@@ -37,7 +55,39 @@ async fn main() -> Result<(), anyhow::Error> {
         .tool(DriftTrailingStopOrders)
         .tool(DriftGetOpenPositions)
         .tool(DriftClosePerpPosition)
-        .build();
+        .tool(DriftPositionSize)
+        .tool(DriftFlattenAll)
+        .tool(DriftListSubAccounts)
+        .tool(DriftCreateSubAccount)
+        .tool(DriftTransferCollateral)
+        .tool(DriftSpotRates)
+        .tool(DriftSpotLending)
+        .tool(DriftMarginInfo)
+        .tool(DriftFundingRates)
+        .tool(DriftOrderbook)
+        .tool(DriftTradeHistory)
+        .tool(DriftSettlePnl)
+        .tool(DriftAddAlert)
+        .tool(DriftListAlerts)
+        .tool(DriftRemoveAlert);
+    if manager_tools_enabled() {
+        drift_agent = drift_agent
+            .tool(DriftInitVault)
+            .tool(DriftUpdateVault)
+            .tool(DriftManagerDeposit)
+            .tool(DriftManagerWithdraw)
+            .tool(DriftInitVaultDepositor);
+    }
+    let drift_agent = Arc::new(drift_agent.build());
+
+    // Snapshot the agent vault so its performance can be reported.
+    let drift_client = Arc::new(drift_client);
+    let agent_vault = std::env::var("AGENT_VAULT")?.parse::<Pubkey>()?;
+    spawn_vault_recorder(drift_client.clone(), vec![agent_vault])?;
+
+    // Watch registered price alerts and hand the ones that fire back to the agent.
+    spawn_alert_watcher(drift_client);
+    spawn_alert_prompter(drift_agent.clone());
 
     println!("[Example Agent]");
     println!("Enter your prompt below");
@@ -53,5 +103,8 @@ async fn main() -> Result<(), anyhow::Error> {
         let formatted_response = response.replace("\\n", "\n").replace("\\\"", "\"");
         println!("Agent: {}", formatted_response);
 
+        // In approval mode order tools only queue trade intents; decide on them here.
+        prompt_pending_intents().await;
+
     }
 }
@@ -3,7 +3,7 @@ use rand::Rng;
 use tokio::time::sleep;
 use solana_sdk::pubkey::Pubkey;
 use agent_trading_kit::tools::*;
-use drift_rs::{types::{Context, MarketId, MarketType}, DriftClient, Wallet};
+use drift_rs::{types::Context, DriftClient, Wallet};
 use rand::thread_rng;
 
 #[tokio::main]
@@ -37,6 +37,7 @@ async fn main() {
             account_pubkey: None,
             market_index: None,
             position_type: None,
+            sub_account_id: None,
         }, &client).await;
 
         if let Ok(open_positions) = open_positions_result {
@@ -47,14 +48,17 @@ async fn main() {
 
                     let close_order = PerpOrder {
                         market_index,
-                        amount: close_amount / 1_000_000_000, 
+                        amount: close_amount as f64 / 1_000_000_000.0, 
                         price: None,
                         post_only: Some(false),
+                        reduce_only: Some(true),
+                        max_slippage_bps: None,
                     };
 
                     println!("Closing open position on market {}: {}", market_index, close_amount);
                     let close_result = DriftPlacePerpOrders::place_perp_orders(DriftPlacePerpOrdersArgs {
                         orders: vec![close_order],
+                        sub_account_id: None,
                     }, &client)
                     .await;
 
@@ -75,14 +79,16 @@ async fn main() {
 
         let mut rng = thread_rng();
         let market_index = rng.gen_range(1..=10);
-        let market_id: MarketId = MarketId::new(market_index, MarketType::Perp);
         //let amount = rng.gen_range(1..=5) as i64 * if rng.gen_bool(0.5) { 1 } else { -1 }; // Random long or short
-        let price  = get_token_oracle_price(market_id, &client).await.unwrap();
-        let price_float = price as f64 / 1_000_000.0; 
-
         let usdc_value = 50.0;
-        let amount = (usdc_value / price_float).round() as i64; 
-        if amount == 0 {
+        let amount = match calculate_position_size(&client, market_index, SizingMode::UsdNotional(usdc_value), true, None).await {
+            Ok(size) => size.amount,
+            Err(e) => {
+                println!("Skipping market {}: {}", market_index, e);
+                continue;
+            }
+        };
+        if amount == 0.0 {
             println!("Skipping market {} due to price being too high for a meaningful trade.", market_index);
             continue;
         }
@@ -92,12 +98,15 @@ async fn main() {
             amount,
             price: None,
             post_only: Some(false),
+            reduce_only: None,
+            max_slippage_bps: None,
         };
 
         println!("Placing order: {:?} on market {}", amount, market_index);
 
         let order_result = DriftPlacePerpOrders::place_perp_orders(DriftPlacePerpOrdersArgs {
             orders: vec![order],
+            sub_account_id: None,
         }, &client)
         .await;
 
@@ -120,6 +129,7 @@ async fn main() {
             account_pubkey: None,
             market_index: Some(market_index),
             position_type: None,
+            sub_account_id: None,
         }, &client).await;
 
         match open_positions {
@@ -128,14 +138,17 @@ async fn main() {
                     let close_amount = -positions.perp_positions[0].base_asset_amount;
                     let close_order = PerpOrder {
                         market_index,
-                        amount: close_amount / 1_000_000_000,
+                        amount: close_amount as f64 / 1_000_000_000.0,
                         price: None,
                         post_only: Some(false),
+                        reduce_only: Some(true),
+                        max_slippage_bps: None,
                     };
 
                     println!("Closing position on market {}: {}", market_index, close_amount);
                     let close_result = DriftPlacePerpOrders::place_perp_orders(DriftPlacePerpOrdersArgs {
                         orders: vec![close_order],
+                        sub_account_id: None,
                     }, &client)
                     .await;
 
@@ -1,9 +1,9 @@
-use std::{env, str::FromStr, time::Duration};
+use std::{env, str::FromStr, sync::Arc, time::Duration};
 use rand::Rng;
 use tokio::time::sleep;
 use solana_sdk::pubkey::Pubkey;
 use agent_trading_kit::tools::*;
-use drift_rs::{types::{Context, MarketId, MarketType}, DriftClient, Wallet};
+use drift_rs::{types::Context, DriftClient, Wallet};
 use rand::thread_rng;
 
 #[tokio::main]
@@ -31,18 +31,40 @@ async fn main() {
     .await
     .unwrap();
 
+    let event_source = WebsocketEventSource::subscribe(Arc::new(client.clone()), None)
+        .await
+        .expect("Failed to subscribe to account events");
+    let mut events = EventStream::new().spawn(event_source);
+
+    // Price alerts replace polling the oracle while a position is open.
+    spawn_alert_watcher(Arc::new(client.clone()));
+    let mut alerts = alert_registry().subscribe();
+
     loop {
         println!("Checking for open positions before trading...");
         let open_positions_result = DriftGetOpenPositions::get_open_positions(GetOpenPositionsArgs {
             account_pubkey: None,
             market_index: None,
             position_type: None,
+            sub_account_id: None,
         }, &client).await;
 
         if let Ok(open_positions) = open_positions_result {
             if !open_positions.perp_positions.is_empty() {
                 println!("Waiting for trailing stop orders to close positions...");
-                sleep(Duration::from_secs(10)).await;
+                // Wait on account events and price alerts instead of polling until a position is closed.
+                loop {
+                    tokio::select! {
+                        Ok(event) = events.recv() => {
+                            println!("Event: {}", event.describe());
+                            if matches!(event, AccountEvent::PositionChanged { new_size, .. } if new_size == 0.0) {
+                                break;
+                            }
+                        }
+                        Ok(fired) = alerts.recv() => println!("{}", fired.message),
+                        else => break,
+                    }
+                }
                 continue;
             }
         } else {
@@ -53,27 +75,21 @@ async fn main() {
 
         let mut rng = thread_rng();
         let market_index = rng.gen_range(1..=10);
-        let market_id: MarketId = MarketId::new(market_index, MarketType::Perp);
         
-        let price = match get_token_oracle_price(market_id, &client).await {
-            Ok(price) => price, // Assign the value if successful
+        let position_type = if rng.gen_bool(0.5) { "long" } else { "short" };
+
+        let usdc_value = 50.0;
+        let amount = match calculate_position_size(&client, market_index, SizingMode::UsdNotional(usdc_value), position_type == "long", None).await {
+            Ok(size) => size.amount.abs(),
             Err(e) => {
-                eprintln!("Error getting token oracle price: {:?}", e);
-                //return Err(e.into()); // Handle the error or propagate it
+                eprintln!("Error sizing position: {:?}", e);
                 continue;
             }
         };
-        
-        let price_float = price as f64 / 1_000_000.0; 
-
-        let usdc_value = 50.0;
-        let amount = (usdc_value / price_float).round() as i64; 
-        if amount == 0 {
+        if amount == 0.0 {
             println!("Skipping market {} due to price being too high for a meaningful trade.", market_index);
             continue;
         }
-
-        let position_type = if rng.gen_bool(0.5) { "long" } else { "short" };
         let trailing_stop_percentage = 2.0; // Example: 2% trailing stop
 
         println!(
@@ -87,11 +103,18 @@ async fn main() {
             position_type: Some(position_type.to_string()),
             trailing_stop_percentage: Some(trailing_stop_percentage),
             entry_price: None,
+            sub_account_id: None,
         })
         .await;
 
         match order_result {
-            Ok(order_id) => println!("Trailing stop order placed successfully: {}", order_id),
+            Ok(order_id) => {
+                println!("Trailing stop order placed successfully: {}", order_id);
+                let condition = AlertCondition::Move { percent: trailing_stop_percentage, window_secs: 3600 };
+                if let Err(e) = alert_registry().add(market_index, condition, AlertAction::Event, false) {
+                    println!("Failed to register price alert: {:?}", e);
+                }
+            }
             Err(e) => {
                 println!("Failed to place trailing stop order: {:?}", e);
                 continue;
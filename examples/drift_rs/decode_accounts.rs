@@ -0,0 +1,46 @@
+use std::{env, fs, str::FromStr};
+use anyhow::{anyhow, Result};
+use dotenv::dotenv;
+use solana_sdk::pubkey::Pubkey;
+
+use agent_trading_kit::tools::*;
+
+/**
+ * Checks the account decoders against account fixtures, offline (`cargo test` runs the same
+ * check over examples/fixtures/anchor_accounts.jsonl)
+ * usage cargo run --example decode_accounts [fixtures, default examples/fixtures/anchor_accounts.jsonl]
+ * Record a live mainnet account as a new fixture line:
+ *       cargo run --example decode_accounts -- --record [Vault|VaultDepositor|InsuranceFundStake] [ADDRESS] >> examples/fixtures/anchor_accounts.jsonl
+ */
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(|a| a.as_str()) == Some("--record") {
+        let (Some(account_type), Some(address)) = (args.get(1), args.get(2)) else {
+            return Err(anyhow!("usage: --record [{}] [ADDRESS]", FIXTURE_ACCOUNT_TYPES.join("|")));
+        };
+        let client = init_drift_client().await?;
+        let fixture = AccountFixture::record(&client.rpc(), account_type, &Pubkey::from_str(address)?).await?;
+        println!("{}", serde_json::to_string(&fixture)?);
+        return Ok(());
+    }
+
+    let path = args.first().cloned().unwrap_or("examples/fixtures/anchor_accounts.jsonl".to_string());
+    let mut failures = 0;
+    for line in fs::read_to_string(&path)?.lines().filter(|line| !line.trim().is_empty()) {
+        let fixture: AccountFixture = serde_json::from_str(line)?;
+        match fixture.check() {
+            Ok(()) => println!("ok    {} ({})", fixture.label, fixture.source),
+            Err(err) => {
+                failures += 1;
+                println!("FAIL  {}", err);
+            }
+        }
+    }
+    if failures > 0 {
+        return Err(anyhow!("{} fixture(s) failed", failures));
+    }
+    Ok(())
+}
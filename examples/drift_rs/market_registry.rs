@@ -0,0 +1,22 @@
+use anyhow::{anyhow, Result};
+use dotenv::dotenv;
+
+use agent_trading_kit::data::*;
+use agent_trading_kit::tools::*;
+
+/**
+ * Prints the market registry loaded from the Drift program as JSON
+ * usage cargo run --example market_registry > src/data/markets_snapshot.json
+ */
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+    let client = init_drift_client().await?;
+    let registry = load_market_registry(&client);
+    if registry.source != RegistrySource::ProgramData {
+        // Printing the bundled snapshot would overwrite it with itself.
+        return Err(anyhow!("No markets loaded from the Drift program; not printing the bundled snapshot"));
+    }
+    println!("{}", serde_json::to_string_pretty(registry.as_ref())?);
+    Ok(())
+}
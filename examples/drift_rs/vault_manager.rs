@@ -0,0 +1,145 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use dotenv::dotenv;
+use rig::tool::Tool;
+
+use agent_trading_kit::tools::*;
+
+/**
+ * Drift vault manager commands, signed with VAULT_MANAGER_KEYPAIR
+ * usage cargo run --example vault_manager -- init-vault --name MyVault --delegate [DELEGATE]
+ *       cargo run --example vault_manager -- manager-deposit --vault-address [VAULT] --amount 2
+ */
+
+/// Command-line arguments
+#[derive(Parser)]
+struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a vault managed by the manager keypair
+    InitVault {
+        #[clap(long)]
+        name: String,
+        #[clap(long)]
+        delegate: Option<String>,
+        #[clap(long)]
+        spot_market_index: Option<u16>,
+        #[clap(long)]
+        redeem_period_secs: Option<i64>,
+        #[clap(long)]
+        max_tokens: Option<f64>,
+        #[clap(long)]
+        min_deposit_amount: Option<f64>,
+        #[clap(long)]
+        management_fee_percent: Option<f64>,
+        #[clap(long)]
+        profit_share_percent: Option<f64>,
+        #[clap(long)]
+        hurdle_rate_percent: Option<f64>,
+        #[clap(long)]
+        permissioned: bool,
+    },
+    /// Change the vault's trading delegate
+    UpdateDelegate {
+        #[clap(long)]
+        vault_address: Option<String>,
+        #[clap(long)]
+        delegate: String,
+    },
+    /// Create the depositor account for a wallet
+    InitVaultDepositor {
+        #[clap(long)]
+        vault_address: Option<String>,
+        #[clap(long)]
+        deposit_authority: String,
+    },
+    /// Deposit whole tokens from the manager wallet
+    ManagerDeposit {
+        #[clap(long)]
+        vault_address: Option<String>,
+        #[clap(long)]
+        amount: f64,
+    },
+    /// Request, cancel or complete a manager withdrawal
+    ManagerWithdraw {
+        #[clap(long)]
+        vault_address: Option<String>,
+        /// request, cancel or complete
+        #[clap(long)]
+        action: String,
+        #[clap(long)]
+        amount: Option<f64>,
+        /// token, shares or shares_percent
+        #[clap(long)]
+        unit: Option<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+
+    let result = match Args::parse().command {
+        Command::InitVault {
+            name,
+            delegate,
+            spot_market_index,
+            redeem_period_secs,
+            max_tokens,
+            min_deposit_amount,
+            management_fee_percent,
+            profit_share_percent,
+            hurdle_rate_percent,
+            permissioned,
+        } => DriftInitVault.call(DriftInitVaultArgs {
+            name,
+            delegate,
+            spot_market_index,
+            redeem_period_secs,
+            max_tokens,
+            min_deposit_amount,
+            management_fee_percent,
+            profit_share_percent,
+            hurdle_rate_percent,
+            permissioned: Some(permissioned),
+        }).await,
+        Command::UpdateDelegate { vault_address, delegate } => DriftUpdateVault.call(DriftUpdateVaultArgs {
+            vault_address,
+            delegate: Some(delegate),
+            redeem_period_secs: None,
+            max_tokens: None,
+            min_deposit_amount: None,
+            management_fee_percent: None,
+            profit_share_percent: None,
+            hurdle_rate_percent: None,
+            permissioned: None,
+        }).await,
+        Command::InitVaultDepositor { vault_address, deposit_authority } => DriftInitVaultDepositor.call(DriftInitVaultDepositorArgs {
+            authority: deposit_authority,
+            vault_address,
+        }).await,
+        Command::ManagerDeposit { vault_address, amount } => DriftManagerDeposit.call(DriftManagerDepositArgs {
+            amount,
+            vault_address,
+        }).await,
+        Command::ManagerWithdraw { vault_address, action, amount, unit } => DriftManagerWithdraw.call(DriftManagerWithdrawArgs {
+            action,
+            amount,
+            unit,
+            vault_address,
+        }).await,
+    };
+
+    match result {
+        Ok(output) => println!("{}", output),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
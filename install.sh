@@ -5,12 +5,8 @@ set -e
 echo "Updating package lists..."
 sudo apt update
 
-echo "Installing node.js and yarn"
+echo "Installing curl..."
 sudo apt install -y curl
-curl -fsSL https://deb.nodesource.com/setup_18.x | sudo -E bash -  
-sudo apt install -y nodejs
-sudo npm install --global yarn
-yarn --version
 
 echo "Installing Git..."
 sudo apt install -y git
@@ -1,9 +1,18 @@
-use axum::{routing::post, Json, Router};
+use axum::{
+    extract::{Path, Request},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
 use rig::{completion::{CompletionModel, Prompt}, message::Message};
 use serde::{Deserialize, Serialize};
-use std::{net::SocketAddr, sync::Arc};
+use std::{env, net::SocketAddr, sync::Arc};
 use tokio::net::TcpListener;
 
+use crate::tools::{approve_intent, list_intents, reject_intent, TradeIntent};
+
 
 #[derive(Deserialize)]
 struct PromptRequest {
@@ -27,16 +36,78 @@ async fn handle_prompt<M: CompletionModel>(
     Json(PromptResponse { response })
 }
 
+#[derive(Serialize)]
+struct IntentDecisionResponse {
+    id: String,
+    result: String,
+}
+
+async fn handle_list_intents() -> Json<Vec<TradeIntent>> {
+    Json(list_intents())
+}
+
+async fn handle_approve_intent(Path(id): Path<String>) -> (StatusCode, Json<IntentDecisionResponse>) {
+    match approve_intent(&id).await {
+        Ok(result) => (StatusCode::OK, Json(IntentDecisionResponse { id, result })),
+        Err(err) => (StatusCode::BAD_REQUEST, Json(IntentDecisionResponse { id, result: err.to_string() })),
+    }
+}
+
+async fn handle_reject_intent(Path(id): Path<String>) -> (StatusCode, Json<IntentDecisionResponse>) {
+    match reject_intent(&id) {
+        Ok(_) => (StatusCode::OK, Json(IntentDecisionResponse { id, result: "Rejected".to_string() })),
+        Err(err) => (StatusCode::BAD_REQUEST, Json(IntentDecisionResponse { id, result: err.to_string() })),
+    }
+}
+
+/// Intent routes move funds, so they need `Authorization: Bearer <AGENT_APPROVAL_TOKEN>`. Without a
+/// token configured they are only served to a server bound to localhost.
+fn authorize_intents(headers: &HeaderMap, public: bool) -> Result<(), StatusCode> {
+    let Some(token) = env::var("AGENT_APPROVAL_TOKEN").ok().filter(|token| !token.is_empty()) else {
+        return if public { Err(StatusCode::FORBIDDEN) } else { Ok(()) };
+    };
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match bearer {
+        Some(bearer) if constant_time_eq(bearer.as_bytes(), token.as_bytes()) => Ok(()),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 pub async fn run_server<M: CompletionModel + 'static>(agent: rig::agent::Agent<M>, port: u16, public: bool, https: bool) {
     let agent = Arc::new(agent);
 
-    let app = Router::new().route(
-        "/prompt",
-        post({
-            let agent = Arc::clone(&agent);
-            move |payload| handle_prompt(payload, agent.clone())
-        }),
-    );
+    if public && env::var("AGENT_APPROVAL_TOKEN").map_or(true, |token| token.is_empty()) {
+        println!("AGENT_APPROVAL_TOKEN is not set, intent routes are disabled on a public server");
+    }
+
+    // Trade intents created while approval mode is on
+    let intents = Router::new()
+        .route("/intents", get(handle_list_intents))
+        .route("/intents/:id/approve", post(handle_approve_intent))
+        .route("/intents/:id/reject", post(handle_reject_intent))
+        .route_layer(middleware::from_fn(move |request: Request, next: Next| async move {
+            match authorize_intents(request.headers(), public) {
+                Ok(()) => next.run(request).await,
+                Err(status) => status.into_response(),
+            }
+        }));
+
+    let app = Router::new()
+        .route(
+            "/prompt",
+            post({
+                let agent = Arc::clone(&agent);
+                move |payload| handle_prompt(payload, agent.clone())
+            }),
+        )
+        .merge(intents);
 
     let address = if public { [0, 0, 0, 0] } else { [127, 0, 0, 1] };
     let addr = SocketAddr::from((address, port));
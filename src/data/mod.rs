@@ -1,4 +1,3 @@
-mod markets;
+mod registry;
 
-pub use markets::MARKET_DATA;
-pub use markets::parse_market_data;
\ No newline at end of file
+pub use registry::*;
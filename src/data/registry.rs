@@ -0,0 +1,179 @@
+use std::sync::{Arc, OnceLock, RwLock};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use drift_rs::DriftClient;
+
+/// Markets as of the last snapshot, used until `load_market_registry` runs or when it cannot
+/// reach Drift. The bundled file is still the earlier hand-kept list, with gaps and no decimals;
+/// markets missing from it are named `SPOT-n`/`PERP-n`. Regenerate it from program data with
+/// `cargo run --example market_registry > src/data/markets_snapshot.json`.
+const MARKETS_SNAPSHOT: &str = include_str!("markets_snapshot.json");
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MarketInfo {
+    pub market_index: u16,
+    pub name: String,
+    /// Token decimals of a spot market; missing from a snapshot that was not generated from program data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decimals: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrySource {
+    #[default]
+    Snapshot,
+    ProgramData,
+}
+
+/// Drift spot and perp markets by index.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MarketRegistry {
+    pub spot: Vec<MarketInfo>,
+    pub perp: Vec<MarketInfo>,
+    #[serde(skip)]
+    pub source: RegistrySource,
+}
+
+fn decode_market_name(name: &[u8; 32]) -> String {
+    String::from_utf8_lossy(name).trim_end_matches(['\0', ' ']).to_string()
+}
+
+impl MarketRegistry {
+    /// The registry bundled with the crate.
+    pub fn snapshot() -> Self {
+        serde_json::from_str(MARKETS_SNAPSHOT).expect("The bundled market snapshot is valid JSON")
+    }
+
+    /// Every market the client loaded from the Drift program; the snapshot if it loaded none.
+    pub fn from_program_data(client: &DriftClient) -> Self {
+        let program_data = client.program_data();
+        let mut spot: Vec<MarketInfo> = program_data
+            .spot_market_configs()
+            .iter()
+            .map(|market| MarketInfo {
+                market_index: market.market_index,
+                name: decode_market_name(&market.name),
+                decimals: Some(market.decimals),
+            })
+            .collect();
+        let mut perp: Vec<MarketInfo> = program_data
+            .perp_market_configs()
+            .iter()
+            .map(|market| MarketInfo {
+                market_index: market.market_index,
+                name: decode_market_name(&market.name),
+                decimals: None,
+            })
+            .collect();
+        if spot.is_empty() && perp.is_empty() {
+            return Self::snapshot();
+        }
+        spot.sort_by_key(|market| market.market_index);
+        perp.sort_by_key(|market| market.market_index);
+        MarketRegistry { spot, perp, source: RegistrySource::ProgramData }
+    }
+
+    pub fn spot(&self, market_index: u16) -> Option<&MarketInfo> {
+        self.spot.iter().find(|market| market.market_index == market_index)
+    }
+
+    pub fn perp(&self, market_index: u16) -> Option<&MarketInfo> {
+        self.perp.iter().find(|market| market.market_index == market_index)
+    }
+
+    pub fn spot_name(&self, market_index: u16) -> String {
+        self.spot(market_index).map_or(format!("SPOT-{}", market_index), |market| market.name.clone())
+    }
+
+    pub fn perp_name(&self, market_index: u16) -> String {
+        self.perp(market_index).map_or(format!("PERP-{}", market_index), |market| market.name.clone())
+    }
+
+    /// Looks a market up by name, ignoring case, e.g. "sol" or "SOL-PERP".
+    pub fn find_spot(&self, name: &str) -> Option<&MarketInfo> {
+        self.spot.iter().find(|market| market.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn find_perp(&self, name: &str) -> Option<&MarketInfo> {
+        self.perp.iter().find(|market| market.name.eq_ignore_ascii_case(name))
+    }
+
+    /// One `Spot Market 0: "USDC"` / `Perp Market 0: "SOL-PERP"` line per market, for agent context.
+    pub fn context(&self) -> String {
+        self.spot
+            .iter()
+            .map(|market| format!("Spot Market {}: \"{}\"", market.market_index, market.name))
+            .chain(self.perp.iter().map(|market| format!("Perp Market {}: \"{}\"", market.market_index, market.name)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn registry_lock() -> &'static RwLock<Arc<MarketRegistry>> {
+    static MARKET_REGISTRY: OnceLock<RwLock<Arc<MarketRegistry>>> = OnceLock::new();
+    MARKET_REGISTRY.get_or_init(|| RwLock::new(Arc::new(MarketRegistry::snapshot())))
+}
+
+/// The current registry: the bundled snapshot until `load_market_registry` has run.
+pub fn market_registry() -> Arc<MarketRegistry> {
+    registry_lock().read().unwrap().clone()
+}
+
+/// Replaces the registry with the markets in `client`'s program data. Call once at startup.
+pub fn load_market_registry(client: &DriftClient) -> Arc<MarketRegistry> {
+    let registry = Arc::new(MarketRegistry::from_program_data(client));
+    *registry_lock().write().unwrap() = registry.clone();
+    registry
+}
+
+/// Name of a perp market in the current registry, e.g. "SOL-PERP", or `PERP-n` if unknown.
+pub fn perp_market_name(market_index: u16) -> String {
+    market_registry().perp_name(market_index)
+}
+
+/// Name of a spot market in the current registry, e.g. "USDC", or `SPOT-n` if unknown.
+pub fn spot_market_name(market_index: u16) -> String {
+    market_registry().spot_name(market_index)
+}
+
+/// The registry as `{"SPOT": {"0": "USDC", ..}, "PERP": {"0": "SOL-PERP", ..}}`.
+pub fn parse_market_data() -> serde_json::Value {
+    let registry = market_registry();
+    let names = |markets: &[MarketInfo]| -> serde_json::Map<String, serde_json::Value> {
+        markets.iter().map(|market| (market.market_index.to_string(), json!(market.name))).collect()
+    };
+    json!({
+        "SPOT": names(&registry.spot),
+        "PERP": names(&registry.perp)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Program data lists every market from 0 up and carries spot decimals, so gaps or missing
+    /// decimals mean the bundled file was not generated by the market_registry example.
+    #[test]
+    fn bundled_snapshot_is_generated_from_program_data() {
+        let snapshot = MarketRegistry::snapshot();
+        for (markets, kind) in [(&snapshot.spot, "spot"), (&snapshot.perp, "perp")] {
+            for (expected_index, market) in markets.iter().enumerate() {
+                assert_eq!(
+                    market.market_index as usize, expected_index,
+                    "{} market {} is missing from the snapshot; regenerate it with `cargo run --example market_registry > src/data/markets_snapshot.json`",
+                    kind, expected_index
+                );
+            }
+        }
+        for market in &snapshot.spot {
+            assert!(
+                market.decimals.is_some(),
+                "spot market {} has no decimals; regenerate the snapshot with `cargo run --example market_registry > src/data/markets_snapshot.json`",
+                market.market_index
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 pub mod agent;
 pub mod tools;
 pub mod data;
-pub mod askj;
\ No newline at end of file
+pub mod askj;
+pub mod scheduler;
\ No newline at end of file
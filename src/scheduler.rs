@@ -0,0 +1,351 @@
+use std::{env, fs::OpenOptions, io::Write, path::PathBuf, str::FromStr, sync::{Mutex, OnceLock}, time::Duration};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::time::sleep;
+
+use rig::{
+    agent::Agent,
+    completion::{CompletionModel, Prompt, ToolDefinition},
+    tool::Tool,
+};
+use drift_rs::DriftClient;
+
+use crate::tools::*;
+
+const DEFAULT_SCHEDULE: &str = "15m";
+const DEFAULT_ACTION_LOG_PATH: &str = ".agent_actions.jsonl";
+const DEFAULT_MAX_ACTIONS_PER_CYCLE: usize = 3;
+const DEFAULT_WATCH_MARKETS: [u16; 3] = [0, 1, 2];
+const DEFAULT_TASK: &str = "Review the portfolio and market context and decide whether to open, adjust or close positions. If nothing needs doing, say so and do not call trading tools.";
+/// Attempts per cycle before it is skipped until the next tick.
+const MAX_CYCLE_ATTEMPTS: u32 = 5;
+const BASE_BACKOFF_SECS: u64 = 5;
+const MAX_BACKOFF_SECS: u64 = 300;
+
+/// When the scheduler prompts the agent: a fixed interval such as `15m`, `90s` or `1h`, or a
+/// cron expression with a seconds field such as `0 */15 * * * *`.
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    Interval(Duration),
+    Cron(Box<cron::Schedule>),
+}
+
+impl FromStr for Schedule {
+    type Err = DriftDataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let interval = s
+            .char_indices()
+            .last()
+            .and_then(|(i, unit)| {
+                let value: u64 = s[..i].parse().ok()?;
+                match unit {
+                    's' => Some(value),
+                    'm' => Some(value * 60),
+                    'h' => Some(value * 3600),
+                    _ => None,
+                }
+            });
+        if let Some(secs) = interval.filter(|secs| *secs > 0) {
+            return Ok(Schedule::Interval(Duration::from_secs(secs)));
+        }
+        cron::Schedule::from_str(s)
+            .map(|schedule| Schedule::Cron(Box::new(schedule)))
+            .map_err(|e| DriftDataError { message: format!("Invalid schedule '{}': {}", s, e) })
+    }
+}
+
+impl Schedule {
+    /// Time to wait from now until the next tick.
+    pub fn next_delay(&self) -> Duration {
+        match self {
+            Schedule::Interval(interval) => *interval,
+            Schedule::Cron(schedule) => schedule
+                .upcoming(chrono::Utc)
+                .next()
+                .and_then(|next| (next - chrono::Utc::now()).to_std().ok())
+                .unwrap_or(Duration::from_secs(60)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SchedulerConfig {
+    pub schedule: Schedule,
+    /// Trading tool calls allowed per cycle; further calls are refused.
+    pub max_actions_per_cycle: usize,
+    pub sub_account_id: Option<u16>,
+    /// Perp markets whose prices and funding go into every prompt, on top of held markets.
+    pub watch_markets: Vec<u16>,
+    /// Standing instruction sent with every snapshot.
+    pub task: String,
+}
+
+impl SchedulerConfig {
+    /// Reads AGENT_SCHEDULE, AGENT_MAX_ACTIONS_PER_CYCLE, AGENT_WATCH_MARKETS and AGENT_TASK_PROMPT.
+    pub fn from_env() -> Result<Self, DriftDataError> {
+        let schedule = env::var("AGENT_SCHEDULE").unwrap_or(DEFAULT_SCHEDULE.to_string()).parse()?;
+        let max_actions_per_cycle = match env::var("AGENT_MAX_ACTIONS_PER_CYCLE") {
+            Ok(value) => value.parse().map_err(|_| DriftDataError {
+                message: format!("Invalid AGENT_MAX_ACTIONS_PER_CYCLE: {}", value)
+            })?,
+            Err(_) => DEFAULT_MAX_ACTIONS_PER_CYCLE,
+        };
+        let watch_markets = match env::var("AGENT_WATCH_MARKETS") {
+            Ok(value) => value
+                .split(',')
+                .map(|index| index.trim().parse().map_err(|_| DriftDataError {
+                    message: format!("Invalid market index in AGENT_WATCH_MARKETS: {}", index)
+                }))
+                .collect::<Result<Vec<u16>, _>>()?,
+            Err(_) => DEFAULT_WATCH_MARKETS.to_vec(),
+        };
+
+        Ok(SchedulerConfig {
+            schedule,
+            max_actions_per_cycle,
+            sub_account_id: None,
+            watch_markets,
+            task: env::var("AGENT_TASK_PROMPT").unwrap_or(DEFAULT_TASK.to_string()),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    /// The prompt sent to the agent.
+    Prompt,
+    /// The agent's final answer for the cycle.
+    Decision,
+    ToolCall,
+    /// A tool call refused because the cycle's action limit was reached.
+    Refused,
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ActionRecord {
+    pub ts: i64,
+    pub cycle: u64,
+    pub kind: ActionKind,
+    pub name: Option<String>,
+    pub input: Value,
+    pub output: Value,
+}
+
+struct CycleState {
+    cycle: u64,
+    actions: usize,
+    max_actions: usize,
+}
+
+/// Append-only JSON lines log of every prompt, decision and tool call, at AGENT_ACTION_LOG
+/// (default `.agent_actions.jsonl`), plus the action budget of the current cycle.
+pub struct ActionLog {
+    path: PathBuf,
+    state: Mutex<CycleState>,
+}
+
+pub fn action_log() -> &'static ActionLog {
+    static ACTION_LOG: OnceLock<ActionLog> = OnceLock::new();
+    ACTION_LOG.get_or_init(|| ActionLog {
+        path: PathBuf::from(env::var("AGENT_ACTION_LOG").unwrap_or(DEFAULT_ACTION_LOG_PATH.to_string())),
+        state: Mutex::new(CycleState { cycle: 0, actions: 0, max_actions: usize::MAX }),
+    })
+}
+
+impl ActionLog {
+    /// Starts a new cycle with a fresh action budget and returns its number.
+    pub fn start_cycle(&self, max_actions: usize) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.cycle += 1;
+        state.actions = 0;
+        state.max_actions = max_actions;
+        state.cycle
+    }
+
+    pub fn current_cycle(&self) -> u64 {
+        self.state.lock().unwrap().cycle
+    }
+
+    /// Takes one action from the current cycle's budget.
+    fn take_action(&self) -> Result<(), DriftDataError> {
+        let mut state = self.state.lock().unwrap();
+        if state.actions >= state.max_actions {
+            return Err(DriftDataError {
+                message: format!(
+                    "Action refused: the limit of {} trading actions for this cycle has been reached. Do not retry until the next cycle.",
+                    state.max_actions
+                ),
+            });
+        }
+        state.actions += 1;
+        Ok(())
+    }
+
+    pub fn record(&self, kind: ActionKind, name: Option<&str>, input: Value, output: Value) {
+        let record = ActionRecord {
+            ts: chrono::Utc::now().timestamp(),
+            cycle: self.current_cycle(),
+            kind,
+            name: name.map(|name| name.to_string()),
+            input,
+            output,
+        };
+        let written = serde_json::to_string(&record).ok().and_then(|line| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .and_then(|mut file| writeln!(file, "{}", line))
+                .ok()
+        });
+        if written.is_none() {
+            eprintln!("Failed to write action log entry to {}", self.path.display());
+        }
+    }
+}
+
+/// Wraps a tool so every call is written to the action log. Trading tools wrapped with
+/// `LoggedTool::action` also count against the cycle's action limit and are refused past it.
+pub struct LoggedTool<T> {
+    tool: T,
+    is_action: bool,
+}
+
+impl<T> LoggedTool<T> {
+    pub fn action(tool: T) -> Self {
+        LoggedTool { tool, is_action: true }
+    }
+
+    pub fn query(tool: T) -> Self {
+        LoggedTool { tool, is_action: false }
+    }
+}
+
+impl<T> Tool for LoggedTool<T>
+where
+    T: Tool<Error = DriftDataError>,
+    T::Args: Serialize,
+{
+    const NAME: &'static str = T::NAME;
+
+    type Error = DriftDataError;
+    type Args = T::Args;
+    type Output = T::Output;
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        self.tool.definition(prompt).await
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let input = serde_json::to_value(&args).unwrap_or(Value::Null);
+        if self.is_action {
+            if let Err(err) = action_log().take_action() {
+                action_log().record(ActionKind::Refused, Some(T::NAME), input, json!(err.to_string()));
+                return Err(err);
+            }
+        }
+
+        let result = self.tool.call(args).await;
+        let output = match &result {
+            Ok(output) => serde_json::to_value(output).unwrap_or(Value::Null),
+            Err(err) => json!({ "error": err.to_string() }),
+        };
+        action_log().record(ActionKind::ToolCall, Some(T::NAME), input, output);
+        result
+    }
+}
+
+/// Portfolio and market context for one cycle: margin metrics and positions for the account,
+/// plus price and funding for every held or watched market.
+pub async fn cycle_snapshot(client: &DriftClient, config: &SchedulerConfig) -> Result<Value, DriftDataError> {
+    let account = get_account_risk_metrics(client, config.sub_account_id).await?;
+
+    let mut markets: Vec<u16> = account.positions.iter().map(|p| p.market_index).chain(config.watch_markets.iter().copied()).collect();
+    markets.sort_unstable();
+    markets.dedup();
+    let mut market_context = Vec::new();
+    for market_index in markets {
+        market_context.push(get_funding_snapshot(client, market_index).await?);
+    }
+
+    let jobs: Vec<Value> = job_store()
+        .and_then(|store| store.running().ok())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|job| json!({ "id": job.id, "kind": job.kind, "args": job.args }))
+        .collect();
+
+    Ok(json!({
+        "ts": chrono::Utc::now().to_rfc3339(),
+        "account": account,
+        "markets": market_context,
+        "running_jobs": jobs,
+    }))
+}
+
+/// Prompts a rig agent on a schedule with a fresh snapshot each cycle, logging every prompt,
+/// tool call and decision, and backing off when the snapshot (RPC) or the LLM fails.
+pub struct Scheduler<M: CompletionModel> {
+    agent: Agent<M>,
+    config: SchedulerConfig,
+}
+
+impl<M: CompletionModel> Scheduler<M> {
+    pub fn new(agent: Agent<M>, config: SchedulerConfig) -> Self {
+        Scheduler { agent, config }
+    }
+
+    /// Runs one cycle, retrying with exponential backoff, and returns the agent's decision.
+    pub async fn run_cycle(&self, client: &DriftClient) -> Result<String, DriftDataError> {
+        let cycle = action_log().start_cycle(self.config.max_actions_per_cycle);
+        let mut attempt = 0;
+        loop {
+            match self.attempt_cycle(client, cycle).await {
+                Ok(decision) => return Ok(decision),
+                Err(err) => {
+                    attempt += 1;
+                    action_log().record(ActionKind::Error, None, json!({ "attempt": attempt }), json!(err.to_string()));
+                    if attempt >= MAX_CYCLE_ATTEMPTS {
+                        return Err(err);
+                    }
+                    let backoff = (BASE_BACKOFF_SECS << (attempt - 1)).min(MAX_BACKOFF_SECS);
+                    eprintln!("Cycle {} attempt {} failed: {}. Retrying in {}s", cycle, attempt, err, backoff);
+                    sleep(Duration::from_secs(backoff)).await;
+                }
+            }
+        }
+    }
+
+    async fn attempt_cycle(&self, client: &DriftClient, cycle: u64) -> Result<String, DriftDataError> {
+        let snapshot = cycle_snapshot(client, &self.config).await?;
+        let prompt = format!(
+            "Scheduled cycle {}. {}\nYou may make at most {} trading tool calls this cycle. Finish with a short summary of what you decided and why.\n\nSnapshot:\n{}",
+            cycle,
+            self.config.task,
+            self.config.max_actions_per_cycle,
+            serde_json::to_string_pretty(&snapshot).unwrap_or_default(),
+        );
+        action_log().record(ActionKind::Prompt, None, json!(prompt), Value::Null);
+
+        let decision = self.agent.prompt(prompt.as_str()).await.map_err(|e| DriftDataError {
+            message: format!("Failed to prompt agent: {}", e)
+        })?;
+        action_log().record(ActionKind::Decision, None, Value::Null, json!(decision));
+        Ok(decision)
+    }
+
+    /// Runs cycles forever, starting immediately and then on every tick of the schedule.
+    pub async fn run(&self, client: &DriftClient) {
+        loop {
+            match self.run_cycle(client).await {
+                Ok(decision) => println!("Cycle {}: {}", action_log().current_cycle(), decision),
+                Err(err) => eprintln!("Cycle {} skipped after {} attempts: {}", action_log().current_cycle(), MAX_CYCLE_ATTEMPTS, err),
+            }
+            sleep(self.config.schedule.next_delay()).await;
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use std::{collections::{HashMap, VecDeque}, env, fs, path::PathBuf, sync::{Arc, Mutex, OnceLock}, time::Duration};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{sync::broadcast, time::sleep};
+
+use rig::{
+    agent::Agent,
+    completion::{CompletionModel, Prompt, ToolDefinition},
+    tool::Tool,
+};
+use drift_rs::{math::constants::PRICE_PRECISION, types::MarketId, DriftClient};
+
+use crate::tools::shared::*;
+use crate::data::perp_market_name;
+
+const DEFAULT_ALERT_STORE_PATH: &str = ".agent_alerts.json";
+const ALERT_POLL_SECS: u64 = 5;
+const ALERT_CHANNEL_CAPACITY: usize = 64;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// Oracle price at or above `price`.
+    Above { price: f64 },
+    /// Oracle price at or below `price`.
+    Below { price: f64 },
+    /// Oracle price moved at least `percent` either way within the last `window_secs`.
+    Move { percent: f64, window_secs: i64 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertAction {
+    /// Prompt the agent, with `prompt` or a generated description of what fired.
+    Prompt { prompt: Option<String> },
+    /// Only emit an `AlertFired` event.
+    Event,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Alert {
+    pub id: String,
+    pub market_index: u16,
+    pub condition: AlertCondition,
+    pub action: AlertAction,
+    /// Keep the alert after it fires; it fires again only once the condition has cleared.
+    pub repeat: bool,
+    pub created_at: i64,
+    pub last_fired_at: Option<i64>,
+    #[serde(default)]
+    pub active: bool,
+}
+
+impl Alert {
+    pub fn describe(&self) -> String {
+        let market = perp_market_name(self.market_index);
+        match &self.condition {
+            AlertCondition::Above { price } => format!("{} oracle above ${}", market, price),
+            AlertCondition::Below { price } => format!("{} oracle below ${}", market, price),
+            AlertCondition::Move { percent, window_secs } => {
+                format!("{} moves {}% within {} minutes", market, percent, window_secs / 60)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AlertFired {
+    pub alert: Alert,
+    pub price: f64,
+    pub ts: i64,
+    pub message: String,
+    /// The prompt to send to the agent for `AlertAction::Prompt` alerts.
+    pub prompt: Option<String>,
+}
+
+/// Registered alerts, persisted as one JSON file at AGENT_ALERT_STORE (default `.agent_alerts.json`).
+pub struct AlertRegistry {
+    path: PathBuf,
+    alerts: Mutex<Vec<Alert>>,
+    /// Whether each repeating alert's condition held on the last check, so it fires on the edge.
+    triggered: Mutex<HashMap<String, bool>>,
+    sender: broadcast::Sender<AlertFired>,
+}
+
+pub fn alert_registry() -> &'static AlertRegistry {
+    static ALERT_REGISTRY: OnceLock<AlertRegistry> = OnceLock::new();
+    ALERT_REGISTRY.get_or_init(|| {
+        let path = PathBuf::from(env::var("AGENT_ALERT_STORE").unwrap_or(DEFAULT_ALERT_STORE_PATH.to_string()));
+        let alerts = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        let (sender, _) = broadcast::channel(ALERT_CHANNEL_CAPACITY);
+        AlertRegistry {
+            path,
+            alerts: Mutex::new(alerts),
+            triggered: Mutex::new(HashMap::new()),
+            sender,
+        }
+    })
+}
+
+impl AlertRegistry {
+    fn persist(&self, alerts: &[Alert]) -> Result<(), DriftDataError> {
+        let contents = serde_json::to_string_pretty(alerts).map_err(|_| DriftDataError {
+            message: "Failed to serialize alerts".to_string()
+        })?;
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, contents)
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .map_err(|e| DriftDataError {
+                message: format!("Failed to write alerts to {}: {}", self.path.display(), e)
+            })
+    }
+
+    pub fn add(&self, market_index: u16, condition: AlertCondition, action: AlertAction, repeat: bool) -> Result<Alert, DriftDataError> {
+        let alert = Alert {
+            id: uuid::Uuid::new_v4().to_string(),
+            market_index,
+            condition,
+            action,
+            repeat,
+            created_at: chrono::Utc::now().timestamp(),
+            last_fired_at: None,
+            active: true,
+        };
+        let mut alerts = self.alerts.lock().unwrap();
+        alerts.push(alert.clone());
+        self.persist(&alerts)?;
+        Ok(alert)
+    }
+
+    pub fn list(&self) -> Vec<Alert> {
+        self.alerts.lock().unwrap().clone()
+    }
+
+    pub fn remove(&self, id: &str) -> Result<Alert, DriftDataError> {
+        let mut alerts = self.alerts.lock().unwrap();
+        let position = alerts.iter().position(|a| a.id == id).ok_or(DriftDataError {
+            message: format!("Unknown alert {}", id)
+        })?;
+        let alert = alerts.remove(position);
+        self.persist(&alerts)?;
+        Ok(alert)
+    }
+
+    /// Receiver for every alert that fires after this call.
+    pub fn subscribe(&self) -> broadcast::Receiver<AlertFired> {
+        self.sender.subscribe()
+    }
+
+    /// Checks every active alert against the latest prices and fires the ones that hit.
+    fn evaluate(&self, history: &HashMap<u16, VecDeque<(i64, f64)>>, now: i64) {
+        let mut fired = Vec::new();
+        {
+            let mut alerts = self.alerts.lock().unwrap();
+            let mut triggered = self.triggered.lock().unwrap();
+            for alert in alerts.iter_mut().filter(|a| a.active) {
+                let Some(prices) = history.get(&alert.market_index) else {
+                    continue;
+                };
+                let Some(&(_, price)) = prices.back() else {
+                    continue;
+                };
+                let hit = match &alert.condition {
+                    AlertCondition::Above { price: target } => price >= *target,
+                    AlertCondition::Below { price: target } => price <= *target,
+                    AlertCondition::Move { percent, window_secs } => prices
+                        .iter()
+                        .find(|(ts, _)| now - ts <= *window_secs)
+                        .map_or(false, |&(_, reference)| reference > 0.0 && ((price - reference) / reference * 100.0).abs() >= *percent),
+                };
+                let was_hit = triggered.insert(alert.id.clone(), hit).unwrap_or(false);
+                if !hit || was_hit {
+                    continue;
+                }
+
+                alert.last_fired_at = Some(now);
+                alert.active = alert.repeat;
+                let message = format!("Alert {} fired: {} (oracle ${:.6})", alert.id, alert.describe(), price);
+                let prompt = match &alert.action {
+                    AlertAction::Prompt { prompt } => Some(match prompt {
+                        Some(prompt) => format!("{}\n{}", message, prompt),
+                        None => message.clone(),
+                    }),
+                    AlertAction::Event => None,
+                };
+                fired.push(AlertFired { alert: alert.clone(), price, ts: now, message, prompt });
+            }
+            if !fired.is_empty() {
+                if let Err(err) = self.persist(&alerts) {
+                    eprintln!("{}", err);
+                }
+            }
+        }
+        for event in fired {
+            tracing::info!("{}", event.message);
+            // No receivers is fine; the alert is still recorded as fired.
+            let _ = self.sender.send(event);
+        }
+    }
+}
+
+/// Starts the background watcher that polls oracle prices for every market with an active
+/// alert and fires alerts through `AlertRegistry::subscribe`.
+pub fn spawn_alert_watcher(client: Arc<DriftClient>) {
+    tokio::spawn(async move {
+        let registry = alert_registry();
+        let mut history: HashMap<u16, VecDeque<(i64, f64)>> = HashMap::new();
+        loop {
+            let alerts = registry.list();
+            let now = chrono::Utc::now().timestamp();
+            let longest_window = alerts.iter().filter_map(|a| match a.condition {
+                AlertCondition::Move { window_secs, .. } => Some(window_secs),
+                _ => None,
+            }).max().unwrap_or(0);
+
+            let mut markets: Vec<u16> = alerts.iter().filter(|a| a.active).map(|a| a.market_index).collect();
+            markets.sort_unstable();
+            markets.dedup();
+            for market_index in markets {
+                let Ok(price) = get_token_oracle_price(MarketId::perp(market_index), &client).await else {
+                    continue;
+                };
+                let prices = history.entry(market_index).or_default();
+                prices.push_back((now, price as f64 / PRICE_PRECISION as f64));
+                while prices.front().map_or(false, |(ts, _)| now - ts > longest_window) {
+                    prices.pop_front();
+                }
+            }
+
+            registry.evaluate(&history, now);
+            sleep(Duration::from_secs(ALERT_POLL_SECS)).await;
+        }
+    });
+}
+
+/// Sends the prompt of every fired `AlertAction::Prompt` alert to `agent` and prints the reply.
+pub fn spawn_alert_prompter<M: CompletionModel + 'static>(agent: Arc<Agent<M>>) {
+    let mut receiver = alert_registry().subscribe();
+    tokio::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(AlertFired { prompt: Some(prompt), .. }) => match agent.prompt(prompt.as_str()).await {
+                    Ok(response) => println!("Agent (alert): {}", response),
+                    Err(err) => eprintln!("Failed to prompt agent for alert: {}", err),
+                },
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftAddAlertArgs {
+    pub market_index: u16,
+    pub condition: String,              // "above", "below" or "move"
+    pub price: Option<f64>,             // Required for above/below
+    pub percent: Option<f64>,           // Required for move
+    pub window_minutes: Option<i64>,    // For move (default 60)
+    pub action: Option<String>,         // "prompt" (default) or "event"
+    pub prompt: Option<String>,
+    pub repeat: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftAddAlert;
+
+impl Tool for DriftAddAlert {
+    const NAME: &'static str = "drift_add_alert";
+
+    type Error = DriftDataError;
+    type Args = DriftAddAlertArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_add_alert".to_string(),
+            description: "Registers a price alert on a Drift perp market's oracle price, e.g. SOL-PERP above 200 or BTC moves 3% in an hour. When it fires the agent is prompted (with an optional instruction) or an event is emitted. Use instead of polling prices.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "market_index": { "type": "integer", "description": "The perp market index to watch." },
+                    "condition": { "type": "string", "enum": ["above", "below", "move"], "description": "Fire when the oracle price is above or below price, or moves by percent within window_minutes." },
+                    "price": { "type": "number", "description": "Price threshold in USD, for above and below." },
+                    "percent": { "type": "number", "description": "Move size in percent either way, for move." },
+                    "window_minutes": { "type": "integer", "description": "Window for move, in minutes (default: 60)." },
+                    "action": { "type": "string", "enum": ["prompt", "event"], "description": "Prompt the agent (default) or only emit an event." },
+                    "prompt": { "type": "string", "description": "Instruction sent to the agent with the alert, e.g. 'close the SOL long' (optional)." },
+                    "repeat": { "type": "boolean", "description": "Keep the alert after it fires (default: false)." }
+                },
+                "required": ["market_index", "condition"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let missing = |field: &str| DriftDataError { message: format!("{} is required for a {} alert", field, args.condition) };
+        let condition = match args.condition.as_str() {
+            "above" => AlertCondition::Above { price: args.price.ok_or_else(|| missing("price"))? },
+            "below" => AlertCondition::Below { price: args.price.ok_or_else(|| missing("price"))? },
+            "move" => AlertCondition::Move {
+                percent: args.percent.ok_or_else(|| missing("percent"))?,
+                window_secs: args.window_minutes.unwrap_or(60) * 60,
+            },
+            other => return Err(DriftDataError { message: format!("Unknown alert condition {}", other) }),
+        };
+        let action = match args.action.as_deref() {
+            Some("event") => AlertAction::Event,
+            _ => AlertAction::Prompt { prompt: args.prompt.clone() },
+        };
+
+        let alert = alert_registry().add(args.market_index, condition, action, args.repeat.unwrap_or(false))?;
+        Ok(format!("Alert {} registered: {}", alert.id, alert.describe()))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftListAlertsArgs {}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftListAlerts;
+
+impl Tool for DriftListAlerts {
+    const NAME: &'static str = "drift_list_alerts";
+
+    type Error = DriftDataError;
+    type Args = DriftListAlertsArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_list_alerts".to_string(),
+            description: "Lists registered price alerts with their ids, conditions and whether they are still active.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        }
+    }
+
+    async fn call(&self, _args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let alerts = alert_registry().list();
+        if alerts.is_empty() {
+            return Ok("No alerts registered.".to_string());
+        }
+        Ok(alerts.iter().map(|alert| format!(
+            "- {}: {} ({}{}{})",
+            alert.id,
+            alert.describe(),
+            if alert.active { "active" } else { "fired" },
+            if alert.repeat { ", repeating" } else { "" },
+            alert.last_fired_at.map_or(String::new(), |ts| format!(
+                ", last fired {}",
+                chrono::DateTime::from_timestamp(ts, 0).map_or(ts.to_string(), |t| t.to_rfc3339())
+            )),
+        )).collect::<Vec<_>>().join("\n"))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftRemoveAlertArgs {
+    pub id: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftRemoveAlert;
+
+impl Tool for DriftRemoveAlert {
+    const NAME: &'static str = "drift_remove_alert";
+
+    type Error = DriftDataError;
+    type Args = DriftRemoveAlertArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_remove_alert".to_string(),
+            description: "Removes a registered price alert by id.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string", "description": "Id of the alert to remove." }
+                },
+                "required": ["id"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let alert = alert_registry().remove(&args.id)?;
+        Ok(format!("Alert {} removed: {}", alert.id, alert.describe()))
+    }
+}
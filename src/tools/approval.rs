@@ -0,0 +1,195 @@
+use std::{collections::HashMap, env, sync::{atomic::{AtomicBool, Ordering}, Mutex, OnceLock}};
+use std::io::{self, Write};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use rig::tool::Tool;
+
+use crate::tools::shared::*;
+use crate::tools::{
+    DriftClosePerpPosition, DriftPlacePerpOrders, DriftTWAPOrders, DriftTrailingStopOrders, DriftVWAPOrders,
+};
+
+const DEFAULT_INTENT_TIMEOUT_SECS: i64 = 300;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IntentStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Expired,
+}
+
+/// An order tool call held back until a human approves it. `args` holds the original
+/// tool arguments so the call can be replayed unchanged.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TradeIntent {
+    pub id: String,
+    pub tool: String,
+    pub summary: String,
+    pub args: Value,
+    pub status: IntentStatus,
+    pub result: Option<String>,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+impl TradeIntent {
+    fn expire_if_due(&mut self, now: i64) {
+        if self.status == IntentStatus::Pending && now >= self.expires_at {
+            self.status = IntentStatus::Expired;
+        }
+    }
+}
+
+fn approval_flag() -> &'static AtomicBool {
+    static APPROVAL_MODE: OnceLock<AtomicBool> = OnceLock::new();
+    APPROVAL_MODE.get_or_init(|| {
+        let enabled = env::var("AGENT_APPROVAL_MODE").map_or(false, |v| v == "1" || v.eq_ignore_ascii_case("true"));
+        AtomicBool::new(enabled)
+    })
+}
+
+/// Whether order tools should return a pending trade intent instead of executing.
+/// Defaults to AGENT_APPROVAL_MODE.
+pub fn approval_mode_enabled() -> bool {
+    approval_flag().load(Ordering::SeqCst)
+}
+
+pub fn set_approval_mode(enabled: bool) {
+    approval_flag().store(enabled, Ordering::SeqCst);
+}
+
+/// Seconds a trade intent waits for a decision, from AGENT_INTENT_TIMEOUT_SECS (default 300).
+pub fn intent_timeout_secs() -> i64 {
+    env::var("AGENT_INTENT_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_INTENT_TIMEOUT_SECS)
+}
+
+fn intents() -> &'static Mutex<HashMap<String, TradeIntent>> {
+    static INTENTS: OnceLock<Mutex<HashMap<String, TradeIntent>>> = OnceLock::new();
+    INTENTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Records a pending intent for `tool` and returns the message handed back to the agent.
+pub fn submit_intent<A: Serialize>(tool: &str, summary: String, args: &A) -> Result<String, DriftDataError> {
+    let now = chrono::Utc::now().timestamp();
+    let intent = TradeIntent {
+        id: uuid::Uuid::new_v4().to_string(),
+        tool: tool.to_string(),
+        summary,
+        args: serde_json::to_value(args).map_err(|_| DriftDataError {
+            message: "Failed to serialize trade intent".to_string()
+        })?,
+        status: IntentStatus::Pending,
+        result: None,
+        created_at: now,
+        expires_at: now + intent_timeout_secs(),
+    };
+    let message = format!(
+        "Trade not executed. Trade intent {} is pending human approval: {}. It expires in {} seconds.",
+        intent.id, intent.summary, intent.expires_at - now
+    );
+    intents().lock().unwrap().insert(intent.id.clone(), intent);
+    Ok(message)
+}
+
+/// All intents, newest first, with overdue pending intents marked expired.
+pub fn list_intents() -> Vec<TradeIntent> {
+    let now = chrono::Utc::now().timestamp();
+    let mut intents = intents().lock().unwrap();
+    let mut list: Vec<TradeIntent> = intents
+        .values_mut()
+        .map(|intent| {
+            intent.expire_if_due(now);
+            intent.clone()
+        })
+        .collect();
+    list.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    list
+}
+
+pub fn pending_intents() -> Vec<TradeIntent> {
+    list_intents().into_iter().filter(|i| i.status == IntentStatus::Pending).collect()
+}
+
+/// Moves a pending intent to `status`, failing if it is unknown, already decided or expired.
+fn decide(id: &str, status: IntentStatus) -> Result<TradeIntent, DriftDataError> {
+    let now = chrono::Utc::now().timestamp();
+    let mut intents = intents().lock().unwrap();
+    let intent = intents.get_mut(id).ok_or(DriftDataError {
+        message: format!("Unknown trade intent {}", id)
+    })?;
+    intent.expire_if_due(now);
+    if intent.status != IntentStatus::Pending {
+        return Err(DriftDataError {
+            message: format!("Trade intent {} is {:?}, not pending", id, intent.status)
+        });
+    }
+    intent.status = status;
+    Ok(intent.clone())
+}
+
+pub fn reject_intent(id: &str) -> Result<TradeIntent, DriftDataError> {
+    decide(id, IntentStatus::Rejected)
+}
+
+/// Approves a pending intent and sends it by replaying the original tool call.
+pub async fn approve_intent(id: &str) -> Result<String, DriftDataError> {
+    let intent = decide(id, IntentStatus::Approved)?;
+    let result = execute_intent(&intent).await;
+
+    let message = match &result {
+        Ok(output) => output.clone(),
+        Err(err) => format!("Error: {}", err),
+    };
+    if let Some(stored) = intents().lock().unwrap().get_mut(id) {
+        stored.result = Some(message);
+    }
+    result
+}
+
+fn parse_args<A: for<'de> Deserialize<'de>>(intent: &TradeIntent) -> Result<A, DriftDataError> {
+    serde_json::from_value(intent.args.clone()).map_err(|_| DriftDataError {
+        message: format!("Failed to parse args of trade intent {}", intent.id)
+    })
+}
+
+async fn execute_intent(intent: &TradeIntent) -> Result<String, DriftDataError> {
+    match intent.tool.as_str() {
+        DriftPlacePerpOrders::NAME => DriftPlacePerpOrders::execute_place_perp_orders(parse_args(intent)?).await,
+        DriftTWAPOrders::NAME => DriftTWAPOrders::execute_twap_orders(parse_args(intent)?).await,
+        DriftVWAPOrders::NAME => DriftVWAPOrders::execute_vwap_orders(parse_args(intent)?).await,
+        DriftTrailingStopOrders::NAME => DriftTrailingStopOrders::execute_trailing_stop_orders(parse_args(intent)?).await,
+        DriftClosePerpPosition::NAME => DriftClosePerpPosition::execute_close_perp_position(parse_args(intent)?).await,
+        tool => Err(DriftDataError {
+            message: format!("Trade intent {} has unsupported tool {}", intent.id, tool)
+        }),
+    }
+}
+
+/// Asks on stdin whether to approve each pending intent, for agents run from a terminal.
+pub async fn prompt_pending_intents() {
+    for intent in pending_intents() {
+        print!("Approve trade intent {} ({})? [y/N] > ", intent.id, intent.summary);
+        io::stdout().flush().unwrap();
+        // Reading stdin blocks, so keep it off the runtime's workers and the background tasks running.
+        let read = tokio::task::spawn_blocking(|| {
+            let mut input = String::new();
+            io::stdin().read_line(&mut input).map(|_| input)
+        });
+        let Ok(Ok(input)) = read.await else {
+            continue;
+        };
+
+        let outcome = if input.trim().eq_ignore_ascii_case("y") {
+            approve_intent(&intent.id).await.unwrap_or_else(|err| format!("Error: {}", err))
+        } else {
+            reject_intent(&intent.id).map_or_else(|err| format!("Error: {}", err), |_| "Rejected".to_string())
+        };
+        println!("Trade intent {}: {}", intent.id, outcome);
+    }
+}
@@ -0,0 +1,291 @@
+use std::str::FromStr;
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use drift_rs::{DriftClient, Pubkey};
+
+use crate::tools::shared::*;
+use crate::tools::vault::*;
+use crate::tools::anchor::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Debug)]
+pub struct VaultDepositor {
+    pub vault: Pubkey,
+    pub pubkey: Pubkey,
+    pub authority: Pubkey,
+    pub vault_shares: u128,
+    pub last_withdraw_request: WithdrawRequest,
+    pub last_valid_ts: i64,
+    pub net_deposits: i64,
+    pub total_deposits: u64,
+    pub total_withdraws: u64,
+    pub cumulative_profit_share_amount: i64,
+    pub profit_share_fee_paid: u64,
+    pub vault_shares_base: u32,
+    pub padding1: u32,
+    pub padding: [u64; 8],
+}
+
+impl AnchorAccount for VaultDepositor {
+    const ACCOUNT_NAME: &'static str = "VaultDepositor";
+    const PROGRAM_ID: Pubkey = DRIFT_VAULTS_PROGRAM_ID;
+    const DATA_SIZE: usize = 264;
+    const SIBLING_ACCOUNTS: &'static [&'static str] = DRIFT_VAULTS_ACCOUNTS;
+}
+
+impl VaultDepositor {
+    /// Shares in the vault's current share base; the depositor's own base lags until its next action.
+    /// A base so far behind that the divisor overflows leaves no shares.
+    pub fn rebased_shares(&self, vault_shares_base: u32) -> u128 {
+        let expo = vault_shares_base.saturating_sub(self.vault_shares_base);
+        10u128.checked_pow(expo).map_or(0, |divisor| self.vault_shares / divisor)
+    }
+}
+
+/// Address of the VaultDepositor account of `authority` in `vault`.
+pub fn vault_depositor_address(vault: &Pubkey, authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"vault_depositor", vault.as_ref(), authority.as_ref()], &DRIFT_VAULTS_PROGRAM_ID).0
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingWithdraw {
+    pub shares: u128,
+    /// Value at the time of the request, in vault tokens.
+    pub value: f64,
+    pub ts: i64,
+    /// Seconds until the request can be completed, 0 if it already can.
+    pub available_in_secs: i64,
+}
+
+/// A vault depositor with token amounts in whole units of the vault's spot market token.
+#[derive(Debug, Clone, Serialize)]
+pub struct DepositorInfo {
+    pub address: String,
+    pub authority: String,
+    pub shares: u128,
+    pub share_percent: f64,
+    pub net_deposits: f64,
+    pub total_deposits: f64,
+    pub total_withdraws: f64,
+    pub current_value: f64,
+    /// Current value minus net deposits.
+    pub pnl: f64,
+    pub profit_share_paid: f64,
+    pub pending_withdraw: Option<PendingWithdraw>,
+}
+
+fn depositor_info(address: &Pubkey, depositor: &VaultDepositor, vault: &VaultInfo) -> DepositorInfo {
+    let token_precision = 10f64.powi(vault.decimals as i32);
+    let tokens = |amount: f64| amount / token_precision;
+    let shares = depositor.rebased_shares(vault.shares_base);
+    let share_fraction = if vault.total_shares > 0 { shares as f64 / vault.total_shares as f64 } else { 0.0 };
+    let current_value = vault.equity * share_fraction;
+    let net_deposits = tokens(depositor.net_deposits as f64);
+    let request = &depositor.last_withdraw_request;
+
+    DepositorInfo {
+        address: address.to_string(),
+        authority: depositor.authority.to_string(),
+        shares,
+        share_percent: share_fraction * 100.0,
+        net_deposits,
+        total_deposits: tokens(depositor.total_deposits as f64),
+        total_withdraws: tokens(depositor.total_withdraws as f64),
+        current_value,
+        pnl: current_value - net_deposits,
+        profit_share_paid: tokens(depositor.profit_share_fee_paid as f64),
+        pending_withdraw: (request.shares > 0).then(|| PendingWithdraw {
+            shares: request.shares,
+            value: tokens(request.value as f64),
+            ts: request.ts,
+            available_in_secs: (request.ts + vault.redeem_period_secs - chrono::Utc::now().timestamp()).max(0),
+        }),
+    }
+}
+
+/// Every depositor of `vault_pubkey`, largest position first.
+pub async fn get_vault_depositors(client: &DriftClient, vault_pubkey: &Pubkey) -> Result<Vec<DepositorInfo>, DriftDataError> {
+    let vault = get_vault_info(client, vault_pubkey).await?;
+
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, anchor_account_discriminator("VaultDepositor").to_vec())),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(8, vault_pubkey.to_bytes().to_vec())),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let accounts = client
+        .rpc()
+        .get_program_accounts_with_config(&DRIFT_VAULTS_PROGRAM_ID, config)
+        .await
+        .map_err(|e| DriftDataError { message: format!("Failed to fetch vault depositors: {}", e) })?;
+
+    let mut depositors = Vec::new();
+    for (address, account) in accounts {
+        let depositor: VaultDepositor = decode_anchor_account(&address, &account.owner, &account.data)?;
+        depositors.push(depositor_info(&address, &depositor, &vault));
+    }
+    depositors.sort_by(|a, b| b.current_value.total_cmp(&a.current_value));
+    Ok(depositors)
+}
+
+pub async fn get_vault_depositor(client: &DriftClient, vault_pubkey: &Pubkey, authority: &Pubkey) -> Result<DepositorInfo, DriftDataError> {
+    let vault = get_vault_info(client, vault_pubkey).await?;
+
+    let address = vault_depositor_address(vault_pubkey, authority);
+    let depositor: VaultDepositor = fetch_anchor_account(&client.rpc(), &address).await.map_err(|e| DriftDataError {
+        message: format!("No depositor account for {} in vault {}: {}", authority, vault_pubkey, e.message)
+    })?;
+    Ok(depositor_info(&address, &depositor, &vault))
+}
+
+fn format_depositor(d: &DepositorInfo) -> String {
+    format!(
+        "- Authority: {}\n  Depositor Account: {}\n  Shares: {} ({:.4}%)\n  Current Value: {:.6}\n  Net Deposits: {:.6}\n  PnL: {:.6}\n  Total Deposits: {:.6}\n  Total Withdraws: {:.6}\n  Profit Share Paid: {:.6}\n  Pending Withdraw: {}\n",
+        d.authority,
+        d.address,
+        d.shares,
+        d.share_percent,
+        d.current_value,
+        d.net_deposits,
+        d.pnl,
+        d.total_deposits,
+        d.total_withdraws,
+        d.profit_share_paid,
+        d.pending_withdraw.as_ref().map_or("none".to_string(), |w| format!(
+            "{} shares ({:.6}), {}",
+            w.shares,
+            w.value,
+            if w.available_in_secs == 0 { "can be completed now".to_string() } else { format!("can be completed in {}s", w.available_in_secs) }
+        )),
+    )
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftVaultDepositorsArgs {
+    pub vault_address: Option<String>,
+    pub limit: Option<usize>,
+    pub format: Option<String>, // "text" (default) or "json"
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftVaultDepositors;
+
+impl Tool for DriftVaultDepositors {
+    const NAME: &'static str = "drift_vault_depositors";
+
+    type Error = DriftDataError;
+    type Args = DriftVaultDepositorsArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_vault_depositors".to_string(),
+            description: "Lists the depositors of a Drift vault, largest first, with their shares, net deposits, current value, PnL and any pending withdraw request.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "vault_address": { "type": "string", "description": "The public key of the vault (optional, defaults to the agent vault)." },
+                    "limit": { "type": "integer", "description": "Maximum number of depositors to list (default: all)." },
+                    "format": { "type": "string", "enum": ["text", "json"], "description": "Output format (default: text)." }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let vault_pubkey = vault_pubkey_or_agent_vault(args.vault_address.as_deref())?;
+        let handle = tokio::task::spawn(async move {
+            let client = init_drift_client().await?;
+            get_vault_depositors(&client, &vault_pubkey).await
+        });
+        let mut depositors = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await get_vault_depositors".to_string()
+        })??;
+
+        let total = depositors.len();
+        depositors.truncate(args.limit.unwrap_or(total));
+        if args.format.as_deref() == Some("json") {
+            return serde_json::to_string(&depositors).map_err(|_| DriftDataError {
+                message: "Failed to serialize vault depositors".to_string()
+            });
+        }
+
+        let mut result = format!("**Depositors of vault {} ({} total):**\n", vault_pubkey, total);
+        if depositors.is_empty() {
+            result.push_str("No depositors.\n");
+        }
+        for depositor in &depositors {
+            result.push_str(&format_depositor(depositor));
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftVaultDepositorArgs {
+    pub authority: String,
+    pub vault_address: Option<String>,
+    pub format: Option<String>, // "text" (default) or "json"
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftVaultDepositor;
+
+impl Tool for DriftVaultDepositor {
+    const NAME: &'static str = "drift_vault_depositor";
+
+    type Error = DriftDataError;
+    type Args = DriftVaultDepositorArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_vault_depositor".to_string(),
+            description: "Looks up one depositor of a Drift vault by the depositor's wallet (authority) and reports shares, net deposits, current value, PnL and any pending withdraw request.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "authority": { "type": "string", "description": "Wallet public key of the depositor." },
+                    "vault_address": { "type": "string", "description": "The public key of the vault (optional, defaults to the agent vault)." },
+                    "format": { "type": "string", "enum": ["text", "json"], "description": "Output format (default: text)." }
+                },
+                "required": ["authority"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let vault_pubkey = vault_pubkey_or_agent_vault(args.vault_address.as_deref())?;
+        let authority = Pubkey::from_str(&args.authority).map_err(|_| DriftDataError {
+            message: format!("Invalid authority {}", args.authority)
+        })?;
+        let handle = tokio::task::spawn(async move {
+            let client = init_drift_client().await?;
+            get_vault_depositor(&client, &vault_pubkey, &authority).await
+        });
+        let depositor = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await get_vault_depositor".to_string()
+        })??;
+
+        if args.format.as_deref() == Some("json") {
+            return serde_json::to_string(&depositor).map_err(|_| DriftDataError {
+                message: "Failed to serialize vault depositor".to_string()
+            });
+        }
+        Ok(format!("**Depositor of vault {}:**\n{}", vault_pubkey, format_depositor(&depositor)))
+    }
+}
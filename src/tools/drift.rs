@@ -12,6 +12,7 @@ use drift_rs::{types::{Context, MarketId, Order, OrderStatus, PerpPosition, Spot
 
 use crate::data::*;
 use crate::tools::shared::*;
+use crate::tools::margin::{position_breakeven_price, position_entry_price};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct DriftInfoArgs {
@@ -93,6 +94,7 @@ pub struct GetOpenPositionsArgs {
     pub account_pubkey: Option<String>,
     pub market_index: Option<u16>,
     pub position_type: Option<String>, // "perp", "spot", or "both" (default)
+    pub sub_account_id: Option<u16>,
 }
 
 pub struct DriftGetOpenPositions;
@@ -112,7 +114,8 @@ impl Tool for DriftGetOpenPositions {
                 "properties": {
                     "account_pubkey": { "type": "string", "description": "Public key of the account (optional, defaults to AGENT_VAULT)" },
                     "market_index": { "type": "integer", "description": "Filter by market index (optional)" },
-                    "position_type": { "type": "string", "enum": ["perp", "spot", "both"], "description": "Type of positions to retrieve (default: both)" }
+                    "position_type": { "type": "string", "enum": ["perp", "spot", "both"], "description": "Type of positions to retrieve (default: both)" },
+                    "sub_account_id": { "type": "integer", "description": "Sub-account to query (optional, defaults to 0)" }
                 }
             }),
         }
@@ -188,7 +191,7 @@ impl DriftGetOpenPositions {
         let client = drift_client.clone();
 
         let handle = tokio::task::spawn(async move {
-            let user = client.get_user_account(&client.wallet().default_sub_account()).await.map_err(|_| DriftDataError { 
+            let user = client.get_user_account(&sub_account_pubkey(client.wallet(), args.sub_account_id)).await.map_err(|_| DriftDataError { 
                 message: "Failed to get_user_account".to_string()
             })?;
             let filter_index = args.market_index;
@@ -237,12 +240,13 @@ impl DriftGetOpenPositions {
                 let unrealized_pnl = p.get_unrealized_pnl(oracle_price).unwrap_or(0);
     
                 results.push(format!(
-                    "- Market: {}\n  Base Amount: {}\n  Quote Amount: ${}\n  Unrealized PnL: ${}\n  Entry: ${}\n  Open Orders: {}\n",
+                    "- Market: {}\n  Base Amount: {}\n  Quote Amount: ${}\n  Unrealized PnL: ${}\n  Entry Price: ${}\n  Breakeven Price: ${}\n  Open Orders: {}\n",
                     parsed_market_data["PERP"][p.market_index.to_string()], 
                     p.base_asset_amount as f64 / 1_000_000_000.0, 
                     p.quote_asset_amount as f64 / 1_000_000.0, 
                     unrealized_pnl as f64 / 1_000_000.0,
-                    p.quote_entry_amount as f64 / 1_000_000.0,
+                    position_entry_price(p),
+                    position_breakeven_price(p),
                     p.open_orders
                 ));
             }
@@ -0,0 +1,753 @@
+use std::{collections::{HashMap, VecDeque}, env, fs, io::{BufRead, BufReader, Write}, path::Path, sync::Arc, time::Duration};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::broadcast, time::{sleep, sleep_until, Instant}};
+
+use drift_rs::{
+    math::{
+        constants::{BASE_PRECISION, PRICE_PRECISION, QUOTE_PRECISION},
+        liquidation::{calculate_collateral, calculate_margin_requirements},
+    },
+    event_subscriber::{DriftEvent, DriftEventStream, EventSubscriber},
+    types::{accounts::User, MarginRequirementType, MarketType, OrderStatus, PositionDirection},
+    DriftClient, Pubkey,
+};
+
+use crate::tools::shared::*;
+
+const DEFAULT_EVENT_POLL_MS: u64 = 250;
+const DEFAULT_LIQUIDATION_WARNING_RATIO: f64 = 1.5;
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+/// Margin health is also rechecked every this many polls, since prices move without account changes.
+const HEALTH_CHECK_EVERY_POLLS: u32 = 20;
+
+/// A change on the agent's Drift account. Sizes are in token units and prices in USD.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccountEvent {
+    OrderPlaced {
+        ts: i64,
+        order_id: u32,
+        user_order_id: u8,
+        market_index: u16,
+        is_perp: bool,
+        is_long: bool,
+        size: f64,
+        price: f64,
+    },
+    /// `filled_size` is the amount filled since the previous event for this order.
+    OrderFilled {
+        ts: i64,
+        order_id: u32,
+        market_index: u16,
+        is_perp: bool,
+        filled_size: f64,
+        fill_price: Option<f64>,
+        fully_filled: bool,
+        /// Transaction of the fill, known when the event came from Drift's fill record.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+    },
+    OrderCancelled {
+        ts: i64,
+        order_id: u32,
+        market_index: u16,
+        is_perp: bool,
+    },
+    PositionChanged {
+        ts: i64,
+        market_index: u16,
+        old_size: f64,
+        new_size: f64,
+    },
+    /// Total collateral has fallen to within `ratio` times the maintenance margin requirement.
+    LiquidationWarning {
+        ts: i64,
+        ratio: f64,
+        total_collateral_usd: f64,
+        maintenance_margin_requirement_usd: f64,
+    },
+}
+
+impl AccountEvent {
+    /// One line description suitable for feeding back into an agent prompt.
+    pub fn describe(&self) -> String {
+        match self {
+            AccountEvent::OrderPlaced { order_id, market_index, is_perp, is_long, size, price, .. } => format!(
+                "Order {} placed: {} {} on {} market {} at {}",
+                order_id, if *is_long { "buy" } else { "sell" }, size,
+                if *is_perp { "perp" } else { "spot" }, market_index,
+                if *price > 0.0 { format!("${:.6}", price) } else { "market".to_string() }
+            ),
+            AccountEvent::OrderFilled { order_id, market_index, filled_size, fill_price, fully_filled, .. } => format!(
+                "Order {} on market {} {} filled {}{}",
+                order_id, market_index, if *fully_filled { "fully" } else { "partially" }, filled_size,
+                fill_price.map_or(String::new(), |price| format!(" at ${:.6}", price))
+            ),
+            AccountEvent::OrderCancelled { order_id, market_index, .. } => {
+                format!("Order {} on market {} cancelled", order_id, market_index)
+            }
+            AccountEvent::PositionChanged { market_index, old_size, new_size, .. } => {
+                format!("Position on perp market {} changed from {} to {}", market_index, old_size, new_size)
+            }
+            AccountEvent::LiquidationWarning { ratio, total_collateral_usd, maintenance_margin_requirement_usd, .. } => format!(
+                "Liquidation warning: collateral ${:.2} is {:.2}x the maintenance margin requirement of ${:.2}",
+                total_collateral_usd, ratio, maintenance_margin_requirement_usd
+            ),
+        }
+    }
+}
+
+/// Derives events from two consecutive snapshots of the same user account.
+pub fn diff_user_accounts(before: &User, after: &User, ts: i64) -> Vec<AccountEvent> {
+    let mut events = Vec::new();
+    let open_orders = |user: &User| -> Vec<drift_rs::types::Order> {
+        user.orders.iter().filter(|o| o.status == OrderStatus::Open).cloned().collect()
+    };
+    let (orders_before, orders_after) = (open_orders(before), open_orders(after));
+    let base_units = |amount: u64| amount as f64 / BASE_PRECISION as f64;
+
+    // Fill price from the quote moved on the position over the base moved, when it is unambiguous.
+    let position_fill_price = |market_index: u16| {
+        let old = before.perp_positions.iter().find(|p| p.market_index == market_index);
+        let new = after.perp_positions.iter().find(|p| p.market_index == market_index);
+        let (old_base, old_quote) = old.map_or((0, 0), |p| (p.base_asset_amount, p.quote_entry_amount));
+        let (new_base, new_quote) = new.map_or((0, 0), |p| (p.base_asset_amount, p.quote_entry_amount));
+        let base = (new_base - old_base) as f64 / BASE_PRECISION as f64;
+        let quote = (new_quote - old_quote) as f64 / QUOTE_PRECISION as f64;
+        // Only opening or adding to a position keeps the entry quote proportional to the fill.
+        if base != 0.0 && (old_base == 0 || old_base.signum() == new_base.signum()) && new_base.abs() > old_base.abs() {
+            Some((quote / base).abs())
+        } else {
+            None
+        }
+    };
+
+    for order in &orders_after {
+        let is_perp = order.market_type == MarketType::Perp;
+        match orders_before.iter().find(|o| o.order_id == order.order_id) {
+            None => {
+                events.push(AccountEvent::OrderPlaced {
+                    ts,
+                    order_id: order.order_id,
+                    user_order_id: order.user_order_id,
+                    market_index: order.market_index,
+                    is_perp,
+                    is_long: order.direction == PositionDirection::Long,
+                    size: base_units(order.base_asset_amount),
+                    price: order.price as f64 / PRICE_PRECISION as f64,
+                });
+                if order.base_asset_amount_filled > 0 {
+                    events.push(AccountEvent::OrderFilled {
+                        ts,
+                        order_id: order.order_id,
+                        market_index: order.market_index,
+                        is_perp,
+                        filled_size: base_units(order.base_asset_amount_filled),
+                        fill_price: if is_perp { position_fill_price(order.market_index) } else { None },
+                        fully_filled: false,
+                        signature: None,
+                    });
+                }
+            }
+            Some(previous) if order.base_asset_amount_filled > previous.base_asset_amount_filled => {
+                events.push(AccountEvent::OrderFilled {
+                    ts,
+                    order_id: order.order_id,
+                    market_index: order.market_index,
+                    is_perp,
+                    filled_size: base_units(order.base_asset_amount_filled - previous.base_asset_amount_filled),
+                    fill_price: if is_perp { position_fill_price(order.market_index) } else { None },
+                    fully_filled: false,
+                    signature: None,
+                });
+            }
+            Some(_) => {}
+        }
+    }
+
+    // An order that left the book filled if the position moved in its market, else it was cancelled.
+    for order in orders_before.iter().filter(|o| !orders_after.iter().any(|a| a.order_id == o.order_id)) {
+        let is_perp = order.market_type == MarketType::Perp;
+        let position_moved = is_perp && {
+            let base = |user: &User| user.perp_positions.iter().find(|p| p.market_index == order.market_index).map_or(0, |p| p.base_asset_amount);
+            base(before) != base(after)
+        };
+        if position_moved {
+            events.push(AccountEvent::OrderFilled {
+                ts,
+                order_id: order.order_id,
+                market_index: order.market_index,
+                is_perp,
+                filled_size: base_units(order.base_asset_amount - order.base_asset_amount_filled),
+                fill_price: position_fill_price(order.market_index),
+                fully_filled: true,
+                signature: None,
+            });
+        } else {
+            events.push(AccountEvent::OrderCancelled { ts, order_id: order.order_id, market_index: order.market_index, is_perp });
+        }
+    }
+
+    let mut market_indexes: Vec<u16> = before.perp_positions.iter().chain(after.perp_positions.iter())
+        .filter(|p| p.base_asset_amount != 0)
+        .map(|p| p.market_index)
+        .collect();
+    market_indexes.sort_unstable();
+    market_indexes.dedup();
+    for market_index in market_indexes {
+        let base = |user: &User| user.perp_positions.iter().find(|p| p.market_index == market_index).map_or(0, |p| p.base_asset_amount);
+        let (old_base, new_base) = (base(before), base(after));
+        if old_base != new_base {
+            events.push(AccountEvent::PositionChanged {
+                ts,
+                market_index,
+                old_size: old_base as f64 / BASE_PRECISION as f64,
+                new_size: new_base as f64 / BASE_PRECISION as f64,
+            });
+        }
+    }
+
+    events
+}
+
+/// Anything that yields account events in order. `None` means the source is exhausted.
+pub trait EventSource: Send {
+    fn next_event(&mut self) -> impl std::future::Future<Output = Option<AccountEvent>> + Send;
+}
+
+/// A liquidation warning if `total_collateral_usd` is within `threshold` times the maintenance
+/// margin requirement. Warns once when crossing the threshold and again only after `warned` has
+/// been cleared by recovering above it.
+pub fn liquidation_warning_event(
+    total_collateral_usd: f64,
+    maintenance_margin_requirement_usd: f64,
+    threshold: f64,
+    warned: &mut bool,
+    ts: i64,
+) -> Option<AccountEvent> {
+    if maintenance_margin_requirement_usd <= 0.0 {
+        *warned = false;
+        return None;
+    }
+    let ratio = total_collateral_usd / maintenance_margin_requirement_usd;
+    if ratio >= threshold {
+        *warned = false;
+        return None;
+    }
+    if *warned {
+        return None;
+    }
+    *warned = true;
+    Some(AccountEvent::LiquidationWarning { ts, ratio, total_collateral_usd, maintenance_margin_requirement_usd })
+}
+
+/// What a fill or cancel record needs to know about one of the account's orders.
+#[derive(Clone, Copy)]
+struct TrackedOrder {
+    market_index: u16,
+    is_perp: bool,
+    size: u64,
+    filled: u64,
+}
+
+/// Live events for one Drift user account. Order placements, fills and cancels come from Drift's
+/// event records, parsed from the program logs of the account's transactions, so fills carry the
+/// exact price and signature. Positions and margin health come from the account, which the Drift
+/// client keeps current over websocket, so each poll reads the local cache rather than the RPC
+/// node. If the record subscription fails, order events are derived from account changes instead.
+pub struct WebsocketEventSource {
+    client: Arc<DriftClient>,
+    user_pubkey: Pubkey,
+    last: Option<User>,
+    pending: VecDeque<AccountEvent>,
+    poll_interval: Duration,
+    next_poll: Instant,
+    records: Option<DriftEventStream>,
+    orders: HashMap<u32, TrackedOrder>,
+    /// Collateral to maintenance margin ratio below which a liquidation warning is raised.
+    liquidation_warning_ratio: f64,
+    warned: bool,
+    polls: u32,
+}
+
+impl WebsocketEventSource {
+    /// Subscribes to the account over websocket. The liquidation warning ratio defaults to
+    /// AGENT_LIQUIDATION_WARNING_RATIO (1.5).
+    pub async fn subscribe(client: Arc<DriftClient>, sub_account_id: Option<u16>) -> Result<Self, DriftDataError> {
+        let user_pubkey = sub_account_pubkey(client.wallet(), sub_account_id);
+        client.subscribe_account(&user_pubkey).await.map_err(|e| DriftDataError {
+            message: format!("Failed to subscribe to user account: {}", e)
+        })?;
+        let liquidation_warning_ratio = env::var("AGENT_LIQUIDATION_WARNING_RATIO")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_LIQUIDATION_WARNING_RATIO);
+        let records = match EventSubscriber::subscribe(client.ws(), user_pubkey).await {
+            Ok(records) => Some(records),
+            Err(e) => {
+                eprintln!("Failed to subscribe to Drift event records, deriving order events from account changes: {}", e);
+                None
+            }
+        };
+
+        let poll_interval = Duration::from_millis(DEFAULT_EVENT_POLL_MS);
+        Ok(WebsocketEventSource {
+            client,
+            user_pubkey,
+            last: None,
+            pending: VecDeque::new(),
+            poll_interval,
+            next_poll: Instant::now() + poll_interval,
+            records,
+            orders: HashMap::new(),
+            liquidation_warning_ratio,
+            warned: false,
+            polls: 0,
+        })
+    }
+
+    fn liquidation_warning(&mut self, user: &User, ts: i64) -> Option<AccountEvent> {
+        let collateral = calculate_collateral(&self.client, user, MarginRequirementType::Maintenance).ok()?;
+        let requirements = calculate_margin_requirements(&self.client, user).ok()?;
+        liquidation_warning_event(
+            collateral.total as f64 / QUOTE_PRECISION as f64,
+            requirements.maintenance as f64 / QUOTE_PRECISION as f64,
+            self.liquidation_warning_ratio,
+            &mut self.warned,
+            ts,
+        )
+    }
+
+    /// This account's order id in a record naming a taker and a maker, if it is either.
+    fn own_order_id(&self, taker: Option<Pubkey>, taker_order_id: u32, maker: Option<Pubkey>, maker_order_id: u32) -> Option<u32> {
+        if taker == Some(self.user_pubkey) {
+            Some(taker_order_id)
+        } else if maker == Some(self.user_pubkey) {
+            Some(maker_order_id)
+        } else {
+            None
+        }
+    }
+
+    fn record_event(&mut self, record: DriftEvent) -> Option<AccountEvent> {
+        match record {
+            DriftEvent::OrderCreate { order, user, ts, .. } if user == self.user_pubkey => {
+                let is_perp = order.market_type == MarketType::Perp;
+                self.orders.insert(order.order_id, TrackedOrder {
+                    market_index: order.market_index,
+                    is_perp,
+                    size: order.base_asset_amount,
+                    filled: order.base_asset_amount_filled,
+                });
+                Some(AccountEvent::OrderPlaced {
+                    ts: ts as i64,
+                    order_id: order.order_id,
+                    user_order_id: order.user_order_id,
+                    market_index: order.market_index,
+                    is_perp,
+                    is_long: order.direction == PositionDirection::Long,
+                    size: order.base_asset_amount as f64 / BASE_PRECISION as f64,
+                    price: order.price as f64 / PRICE_PRECISION as f64,
+                })
+            }
+            DriftEvent::OrderFill {
+                taker, taker_order_id, maker, maker_order_id, market_index, market_type,
+                base_asset_amount_filled, quote_asset_amount_filled, ts, signature, ..
+            } => {
+                let order_id = self.own_order_id(taker, taker_order_id, maker, maker_order_id)?;
+                let tracked = self.orders.entry(order_id).or_insert(TrackedOrder {
+                    market_index,
+                    is_perp: market_type == MarketType::Perp,
+                    size: 0,
+                    filled: 0,
+                });
+                tracked.filled += base_asset_amount_filled;
+                let fully_filled = tracked.size > 0 && tracked.filled >= tracked.size;
+                if fully_filled {
+                    self.orders.remove(&order_id);
+                }
+                let filled_size = base_asset_amount_filled as f64 / BASE_PRECISION as f64;
+                Some(AccountEvent::OrderFilled {
+                    ts: ts as i64,
+                    order_id,
+                    market_index,
+                    is_perp: market_type == MarketType::Perp,
+                    filled_size,
+                    fill_price: (filled_size > 0.0).then(|| quote_asset_amount_filled as f64 / QUOTE_PRECISION as f64 / filled_size),
+                    fully_filled,
+                    signature: Some(signature),
+                })
+            }
+            DriftEvent::OrderCancel { taker, taker_order_id, maker, maker_order_id, ts, .. } => {
+                let order_id = self.own_order_id(taker, taker_order_id, maker, maker_order_id)?;
+                let tracked = self.orders.remove(&order_id)?;
+                Some(AccountEvent::OrderCancelled { ts: ts as i64, order_id, market_index: tracked.market_index, is_perp: tracked.is_perp })
+            }
+            DriftEvent::OrderExpire { order_id, user, ts, .. } if user == Some(self.user_pubkey) => {
+                let tracked = self.orders.remove(&order_id)?;
+                Some(AccountEvent::OrderCancelled { ts: ts as i64, order_id, market_index: tracked.market_index, is_perp: tracked.is_perp })
+            }
+            _ => None,
+        }
+    }
+
+    async fn poll_account(&mut self) {
+        let Ok(user) = self.client.get_user_account(&self.user_pubkey).await else {
+            return;
+        };
+        let ts = chrono::Utc::now().timestamp();
+        self.polls = self.polls.wrapping_add(1);
+
+        // Orders placed before the record subscription, or whose create record was missed.
+        for order in user.orders.iter().filter(|o| o.status == OrderStatus::Open) {
+            self.orders.entry(order.order_id).or_insert(TrackedOrder {
+                market_index: order.market_index,
+                is_perp: order.market_type == MarketType::Perp,
+                size: order.base_asset_amount,
+                filled: order.base_asset_amount_filled,
+            });
+        }
+
+        let mut changes = self.last.as_ref().map(|last| diff_user_accounts(last, &user, ts)).unwrap_or_default();
+        let check_health = !changes.is_empty() || self.polls % HEALTH_CHECK_EVERY_POLLS == 0;
+        if self.records.is_some() {
+            // The records already reported the order events, with exact fill prices.
+            changes.retain(|event| matches!(event, AccountEvent::PositionChanged { .. }));
+        }
+        self.pending.extend(changes);
+        if check_health {
+            if let Some(warning) = self.liquidation_warning(&user, ts) {
+                self.pending.push_back(warning);
+            }
+        }
+        self.last = Some(user);
+    }
+}
+
+impl EventSource for WebsocketEventSource {
+    async fn next_event(&mut self) -> Option<AccountEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+
+            let record = match self.records.as_mut() {
+                Some(records) => tokio::select! {
+                    record = records.next() => Some(record),
+                    _ = sleep_until(self.next_poll) => None,
+                },
+                None => {
+                    sleep_until(self.next_poll).await;
+                    None
+                }
+            };
+            match record {
+                Some(Some(record)) => {
+                    if let Some(event) = self.record_event(record) {
+                        self.pending.push_back(event);
+                    }
+                }
+                Some(None) => {
+                    eprintln!("Drift event record stream ended, deriving order events from account changes");
+                    self.records = None;
+                }
+                None => {}
+            }
+
+            if Instant::now() >= self.next_poll {
+                self.next_poll = Instant::now() + self.poll_interval;
+                self.poll_account().await;
+            }
+        }
+    }
+}
+
+/// Replays events from a JSON lines fixture, one `AccountEvent` per line, for tests and demos.
+pub struct ReplayEventSource {
+    events: VecDeque<AccountEvent>,
+    delay: Duration,
+}
+
+impl ReplayEventSource {
+    pub fn new(events: Vec<AccountEvent>) -> Self {
+        ReplayEventSource { events: events.into(), delay: Duration::ZERO }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, DriftDataError> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).map_err(|e| DriftDataError {
+            message: format!("Failed to open event fixture {}: {}", path.display(), e)
+        })?;
+        let mut events = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| DriftDataError {
+                message: format!("Failed to read event fixture {}: {}", path.display(), e)
+            })?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            events.push(serde_json::from_str(&line).map_err(|e| DriftDataError {
+                message: format!("Invalid event on line {} of {}: {}", number + 1, path.display(), e)
+            })?);
+        }
+        Ok(Self::new(events))
+    }
+
+    /// Waits `delay` before each event, to replay at a watchable pace.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+impl EventSource for ReplayEventSource {
+    async fn next_event(&mut self) -> Option<AccountEvent> {
+        if !self.delay.is_zero() {
+            sleep(self.delay).await;
+        }
+        self.events.pop_front()
+    }
+}
+
+/// Appends events to a JSON lines file that `ReplayEventSource` can read back.
+pub fn write_event_fixture(path: impl AsRef<Path>, events: &[AccountEvent]) -> Result<(), DriftDataError> {
+    let path = path.as_ref();
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path).map_err(|e| DriftDataError {
+        message: format!("Failed to open event fixture {}: {}", path.display(), e)
+    })?;
+    for event in events {
+        let line = serde_json::to_string(event).map_err(|_| DriftDataError {
+            message: "Failed to serialize event".to_string()
+        })?;
+        writeln!(file, "{}", line).map_err(|e| DriftDataError {
+            message: format!("Failed to write event fixture {}: {}", path.display(), e)
+        })?;
+    }
+    Ok(())
+}
+
+type EventCallback = Box<dyn Fn(&AccountEvent) + Send + Sync>;
+
+/// Fans events from a source out to Rust callbacks and to broadcast receivers, such as an
+/// agent loop that turns them into prompts.
+pub struct EventStream {
+    callbacks: Vec<EventCallback>,
+    sender: broadcast::Sender<AccountEvent>,
+}
+
+impl Default for EventStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventStream {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        EventStream { callbacks: Vec::new(), sender }
+    }
+
+    pub fn on_event(mut self, callback: impl Fn(&AccountEvent) + Send + Sync + 'static) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// A receiver for every event emitted after this call.
+    pub fn subscribe(&self) -> broadcast::Receiver<AccountEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Drives `source` until it is exhausted.
+    pub async fn run<S: EventSource>(&self, mut source: S) {
+        while let Some(event) = source.next_event().await {
+            for callback in &self.callbacks {
+                callback(&event);
+            }
+            // No receivers is fine; callbacks may be the only consumers.
+            let _ = self.sender.send(event);
+        }
+    }
+
+    /// Runs the stream on a background task and returns a receiver for its events.
+    pub fn spawn<S: EventSource + 'static>(self, source: S) -> broadcast::Receiver<AccountEvent> {
+        let receiver = self.subscribe();
+        tokio::spawn(async move { self.run(source).await });
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use drift_rs::types::{Order, PerpPosition};
+
+    const TS: i64 = 1_735_689_600;
+
+    fn base(units: f64) -> i64 {
+        (units * BASE_PRECISION as f64) as i64
+    }
+
+    fn quote(usd: f64) -> i64 {
+        (usd * QUOTE_PRECISION as f64) as i64
+    }
+
+    fn open_order(order_id: u32, direction: PositionDirection, size: f64, filled: f64) -> Order {
+        Order {
+            status: OrderStatus::Open,
+            order_id,
+            market_index: 1,
+            market_type: MarketType::Perp,
+            direction,
+            base_asset_amount: base(size) as u64,
+            base_asset_amount_filled: base(filled) as u64,
+            price: 100 * PRICE_PRECISION as u64,
+            ..Default::default()
+        }
+    }
+
+    /// A user with `orders` open and, if given, a position in perp market 1 of `(base units, entry quote USD)`.
+    fn user(orders: &[Order], position: Option<(f64, f64)>) -> User {
+        let mut user = User::default();
+        for (slot, order) in user.orders.iter_mut().zip(orders) {
+            *slot = *order;
+        }
+        if let Some((size, entry_quote)) = position {
+            user.perp_positions[0] = PerpPosition {
+                market_index: 1,
+                base_asset_amount: base(size),
+                quote_entry_amount: quote(entry_quote),
+                ..Default::default()
+            };
+        }
+        user
+    }
+
+    #[test]
+    fn partial_fill_reports_fill_and_position() {
+        let before = user(&[open_order(7, PositionDirection::Long, 2.0, 0.0)], None);
+        let after = user(&[open_order(7, PositionDirection::Long, 2.0, 0.5)], Some((0.5, -50.0)));
+
+        assert_eq!(diff_user_accounts(&before, &after, TS), vec![
+            AccountEvent::OrderFilled {
+                ts: TS, order_id: 7, market_index: 1, is_perp: true,
+                filled_size: 0.5, fill_price: Some(100.0), fully_filled: false, signature: None,
+            },
+            AccountEvent::PositionChanged { ts: TS, market_index: 1, old_size: 0.0, new_size: 0.5 },
+        ]);
+    }
+
+    #[test]
+    fn order_leaving_the_book_with_a_position_change_is_a_full_fill() {
+        let before = user(&[open_order(7, PositionDirection::Long, 2.0, 0.5)], Some((0.5, -50.0)));
+        let after = user(&[], Some((2.0, -203.0)));
+
+        assert_eq!(diff_user_accounts(&before, &after, TS), vec![
+            AccountEvent::OrderFilled {
+                ts: TS, order_id: 7, market_index: 1, is_perp: true,
+                filled_size: 1.5, fill_price: Some(102.0), fully_filled: true, signature: None,
+            },
+            AccountEvent::PositionChanged { ts: TS, market_index: 1, old_size: 0.5, new_size: 2.0 },
+        ]);
+    }
+
+    #[test]
+    fn order_leaving_the_book_without_a_position_change_is_a_cancel() {
+        let before = user(&[open_order(7, PositionDirection::Short, 1.0, 0.0)], Some((2.0, -200.0)));
+        let after = user(&[], Some((2.0, -200.0)));
+
+        assert_eq!(diff_user_accounts(&before, &after, TS), vec![
+            AccountEvent::OrderCancelled { ts: TS, order_id: 7, market_index: 1, is_perp: true },
+        ]);
+    }
+
+    #[test]
+    fn new_order_is_reported_as_placed() {
+        let before = user(&[], None);
+        let after = user(&[open_order(8, PositionDirection::Short, 1.5, 0.0)], None);
+
+        assert_eq!(diff_user_accounts(&before, &after, TS), vec![
+            AccountEvent::OrderPlaced {
+                ts: TS, order_id: 8, user_order_id: 0, market_index: 1, is_perp: true,
+                is_long: false, size: 1.5, price: 100.0,
+            },
+        ]);
+    }
+
+    #[test]
+    fn position_flip_has_no_fill_price() {
+        // Selling 2 against a 1 long closes it and opens a 1 short in the same fill, so the
+        // entry quote no longer says what the fill price was.
+        let before = user(&[open_order(9, PositionDirection::Short, 2.0, 0.0)], Some((1.0, -100.0)));
+        let after = user(&[], Some((-1.0, 101.0)));
+
+        assert_eq!(diff_user_accounts(&before, &after, TS), vec![
+            AccountEvent::OrderFilled {
+                ts: TS, order_id: 9, market_index: 1, is_perp: true,
+                filled_size: 2.0, fill_price: None, fully_filled: true, signature: None,
+            },
+            AccountEvent::PositionChanged { ts: TS, market_index: 1, old_size: 1.0, new_size: -1.0 },
+        ]);
+    }
+
+    #[test]
+    fn unchanged_account_has_no_events() {
+        let account = user(&[open_order(7, PositionDirection::Long, 2.0, 0.0)], Some((1.0, -100.0)));
+        assert!(diff_user_accounts(&account, &account, TS).is_empty());
+    }
+
+    #[test]
+    fn liquidation_warning_fires_once_per_crossing() {
+        let mut warned = false;
+
+        assert_eq!(liquidation_warning_event(200.0, 100.0, 1.5, &mut warned, TS), None);
+        assert_eq!(liquidation_warning_event(120.0, 100.0, 1.5, &mut warned, TS), Some(AccountEvent::LiquidationWarning {
+            ts: TS, ratio: 1.2, total_collateral_usd: 120.0, maintenance_margin_requirement_usd: 100.0,
+        }));
+        // Still below the threshold: already warned.
+        assert_eq!(liquidation_warning_event(110.0, 100.0, 1.5, &mut warned, TS), None);
+        // Recovering re-arms the warning.
+        assert_eq!(liquidation_warning_event(160.0, 100.0, 1.5, &mut warned, TS), None);
+        assert!(liquidation_warning_event(140.0, 100.0, 1.5, &mut warned, TS).is_some());
+    }
+
+    #[test]
+    fn no_liquidation_warning_without_a_margin_requirement() {
+        let mut warned = true;
+        assert_eq!(liquidation_warning_event(0.0, 0.0, 1.5, &mut warned, TS), None);
+        assert!(!warned);
+    }
+
+    #[tokio::test]
+    async fn replayed_fixture_reaches_callbacks_and_receivers_in_order() {
+        let source = ReplayEventSource::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/fixtures/account_events.jsonl")).unwrap();
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let callback_seen = seen.clone();
+        let stream = EventStream::new().on_event(move |event| callback_seen.lock().unwrap().push(event.clone()));
+        let mut receiver = stream.subscribe();
+
+        stream.run(source).await;
+
+        let mut received = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            received.push(event);
+        }
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(seen, received);
+
+        let kinds: Vec<String> = seen
+            .iter()
+            .map(|event| serde_json::to_value(event).unwrap()["type"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(kinds, [
+            "order_placed", "order_filled", "position_changed",
+            "order_placed", "liquidation_warning", "order_cancelled",
+            "order_placed", "order_filled", "position_changed",
+        ]);
+        assert_eq!(seen[1], AccountEvent::OrderFilled {
+            ts: TS + 1, order_id: 101, market_index: 0, is_perp: true,
+            filled_size: 2.0, fill_price: Some(189.42), fully_filled: true, signature: None,
+        });
+        assert_eq!(seen[8], AccountEvent::PositionChanged { ts: TS + 3607, market_index: 0, old_size: 2.0, new_size: 0.0 });
+    }
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use drift_rs::{
+    math::constants::BASE_PRECISION,
+    types::{MarketId, OrderStatus},
+    DriftClient, Pubkey,
+};
+
+use crate::tools::shared::*;
+
+/// Close orders per transaction. Each closed market adds its market and oracle accounts, so a
+/// sub-account with many positions cannot be flattened in a single transaction.
+const MAX_CLOSES_PER_TRANSACTION: usize = 4;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClosedPosition {
+    pub market_index: u16,
+    pub base_asset_amount: i64,
+}
+
+/// One transaction of a sub-account's flatten: the first also cancels every open order.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlattenTransaction {
+    pub cancels_orders: bool,
+    pub market_indexes: Vec<u16>,
+    pub signature: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubAccountFlattenResult {
+    pub sub_account_id: u16,
+    pub user: String,
+    pub cancelled_orders: usize,
+    /// Positions closed by transactions that landed.
+    pub closed_positions: Vec<ClosedPosition>,
+    pub transactions: Vec<FlattenTransaction>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlattenReport {
+    pub stopped_jobs: Vec<String>,
+    pub sub_accounts: Vec<SubAccountFlattenResult>,
+}
+
+impl fmt::Display for FlattenReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Stopped jobs: {}", if self.stopped_jobs.is_empty() { "none".to_string() } else { self.stopped_jobs.join(", ") })?;
+        for sub_account in &self.sub_accounts {
+            writeln!(f, "- Sub-account {} ({}):", sub_account.sub_account_id, sub_account.user)?;
+            writeln!(f, "  Cancelled Orders: {}", sub_account.cancelled_orders)?;
+            if sub_account.closed_positions.is_empty() {
+                writeln!(f, "  Closed Positions: none")?;
+            }
+            for position in &sub_account.closed_positions {
+                writeln!(
+                    f,
+                    "  Closed Position: market {} base {}",
+                    position.market_index,
+                    position.base_asset_amount as f64 / BASE_PRECISION as f64
+                )?;
+            }
+            for (number, transaction) in sub_account.transactions.iter().enumerate() {
+                let mut contents: Vec<String> = transaction.market_indexes.iter().map(|index| format!("close market {}", index)).collect();
+                if transaction.cancels_orders {
+                    contents.insert(0, "cancel all orders".to_string());
+                }
+                match (&transaction.signature, &transaction.error) {
+                    (Some(signature), _) => writeln!(f, "  Transaction {} ({}): {}", number + 1, contents.join(", "), signature)?,
+                    (None, Some(error)) => writeln!(f, "  Transaction {} ({}) failed: {}", number + 1, contents.join(", "), error)?,
+                    (None, None) => {}
+                }
+            }
+            if let Some(error) = &sub_account.error {
+                writeln!(f, "  Error: {}", error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Kill switch: for each sub-account of the vault (or only `only_sub_account` if given) stops
+/// the algorithm jobs trading it, cancels all open orders and closes every perp position with
+/// reduce-only market orders.
+pub async fn flatten_all(client: &DriftClient, stop_jobs: bool, only_sub_account: Option<u16>) -> Result<FlattenReport, DriftDataError> {
+    // Stop jobs first so a TWAP slice or trailing stop does not re-open what we are closing.
+    let stopped_jobs = if stop_jobs { crate::tools::jobs::stop_jobs(only_sub_account) } else { Vec::new() };
+
+    let mut sub_accounts = Vec::new();
+    for (sub_account_id, user_pubkey) in list_sub_account_pubkeys(client).await? {
+        if only_sub_account.map_or(false, |id| id != sub_account_id) {
+            continue;
+        }
+        sub_accounts.push(flatten_sub_account(client, sub_account_id, user_pubkey).await);
+    }
+
+    Ok(FlattenReport { stopped_jobs, sub_accounts })
+}
+
+/// Cancels the sub-account's orders and closes its positions, MAX_CLOSES_PER_TRANSACTION
+/// positions per transaction. A failed transaction does not stop the rest from being sent.
+async fn flatten_sub_account(client: &DriftClient, sub_account_id: u16, user_pubkey: Pubkey) -> SubAccountFlattenResult {
+    let mut result = SubAccountFlattenResult {
+        sub_account_id,
+        user: user_pubkey.to_string(),
+        cancelled_orders: 0,
+        closed_positions: Vec::new(),
+        transactions: Vec::new(),
+        error: None,
+    };
+
+    let user = match client.get_user_account(&user_pubkey).await {
+        Ok(user) => user,
+        Err(e) => {
+            result.error = Some(format!("Failed to get_user_account: {}", e));
+            return result;
+        }
+    };
+
+    let open_orders = user.orders.iter().filter(|o| o.status == OrderStatus::Open).count();
+    let positions: Vec<ClosedPosition> = user
+        .perp_positions
+        .iter()
+        .filter(|p| p.base_asset_amount != 0)
+        .map(|p| ClosedPosition { market_index: p.market_index, base_asset_amount: p.base_asset_amount })
+        .collect();
+    if open_orders == 0 && positions.is_empty() {
+        return result;
+    }
+
+    let mut chunks: Vec<&[ClosedPosition]> = positions.chunks(MAX_CLOSES_PER_TRANSACTION).collect();
+    if chunks.is_empty() {
+        // Orders to cancel but nothing to close.
+        chunks.push(&[]);
+    }
+    for (number, chunk) in chunks.into_iter().enumerate() {
+        let cancels_orders = number == 0 && open_orders > 0;
+        let close_orders: Vec<_> = chunk
+            .iter()
+            .map(|position| {
+                drift_rs::types::NewOrder::market(MarketId::perp(position.market_index))
+                    .amount(-position.base_asset_amount)
+                    .reduce_only(true)
+                    .build()
+            })
+            .collect();
+
+        let mut builder = drift_rs::TransactionBuilder::new(
+            client.program_data(),
+            user_pubkey,
+            std::borrow::Cow::Borrowed(&user),
+            true,
+        );
+        if cancels_orders {
+            builder = builder.cancel_all_orders();
+        }
+        let tx = builder.place_orders(close_orders).build();
+
+        let mut transaction = FlattenTransaction {
+            cancels_orders,
+            market_indexes: chunk.iter().map(|position| position.market_index).collect(),
+            signature: None,
+            error: None,
+        };
+        match client.sign_and_send(tx).await {
+            Ok(signature) => {
+                transaction.signature = Some(signature.to_string());
+                if cancels_orders {
+                    result.cancelled_orders = open_orders;
+                }
+                result.closed_positions.extend(chunk.iter().cloned());
+            }
+            Err(e) => transaction.error = Some(format!("Failed to send flatten transaction: {}", e)),
+        }
+        result.transactions.push(transaction);
+    }
+    result
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftFlattenAllArgs {
+    pub stop_jobs: Option<bool>,
+    pub sub_account_id: Option<u16>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftFlattenAll;
+
+impl Tool for DriftFlattenAll {
+    const NAME: &'static str = "drift_flatten_all";
+
+    type Error = DriftDataError;
+    type Args = DriftFlattenAllArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_flatten_all".to_string(),
+            description: "Emergency kill switch. On every sub-account of the vault (or only the given one) stops the running TWAP/VWAP/trailing stop jobs, cancels every open order and closes every perp position with reduce-only market orders. Only use when the user explicitly asks to flatten, close everything, or stop all trading.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "stop_jobs": { "type": "boolean", "description": "Whether to stop running algorithm jobs (default: true)." },
+                    "sub_account_id": { "type": "integer", "description": "Only flatten this sub-account (optional, defaults to every sub-account)." }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let handle = tokio::task::spawn(async move {
+            let client = init_drift_client().await?;
+            flatten_all(&client, args.stop_jobs.unwrap_or(true), args.sub_account_id).await
+        });
+        let report = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await flatten_all".to_string()
+        })??;
+
+        Ok(report.to_string())
+    }
+}
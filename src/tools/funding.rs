@@ -0,0 +1,253 @@
+use std::env;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use drift_rs::{
+    math::constants::{BASE_PRECISION, FUNDING_RATE_PRECISION, PRICE_PRECISION, QUOTE_PRECISION},
+    types::MarketId,
+    DriftClient,
+};
+
+use crate::data::*;
+use crate::tools::shared::*;
+use crate::tools::margin::position_unsettled_funding;
+
+const DEFAULT_DRIFT_DATA_API_URL: &str = "https://data.api.drift.trade";
+const HOURS_PER_YEAR: f64 = 24.0 * 365.0;
+/// Markets reported when none is requested and the account holds no positions.
+const DEFAULT_FUNDING_MARKETS: [u16; 3] = [0, 1, 2];
+
+/// Base URL of the Drift data API, from DRIFT_DATA_API_URL.
+pub fn drift_data_api_url() -> String {
+    env::var("DRIFT_DATA_API_URL").unwrap_or(DEFAULT_DRIFT_DATA_API_URL.to_string())
+}
+
+/// Converts a funding rate in quote per base (funding rate precision) into an hourly percent of price.
+fn funding_rate_percent(funding_rate: f64, oracle_price_twap: f64) -> f64 {
+    if oracle_price_twap <= 0.0 {
+        return 0.0;
+    }
+    funding_rate / FUNDING_RATE_PRECISION as f64 / (oracle_price_twap / PRICE_PRECISION as f64) * 100.0
+}
+
+/// Funding rates are hourly percents of price; positive means longs pay shorts.
+#[derive(Debug, Clone, Serialize)]
+pub struct FundingSnapshot {
+    pub market_index: u16,
+    pub market_name: String,
+    pub oracle_price: f64,
+    pub last_funding_rate_percent: f64,
+    pub last_funding_rate_annualized_percent: f64,
+    /// Estimated from the current mark and oracle TWAPs, before any protocol clamps.
+    pub predicted_funding_rate_percent: f64,
+    pub predicted_funding_rate_annualized_percent: f64,
+    pub last_funding_ts: i64,
+    pub next_funding_ts: i64,
+}
+
+pub async fn get_funding_snapshot(client: &DriftClient, market_index: u16) -> Result<FundingSnapshot, DriftDataError> {
+    let market = client.get_perp_market_account(market_index).await.map_err(|_| DriftDataError {
+        message: format!("Failed to get perp market {}", market_index)
+    })?;
+    let oracle_price = get_token_oracle_price(MarketId::perp(market_index), client).await? as f64 / PRICE_PRECISION as f64;
+
+    let amm = &market.amm;
+    let oracle_twap = amm.historical_oracle_data.last_oracle_price_twap as f64;
+    let mark_twap = amm.last_mark_price_twap as f64;
+    let last_funding_rate_percent = funding_rate_percent(amm.last_funding_rate as f64, oracle_twap);
+    // Drift charges the mark/oracle TWAP spread over a day, one funding period at a time.
+    let periods_per_day = 86_400.0 / amm.funding_period.max(1) as f64;
+    let predicted_funding_rate_percent = if oracle_twap > 0.0 {
+        (mark_twap - oracle_twap) / oracle_twap / periods_per_day * 100.0
+    } else {
+        0.0
+    };
+
+    Ok(FundingSnapshot {
+        market_index,
+        market_name: perp_market_name(market_index),
+        oracle_price,
+        last_funding_rate_percent,
+        last_funding_rate_annualized_percent: last_funding_rate_percent * HOURS_PER_YEAR,
+        predicted_funding_rate_percent,
+        predicted_funding_rate_annualized_percent: predicted_funding_rate_percent * HOURS_PER_YEAR,
+        last_funding_ts: amm.last_funding_rate_ts,
+        next_funding_ts: amm.last_funding_rate_ts + amm.funding_period,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FundingRecord {
+    pub ts: i64,
+    pub funding_rate_percent: f64,
+}
+
+/// Most recent funding payments for a perp market from the Drift data API, newest first.
+pub async fn get_funding_history(market_index: u16, limit: usize) -> Result<Vec<FundingRecord>, DriftDataError> {
+    let url = format!("{}/fundingRates", drift_data_api_url());
+    let response: Value = reqwest::Client::new()
+        .get(&url)
+        .query(&[("marketName", perp_market_name(market_index))])
+        .send()
+        .await
+        .map_err(|e| DriftDataError { message: format!("Failed to fetch funding history: {}", e) })?
+        .json()
+        .await
+        .map_err(|e| DriftDataError { message: format!("Failed to parse funding history: {}", e) })?;
+
+    let mut records: Vec<FundingRecord> = response["fundingRates"]
+        .as_array()
+        .map(|rates| {
+            rates.iter().filter_map(|rate| {
+                Some(FundingRecord {
+                    ts: value_as_f64(&rate["ts"])? as i64,
+                    funding_rate_percent: funding_rate_percent(
+                        value_as_f64(&rate["fundingRate"])?,
+                        value_as_f64(&rate["oraclePriceTwap"])?,
+                    ),
+                })
+            }).collect()
+        })
+        .unwrap_or_default();
+    records.sort_by(|a, b| b.ts.cmp(&a.ts));
+    records.truncate(limit);
+    Ok(records)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionFunding {
+    pub market_index: u16,
+    pub market_name: String,
+    pub base_asset_amount: f64,
+    /// Funding accrued since the position was last settled; positive is owed to us.
+    pub unsettled_funding_usd: f64,
+}
+
+pub async fn get_position_funding(client: &DriftClient, sub_account_id: Option<u16>) -> Result<Vec<PositionFunding>, DriftDataError> {
+    let user = client
+        .get_user_account(&sub_account_pubkey(client.wallet(), sub_account_id))
+        .await
+        .map_err(|_| DriftDataError {
+            message: "Failed to get_user_account".to_string()
+        })?;
+
+    let mut positions = Vec::new();
+    for position in user.perp_positions.iter().filter(|p| p.base_asset_amount != 0) {
+        let market = client.get_perp_market_account(position.market_index).await.map_err(|_| DriftDataError {
+            message: format!("Failed to get perp market {}", position.market_index)
+        })?;
+        positions.push(PositionFunding {
+            market_index: position.market_index,
+            market_name: perp_market_name(position.market_index),
+            base_asset_amount: position.base_asset_amount as f64 / BASE_PRECISION as f64,
+            unsettled_funding_usd: position_unsettled_funding(position, &market) as f64 / QUOTE_PRECISION as f64,
+        });
+    }
+    Ok(positions)
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftFundingRatesArgs {
+    pub market_index: Option<u16>,
+    pub history_limit: Option<usize>,
+    pub sub_account_id: Option<u16>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftFundingRates;
+
+impl Tool for DriftFundingRates {
+    const NAME: &'static str = "drift_funding_rates";
+
+    type Error = DriftDataError;
+    type Args = DriftFundingRatesArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_funding_rates".to_string(),
+            description: "Returns the current and predicted hourly funding rate of Drift perp markets, their recent funding history, and the funding accrued on our open positions. Positive funding means longs pay shorts. Use it to weigh the carry cost of holding a perp.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "market_index": { "type": "integer", "description": "Perp market to report (optional, defaults to markets with open positions, or SOL, BTC and ETH)." },
+                    "history_limit": { "type": "integer", "description": "Number of past hourly funding payments to include per market (default: 24, 0 for none)." },
+                    "sub_account_id": { "type": "integer", "description": "Sub-account whose accrued funding is reported (optional, defaults to 0)." }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let history_limit = args.history_limit.unwrap_or(24);
+        let handle = tokio::task::spawn(async move {
+            let client = init_drift_client().await?;
+            let positions = get_position_funding(&client, args.sub_account_id).await?;
+
+            let market_indexes: Vec<u16> = match args.market_index {
+                Some(market_index) => vec![market_index],
+                None if !positions.is_empty() => positions.iter().map(|p| p.market_index).collect(),
+                None => DEFAULT_FUNDING_MARKETS.to_vec(),
+            };
+
+            let mut markets = Vec::new();
+            for market_index in market_indexes {
+                let snapshot = get_funding_snapshot(&client, market_index).await?;
+                // History is best effort; the on-chain rates are still useful without it.
+                let history = if history_limit > 0 {
+                    get_funding_history(market_index, history_limit).await.unwrap_or_default()
+                } else {
+                    Vec::new()
+                };
+                markets.push((snapshot, history));
+            }
+            Ok::<_, DriftDataError>((markets, positions))
+        });
+        let (markets, positions) = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await funding rates".to_string()
+        })??;
+
+        let mut result = String::new();
+        for (snapshot, history) in &markets {
+            result.push_str(&format!(
+                "**{} (market {}):**\n- Oracle Price: ${:.6}\n- Last Funding Rate: {:.6}% per hour ({:.2}% APR)\n- Predicted Funding Rate: {:.6}% per hour ({:.2}% APR)\n- Next Funding: {}\n",
+                snapshot.market_name,
+                snapshot.market_index,
+                snapshot.oracle_price,
+                snapshot.last_funding_rate_percent,
+                snapshot.last_funding_rate_annualized_percent,
+                snapshot.predicted_funding_rate_percent,
+                snapshot.predicted_funding_rate_annualized_percent,
+                chrono::DateTime::from_timestamp(snapshot.next_funding_ts, 0).map_or("unknown".to_string(), |t| t.to_rfc3339()),
+            ));
+            if !history.is_empty() {
+                let average = history.iter().map(|r| r.funding_rate_percent).sum::<f64>() / history.len() as f64;
+                result.push_str(&format!("- Average of last {} payments: {:.6}% per hour\n- History:\n", history.len(), average));
+                for record in history {
+                    result.push_str(&format!(
+                        "  {}: {:.6}%\n",
+                        chrono::DateTime::from_timestamp(record.ts, 0).map_or(record.ts.to_string(), |t| t.to_rfc3339()),
+                        record.funding_rate_percent
+                    ));
+                }
+            }
+            result.push('\n');
+        }
+
+        result.push_str("**Accrued Funding On Open Positions:**\n");
+        if positions.is_empty() {
+            result.push_str("No open perpetual positions.\n");
+        }
+        for position in &positions {
+            result.push_str(&format!(
+                "- {}: base {} unsettled funding ${:.6}\n",
+                position.market_name, position.base_asset_amount, position.unsettled_funding_usd
+            ));
+        }
+        Ok(result)
+    }
+}
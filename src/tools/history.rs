@@ -0,0 +1,432 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use drift_rs::{math::constants::BASE_PRECISION, Pubkey};
+
+use crate::tools::shared::*;
+use crate::data::perp_market_name;
+use crate::tools::funding::drift_data_api_url;
+
+const DEFAULT_FILL_LIMIT: usize = 50;
+const MAX_HISTORY_PAGES: usize = 20;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Fill {
+    pub ts: i64,
+    pub market_index: u16,
+    pub market_name: String,
+    /// "long" (buy) or "short" (sell) from our side of the trade.
+    pub side: String,
+    pub size: f64,
+    pub price: f64,
+    /// Fee paid in USD; negative is a rebate.
+    pub fee: f64,
+    pub is_taker: bool,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FillFilter {
+    pub market_index: Option<u16>,
+    pub start_ts: Option<i64>,
+    pub end_ts: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+impl FillFilter {
+    fn matches(&self, fill: &Fill) -> bool {
+        self.market_index.map_or(true, |index| index == fill.market_index)
+            && self.start_ts.map_or(true, |start| fill.ts >= start)
+            && self.end_ts.map_or(true, |end| fill.ts <= end)
+    }
+}
+
+/// Parses a perp trade record from the data API, whose amounts are already in token units.
+fn parse_fill(record: &Value, user: &str) -> Option<Fill> {
+    if record["marketType"].as_str().map_or(false, |t| t != "perp") {
+        return None;
+    }
+    let is_taker = record["taker"].as_str() == Some(user);
+    let (direction, fee) = if is_taker {
+        (&record["takerOrderDirection"], &record["takerFee"])
+    } else {
+        (&record["makerOrderDirection"], &record["makerFee"])
+    };
+    let size = value_as_f64(&record["baseAssetAmountFilled"])?;
+    let quote = value_as_f64(&record["quoteAssetAmountFilled"])?;
+    let market_index = value_as_f64(&record["marketIndex"])? as u16;
+
+    Some(Fill {
+        ts: value_as_f64(&record["ts"])? as i64,
+        market_index,
+        market_name: perp_market_name(market_index),
+        side: direction.as_str().unwrap_or_default().to_string(),
+        size,
+        price: if size > 0.0 { quote / size } else { 0.0 },
+        fee: value_as_f64(fee).unwrap_or(0.0),
+        is_taker,
+        signature: record["txSig"].as_str().unwrap_or_default().to_string(),
+    })
+}
+
+/// Recent perp fills of `user` from the Drift data API (DRIFT_DATA_API_URL), newest first.
+pub async fn get_fills(user: &Pubkey, filter: &FillFilter) -> Result<Vec<Fill>, DriftDataError> {
+    let (fills, _) = fetch_fills(user, filter, filter.limit.unwrap_or(DEFAULT_FILL_LIMIT)).await?;
+    Ok(fills)
+}
+
+/// Every perp fill of `user` matching `filter`, ignoring its limit, newest first. The flag is
+/// false if the history was cut off at MAX_HISTORY_PAGES before reaching `start_ts` (or the
+/// first trade), in which case the fills only cover part of the range.
+pub async fn get_all_fills(user: &Pubkey, filter: &FillFilter) -> Result<(Vec<Fill>, bool), DriftDataError> {
+    fetch_fills(user, filter, usize::MAX).await
+}
+
+async fn fetch_fills(user: &Pubkey, filter: &FillFilter, limit: usize) -> Result<(Vec<Fill>, bool), DriftDataError> {
+    let user = user.to_string();
+    let url = format!("{}/user/{}/trades", drift_data_api_url(), user);
+    let http = reqwest::Client::new();
+
+    let mut fills = Vec::new();
+    let mut page: Option<String> = None;
+    let mut complete = false;
+    for _ in 0..MAX_HISTORY_PAGES {
+        let mut request = http.get(&url);
+        if let Some(page) = &page {
+            request = request.query(&[("page", page)]);
+        }
+        let response: Value = request
+            .send()
+            .await
+            .map_err(|e| DriftDataError { message: format!("Failed to fetch trade history: {}", e) })?
+            .json()
+            .await
+            .map_err(|e| DriftDataError { message: format!("Failed to parse trade history: {}", e) })?;
+
+        let records = response["records"].as_array().cloned().unwrap_or_default();
+        let mut reached_start = false;
+        for fill in records.iter().filter_map(|record| parse_fill(record, &user)) {
+            if filter.start_ts.map_or(false, |start| fill.ts < start) {
+                reached_start = true;
+                continue;
+            }
+            if filter.matches(&fill) {
+                fills.push(fill);
+            }
+        }
+
+        page = response["meta"]["nextPage"].as_str().map(|p| p.to_string());
+        complete = reached_start || records.is_empty() || page.is_none();
+        if fills.len() >= limit || complete {
+            break;
+        }
+    }
+
+    fills.sort_by(|a, b| b.ts.cmp(&a.ts));
+    fills.truncate(limit);
+    Ok((fills, complete))
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MarketPnl {
+    pub market_index: u16,
+    pub market_name: String,
+    pub fills: usize,
+    pub volume_usd: f64,
+    pub fees_usd: f64,
+    /// Realized trading PnL after fees, from average cost over the fills given.
+    pub realized_pnl_usd: f64,
+    /// Position left open by the fills given, in token units, including any held before them.
+    pub net_size: f64,
+    /// Size closed against a position held before the first fill, in token units. Its entry
+    /// price is not in the fills, so the PnL of closing it is left out of `realized_pnl_usd`.
+    pub unmatched_size: f64,
+}
+
+/// Sizes below this are float noise from summing fills.
+const SIZE_EPSILON: f64 = 1e-9;
+
+/// Position of each market before `fills`, from the position held after them. `fills` must be
+/// every fill of the account from the first one given up to when `sizes_after` was read.
+pub fn sizes_before_fills(fills: &[Fill], sizes_after: &BTreeMap<u16, f64>) -> BTreeMap<u16, f64> {
+    let mut sizes = sizes_after.clone();
+    for fill in fills {
+        let signed_size = if fill.side == "short" { -fill.size } else { fill.size };
+        *sizes.entry(fill.market_index).or_insert(0.0) -= signed_size;
+    }
+    sizes.retain(|_, size| size.abs() > SIZE_EPSILON);
+    sizes
+}
+
+/// Realized PnL per market from a set of fills, using average cost. `sizes_before` is the
+/// position held before the first fill: fills that close it are counted in `unmatched_size`
+/// rather than realized, since its entry price is unknown. Without it every position is taken
+/// to start flat, so a fill closing an older position is treated as opening a new one.
+pub fn realized_pnl_by_market(fills: &[Fill], sizes_before: Option<&BTreeMap<u16, f64>>) -> Vec<MarketPnl> {
+    // Per market: totals, average entry of the position opened by the fills, and the part of
+    // the position held from before the first fill.
+    let mut markets: BTreeMap<u16, (MarketPnl, f64, f64)> = BTreeMap::new();
+
+    let mut ordered: Vec<&Fill> = fills.iter().collect();
+    ordered.sort_by_key(|fill| fill.ts);
+    for fill in ordered {
+        let (pnl, average_price, held_before) = markets.entry(fill.market_index).or_insert_with(|| (
+            MarketPnl {
+                market_index: fill.market_index,
+                market_name: fill.market_name.clone(),
+                ..Default::default()
+            },
+            0.0,
+            sizes_before.and_then(|sizes| sizes.get(&fill.market_index)).copied().unwrap_or(0.0),
+        ));
+        let mut signed_size = if fill.side == "short" { -fill.size } else { fill.size };
+        pnl.fills += 1;
+        pnl.volume_usd += fill.size * fill.price;
+        pnl.fees_usd += fill.fee;
+        pnl.realized_pnl_usd -= fill.fee;
+
+        if *held_before != 0.0 && held_before.signum() != signed_size.signum() {
+            // The older position closes first; whatever is left of the fill trades as usual.
+            let unmatched = signed_size.abs().min(held_before.abs());
+            pnl.unmatched_size += unmatched;
+            *held_before -= unmatched * held_before.signum();
+            signed_size -= unmatched * signed_size.signum();
+            if held_before.abs() <= SIZE_EPSILON {
+                *held_before = 0.0;
+            }
+            if signed_size.abs() <= SIZE_EPSILON {
+                continue;
+            }
+        }
+
+        if pnl.net_size == 0.0 || pnl.net_size.signum() == signed_size.signum() {
+            // Adding to (or opening) a position moves the average entry.
+            let new_size = pnl.net_size + signed_size;
+            *average_price = (*average_price * pnl.net_size.abs() + fill.price * signed_size.abs()) / new_size.abs();
+            pnl.net_size = new_size;
+        } else {
+            let closed = signed_size.abs().min(pnl.net_size.abs());
+            pnl.realized_pnl_usd += closed * (fill.price - *average_price) * pnl.net_size.signum();
+            let new_size = pnl.net_size + signed_size;
+            if new_size != 0.0 && new_size.signum() != pnl.net_size.signum() {
+                // Flipped through zero: the remainder opens at this fill's price.
+                *average_price = fill.price;
+            }
+            pnl.net_size = new_size;
+        }
+    }
+
+    markets
+        .into_values()
+        .map(|(mut pnl, _, held_before)| {
+            pnl.net_size += held_before;
+            pnl
+        })
+        .collect()
+}
+
+/// Perp positions of `user` in token units by market.
+async fn current_position_sizes(user: &Pubkey) -> Result<BTreeMap<u16, f64>, DriftDataError> {
+    let client = init_drift_client().await?;
+    let account = client.get_user_account(user).await.map_err(|_| DriftDataError {
+        message: "Failed to get user account".to_string()
+    })?;
+    Ok(account
+        .perp_positions
+        .iter()
+        .filter(|p| p.base_asset_amount != 0)
+        .map(|p| (p.market_index, p.base_asset_amount as f64 / BASE_PRECISION as f64))
+        .collect())
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftTradeHistoryArgs {
+    pub market_index: Option<u16>,
+    pub start_ts: Option<i64>,      // Unix seconds
+    pub end_ts: Option<i64>,        // Unix seconds
+    pub hours: Option<i64>,         // Shorthand for start_ts = now - hours
+    pub limit: Option<usize>,
+    pub sub_account_id: Option<u16>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftTradeHistory;
+
+impl Tool for DriftTradeHistory {
+    const NAME: &'static str = "drift_trade_history";
+
+    type Error = DriftDataError;
+    type Args = DriftTradeHistoryArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_trade_history".to_string(),
+            description: "Lists the vault account's recent perp fills (market, side, size, price, fee, time and transaction signature) and the realized PnL per market. With start_ts or hours the PnL covers every fill in that period; otherwise only the listed fills. Use it when asked what the agent actually traded or how trades performed.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "market_index": { "type": "integer", "description": "Only fills in this perp market (optional)." },
+                    "start_ts": { "type": "integer", "description": "Only fills at or after this unix timestamp in seconds (optional)." },
+                    "end_ts": { "type": "integer", "description": "Only fills at or before this unix timestamp in seconds (optional)." },
+                    "hours": { "type": "integer", "description": "Only fills in the last this many hours (optional, ignored if start_ts is set)." },
+                    "limit": { "type": "integer", "description": "Maximum number of fills (default: 50)." },
+                    "sub_account_id": { "type": "integer", "description": "Sub-account to report (optional, defaults to 0)." }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let filter = FillFilter {
+            market_index: args.market_index,
+            start_ts: args.start_ts.or(args.hours.map(|hours| chrono::Utc::now().timestamp() - hours * 3600)),
+            end_ts: args.end_ts,
+            limit: args.limit,
+        };
+        let user = sub_account_pubkey(&load_agent_wallet()?, args.sub_account_id);
+        let limit = filter.limit.unwrap_or(DEFAULT_FILL_LIMIT);
+
+        // With a start the PnL is over the whole period, so page past the listing limit.
+        let (pnl_fills, pnl_scope) = match filter.start_ts {
+            Some(start_ts) => {
+                let (all_fills, complete) = get_all_fills(&user, &filter).await?;
+                let since = |ts: i64| chrono::DateTime::from_timestamp(ts, 0).map_or(ts.to_string(), |t| t.to_rfc3339());
+                let scope = if complete {
+                    format!("all {} fills since {}", all_fills.len(), since(start_ts))
+                } else {
+                    format!(
+                        "the latest {} fills only, since {}; history before that was not fetched",
+                        all_fills.len(),
+                        all_fills.last().map_or(since(start_ts), |fill| since(fill.ts))
+                    )
+                };
+                (all_fills, scope)
+            }
+            None => {
+                let fills = get_fills(&user, &filter).await?;
+                let scope = format!("the {} listed fills only; set start_ts or hours for a full period", fills.len());
+                (fills, scope)
+            }
+        };
+        let fills: Vec<&Fill> = pnl_fills.iter().take(limit).collect();
+
+        // Work back from the current position to what was held before the first fill, which
+        // needs every fill from there until now: those after end_ts too.
+        let later_fills = match filter.end_ts {
+            Some(end_ts) => {
+                let later = FillFilter { market_index: filter.market_index, start_ts: Some(end_ts + 1), end_ts: None, limit: None };
+                let (later_fills, complete) = get_all_fills(&user, &later).await?;
+                complete.then_some(later_fills)
+            }
+            None => Some(Vec::new()),
+        };
+        let sizes_before = match later_fills {
+            Some(later_fills) => {
+                let sizes_now = current_position_sizes(&user).await?;
+                let fills_until_now: Vec<Fill> = pnl_fills.iter().chain(later_fills.iter()).cloned().collect();
+                Some(sizes_before_fills(&fills_until_now, &sizes_now))
+            }
+            None => None,
+        };
+
+        if fills.is_empty() {
+            return Ok("No fills found.".to_string());
+        }
+
+        let mut result = String::from("**Fills:**\n");
+        for fill in fills {
+            result.push_str(&format!(
+                "- {} {} {} {} @ ${:.6} fee ${:.6} ({})\n  Signature: {}\n",
+                chrono::DateTime::from_timestamp(fill.ts, 0).map_or(fill.ts.to_string(), |t| t.to_rfc3339()),
+                fill.market_name,
+                fill.side,
+                fill.size,
+                fill.price,
+                fill.fee,
+                if fill.is_taker { "taker" } else { "maker" },
+                fill.signature,
+            ));
+        }
+
+        result.push_str(&format!("\n**Realized PnL By Market** (over {}):\n", pnl_scope));
+        if sizes_before.is_none() {
+            result.push_str("Positions held before the first fill could not be worked out, so fills closing them are counted as opening new positions.\n");
+        }
+        for pnl in realized_pnl_by_market(&pnl_fills, sizes_before.as_ref()) {
+            result.push_str(&format!(
+                "- {}: realized ${:.2} after ${:.2} fees over {} fills (${:.2} volume), net size {}\n",
+                pnl.market_name, pnl.realized_pnl_usd, pnl.fees_usd, pnl.fills, pnl.volume_usd, pnl.net_size
+            ));
+            if pnl.unmatched_size > 0.0 {
+                result.push_str(&format!(
+                    "  Excludes PnL on {} closed from a position opened before the first fill, whose entry price is not in the fills\n",
+                    pnl.unmatched_size
+                ));
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(ts: i64, side: &str, size: f64, price: f64) -> Fill {
+        Fill {
+            ts,
+            market_index: 0,
+            market_name: "SOL-PERP".to_string(),
+            side: side.to_string(),
+            size,
+            price,
+            fee: 0.1,
+            is_taker: true,
+            signature: String::new(),
+        }
+    }
+
+    #[test]
+    fn round_trip_inside_the_window_is_realized() {
+        let fills = [fill(1, "long", 2.0, 100.0), fill(2, "long", 2.0, 110.0), fill(3, "short", 4.0, 120.0)];
+        let pnl = &realized_pnl_by_market(&fills, Some(&BTreeMap::new()))[0];
+        assert!((pnl.realized_pnl_usd - (4.0 * 15.0 - 0.3)).abs() < 1e-9);
+        assert_eq!(pnl.net_size, 0.0);
+        assert_eq!(pnl.unmatched_size, 0.0);
+    }
+
+    #[test]
+    fn closing_a_position_opened_before_the_window_is_not_realized() {
+        let fills = [fill(1, "short", 3.0, 120.0)];
+        let sizes_before = BTreeMap::from([(0, 3.0)]);
+        let pnl = &realized_pnl_by_market(&fills, Some(&sizes_before))[0];
+        assert!((pnl.realized_pnl_usd + 0.1).abs() < 1e-9);
+        assert_eq!(pnl.unmatched_size, 3.0);
+        assert_eq!(pnl.net_size, 0.0);
+    }
+
+    #[test]
+    fn flipping_past_an_older_position_opens_the_remainder() {
+        let fills = [fill(1, "short", 5.0, 120.0), fill(2, "long", 2.0, 100.0)];
+        let sizes_before = BTreeMap::from([(0, 3.0)]);
+        let pnl = &realized_pnl_by_market(&fills, Some(&sizes_before))[0];
+        assert_eq!(pnl.unmatched_size, 3.0);
+        // Short 2 at 120, bought back at 100.
+        assert!((pnl.realized_pnl_usd - (40.0 - 0.2)).abs() < 1e-9);
+        assert_eq!(pnl.net_size, 0.0);
+    }
+
+    #[test]
+    fn sizes_before_fills_works_back_from_the_current_position() {
+        let fills = [fill(1, "short", 3.0, 120.0), fill(2, "long", 1.0, 110.0)];
+        let sizes_now = BTreeMap::from([(0, 1.0)]);
+        assert_eq!(sizes_before_fills(&fills, &sizes_now), BTreeMap::from([(0, 3.0)]));
+        assert!(sizes_before_fills(&fills[1..], &BTreeMap::from([(0, 1.0)])).is_empty());
+    }
+}
@@ -0,0 +1,161 @@
+use std::{collections::HashMap, env, fmt, sync::{atomic::{AtomicU8, Ordering}, Mutex, OnceLock}};
+use serde::Serialize;
+
+use drift_rs::types::{accounts::User, OrderParams, OrderStatus};
+
+const DEFAULT_DEDUPE_WINDOW_SECS: i64 = 30;
+
+/// Outcome of an order submission: the signature of the attempt that landed and the client
+/// order id of each order it carried.
+#[derive(Debug, Clone)]
+pub struct OrderReceipt {
+    pub user_order_ids: Vec<u8>,
+    pub signature: solana_sdk::signature::Signature,
+}
+
+impl fmt::Display for OrderReceipt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids = self.user_order_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ");
+        let label = if self.user_order_ids.len() == 1 { "client order id" } else { "client order ids" };
+        write!(f, "{} ({} {})", self.signature, label, ids)
+    }
+}
+
+/// Picks a client order id (1-255) that none of the user's open orders is using. Ids rotate
+/// so a recently filled order's id is not handed out again straight away.
+pub fn next_user_order_id(user: &User) -> u8 {
+    static NEXT_USER_ORDER_ID: OnceLock<AtomicU8> = OnceLock::new();
+    let counter = NEXT_USER_ORDER_ID.get_or_init(|| AtomicU8::new(rand::random::<u8>()));
+
+    for _ in 0..=u8::MAX {
+        let id = counter.fetch_add(1, Ordering::SeqCst);
+        if id != 0 && !user.orders.iter().any(|o| o.status == OrderStatus::Open && o.user_order_id == id) {
+            return id;
+        }
+    }
+    // Every id is taken by an open order; Drift will reject the duplicate rather than double up.
+    counter.fetch_add(1, Ordering::SeqCst).max(1)
+}
+
+/// Whether `order` is open on `after`, an account fetched while following an attempt, under its
+/// client order id and was not already open on `before`, the account it was sent against. An
+/// order that filled straight away is on neither, so only its signature status can confirm it.
+pub fn order_landed(before: &User, after: &User, order: &OrderParams) -> bool {
+    let open_with_id = |user: &User| user.orders.iter().any(|o| {
+        o.status == OrderStatus::Open
+            && o.user_order_id == order.user_order_id
+            && o.market_index == order.market_index
+            && o.market_type == order.market_type
+    });
+    open_with_id(after) && !open_with_id(before)
+}
+
+/// An order request remembered for deduplication.
+enum RecentRequest {
+    /// Being sent right now.
+    Pending,
+    /// Succeeded at the given unix time with the given result.
+    Done(i64, String),
+}
+
+fn recent_requests() -> &'static Mutex<HashMap<String, RecentRequest>> {
+    static RECENT_REQUESTS: OnceLock<Mutex<HashMap<String, RecentRequest>>> = OnceLock::new();
+    RECENT_REQUESTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Seconds within which an identical order tool call is treated as a repeat, from
+/// AGENT_ORDER_DEDUPE_SECS (default 30, 0 disables).
+pub fn dedupe_window_secs() -> i64 {
+    env::var("AGENT_ORDER_DEDUPE_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_DEDUPE_WINDOW_SECS)
+}
+
+fn request_key<A: Serialize>(tool: &str, args: &A) -> String {
+    format!("{}:{}", tool, serde_json::to_string(args).unwrap_or_default())
+}
+
+/// Holds the dedupe key of an order request while it is sent. `complete` keeps the result for
+/// the dedupe window; dropping the reservation without completing it, when the call failed,
+/// frees the key so the request can be retried.
+pub struct RequestReservation {
+    key: Option<String>,
+}
+
+impl RequestReservation {
+    pub fn complete(mut self, result: &str) {
+        if let Some(key) = self.key.take() {
+            let now = chrono::Utc::now().timestamp();
+            recent_requests().lock().unwrap().insert(key, RecentRequest::Done(now, result.to_string()));
+        }
+    }
+}
+
+impl Drop for RequestReservation {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            if let Ok(mut requests) = recent_requests().lock() {
+                requests.remove(&key);
+            }
+        }
+    }
+}
+
+/// Reserves a call to `tool` before it is sent. If an identical call is still being sent or
+/// succeeded within the dedupe window, returns the reply for the repeat instead; checking and
+/// reserving under one lock means two concurrent identical calls cannot both go out.
+pub fn reserve_request<A: Serialize>(tool: &str, args: &A) -> Result<RequestReservation, String> {
+    let window = dedupe_window_secs();
+    if window <= 0 {
+        return Ok(RequestReservation { key: None });
+    }
+    let now = chrono::Utc::now().timestamp();
+    let key = request_key(tool, args);
+    let mut requests = recent_requests().lock().unwrap();
+    requests.retain(|_, request| match request {
+        RecentRequest::Pending => true,
+        RecentRequest::Done(at, _) => now - *at < window,
+    });
+    match requests.get(&key) {
+        Some(RecentRequest::Pending) => {
+            return Err("Duplicate request ignored: identical orders are already being submitted.".to_string());
+        }
+        Some(RecentRequest::Done(at, result)) => {
+            return Err(format!(
+                "Duplicate request ignored: identical orders were already submitted {}s ago. Previous result: {}",
+                now - at, result
+            ));
+        }
+        None => {}
+    }
+    requests.insert(key.clone(), RecentRequest::Pending);
+    Ok(RequestReservation { key: Some(key) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_pending_request_blocks_an_identical_one() {
+        let reservation = reserve_request("test_pending", &1).unwrap();
+        let duplicate = reserve_request("test_pending", &1).err().unwrap();
+        assert!(duplicate.contains("already being submitted"));
+        assert!(reserve_request("test_pending", &2).is_ok());
+        drop(reservation);
+    }
+
+    #[test]
+    fn a_failed_request_frees_its_key() {
+        drop(reserve_request("test_failed", &1).unwrap());
+        assert!(reserve_request("test_failed", &1).is_ok());
+    }
+
+    #[test]
+    fn a_completed_request_replies_with_its_result() {
+        reserve_request("test_completed", &1).unwrap().complete("signature");
+        let duplicate = reserve_request("test_completed", &1).err().unwrap();
+        assert!(duplicate.contains("Previous result: signature"));
+    }
+}
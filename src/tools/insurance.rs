@@ -0,0 +1,417 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    system_program,
+    sysvar,
+};
+use spl_associated_token_account::{get_associated_token_address, instruction::create_associated_token_account_idempotent};
+
+use drift_rs::{constants, DriftClient, Pubkey};
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+
+use crate::tools::shared::*;
+use crate::tools::anchor::*;
+use crate::data::spot_market_name;
+use crate::tools::user::send_or_serialize;
+use crate::tools::vault_manager::drift_signer;
+
+/// Account types of the Drift program, used to name a mismatched discriminator.
+const DRIFT_ACCOUNTS: &[&str] = &["User", "UserStats", "State", "PerpMarket", "SpotMarket", "InsuranceFundStake"];
+
+#[derive(BorshDeserialize, BorshSerialize, Debug)]
+pub struct InsuranceFundStake {
+    pub authority: Pubkey,
+    pub if_shares: u128,
+    pub last_withdraw_request_shares: u128,
+    pub if_base: u128,
+    pub last_valid_ts: i64,
+    pub last_withdraw_request_value: u64,
+    pub last_withdraw_request_ts: i64,
+    pub cost_basis: i64,
+    pub market_index: u16,
+    pub padding: [u8; 14],
+}
+
+impl AnchorAccount for InsuranceFundStake {
+    const ACCOUNT_NAME: &'static str = "InsuranceFundStake";
+    const PROGRAM_ID: Pubkey = constants::PROGRAM_ID;
+    const DATA_SIZE: usize = 128;
+    const SIBLING_ACCOUNTS: &'static [&'static str] = DRIFT_ACCOUNTS;
+}
+
+pub fn insurance_fund_stake_address(authority: &Pubkey, market_index: u16) -> Pubkey {
+    Pubkey::find_program_address(&[b"insurance_fund_stake", authority.as_ref(), &market_index.to_le_bytes()], &constants::PROGRAM_ID).0
+}
+
+pub fn insurance_fund_vault_address(market_index: u16) -> Pubkey {
+    Pubkey::find_program_address(&[b"insurance_fund_vault", &market_index.to_le_bytes()], &constants::PROGRAM_ID).0
+}
+
+fn user_stats_address(authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"user_stats", authority.as_ref()], &constants::PROGRAM_ID).0
+}
+
+#[derive(BorshSerialize)]
+struct StakeAmountArgs {
+    market_index: u16,
+    amount: u64,
+}
+
+/// Accounts shared by the insurance fund stake instructions of one authority and spot market.
+pub struct StakeAccounts {
+    pub authority: Pubkey,
+    pub market_index: u16,
+    pub spot_market: Pubkey,
+    pub mint: Pubkey,
+    pub decimals: u32,
+    pub stake: Pubkey,
+    pub user_stats: Pubkey,
+    pub insurance_fund_vault: Pubkey,
+    pub user_token_account: Pubkey,
+}
+
+impl StakeAccounts {
+    pub async fn load(client: &DriftClient, authority: &Pubkey, market_index: u16, user_token_account: Option<Pubkey>) -> Result<Self, DriftDataError> {
+        let spot_market = client.get_spot_market_account(market_index).await.map_err(|_| DriftDataError {
+            message: format!("Failed to get spot market {}", market_index)
+        })?;
+        Ok(StakeAccounts {
+            authority: *authority,
+            market_index,
+            spot_market: constants::derive_spot_market_account(market_index),
+            mint: spot_market.mint,
+            decimals: spot_market.decimals,
+            stake: insurance_fund_stake_address(authority, market_index),
+            user_stats: user_stats_address(authority),
+            insurance_fund_vault: insurance_fund_vault_address(market_index),
+            user_token_account: user_token_account.unwrap_or(get_associated_token_address(authority, &spot_market.mint)),
+        })
+    }
+
+    /// Accounts of `request_remove_insurance_fund_stake` and its cancel.
+    fn request_accounts(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.spot_market, false),
+            AccountMeta::new(self.stake, false),
+            AccountMeta::new(self.user_stats, false),
+            AccountMeta::new_readonly(self.authority, true),
+            AccountMeta::new(self.insurance_fund_vault, false),
+        ]
+    }
+
+    pub async fn load_stake(&self, client: &DriftClient) -> Result<Option<InsuranceFundStake>, DriftDataError> {
+        fetch_optional_anchor_account(&client.rpc(), &self.stake).await
+    }
+}
+
+/// Instructions staking `amount` (spot market precision) into the market's insurance fund,
+/// creating the authority's Drift user stats and stake accounts first if needed.
+pub async fn stake_instructions(client: &DriftClient, accounts: &StakeAccounts, amount: u64) -> Result<Vec<Instruction>, DriftDataError> {
+    let mut instructions = Vec::new();
+    if fetch_optional_account(&client.rpc(), &accounts.user_stats).await?.is_none() {
+        instructions.push(anchor_instruction(constants::PROGRAM_ID, "initialize_user_stats", &(), vec![
+            AccountMeta::new(accounts.user_stats, false),
+            AccountMeta::new(*constants::state_account(), false),
+            AccountMeta::new_readonly(accounts.authority, true),
+            AccountMeta::new(accounts.authority, true), // Payer
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ]));
+    }
+    if fetch_optional_account(&client.rpc(), &accounts.stake).await?.is_none() {
+        instructions.push(anchor_instruction(constants::PROGRAM_ID, "initialize_insurance_fund_stake", &accounts.market_index, vec![
+            AccountMeta::new_readonly(accounts.spot_market, false),
+            AccountMeta::new(accounts.stake, false),
+            AccountMeta::new(accounts.user_stats, false),
+            AccountMeta::new_readonly(*constants::state_account(), false),
+            AccountMeta::new_readonly(accounts.authority, true),
+            AccountMeta::new(accounts.authority, true), // Payer
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ]));
+    }
+    instructions.push(anchor_instruction(
+        constants::PROGRAM_ID,
+        "add_insurance_fund_stake",
+        &StakeAmountArgs { market_index: accounts.market_index, amount },
+        vec![
+            AccountMeta::new_readonly(*constants::state_account(), false),
+            AccountMeta::new(accounts.spot_market, false),
+            AccountMeta::new(accounts.stake, false),
+            AccountMeta::new(accounts.user_stats, false),
+            AccountMeta::new_readonly(accounts.authority, true),
+            AccountMeta::new(constants::derive_spot_market_vault(accounts.market_index), false),
+            AccountMeta::new(accounts.insurance_fund_vault, false),
+            AccountMeta::new_readonly(drift_signer(), false),
+            AccountMeta::new(accounts.user_token_account, false),
+            AccountMeta::new_readonly(constants::TOKEN_PROGRAM_ID, false),
+        ],
+    ));
+    Ok(instructions)
+}
+
+/// Instruction requesting to unstake `amount` (spot market precision); the stake can be removed
+/// once the insurance fund's unstaking period has passed.
+pub fn request_unstake_instructions(accounts: &StakeAccounts, amount: u64) -> Vec<Instruction> {
+    let args = StakeAmountArgs { market_index: accounts.market_index, amount };
+    vec![anchor_instruction(constants::PROGRAM_ID, "request_remove_insurance_fund_stake", &args, accounts.request_accounts())]
+}
+
+pub fn cancel_unstake_instructions(accounts: &StakeAccounts) -> Vec<Instruction> {
+    vec![anchor_instruction(constants::PROGRAM_ID, "cancel_request_remove_insurance_fund_stake", &accounts.market_index, accounts.request_accounts())]
+}
+
+/// Instructions completing a pending unstake request into the authority's token account.
+pub fn complete_unstake_instructions(accounts: &StakeAccounts) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    if accounts.user_token_account == get_associated_token_address(&accounts.authority, &accounts.mint) {
+        instructions.push(create_associated_token_account_idempotent(
+            &accounts.authority,
+            &accounts.authority,
+            &accounts.mint,
+            &constants::TOKEN_PROGRAM_ID,
+        ));
+    }
+    instructions.push(anchor_instruction(constants::PROGRAM_ID, "remove_insurance_fund_stake", &accounts.market_index, vec![
+        AccountMeta::new_readonly(*constants::state_account(), false),
+        AccountMeta::new(accounts.spot_market, false),
+        AccountMeta::new(accounts.stake, false),
+        AccountMeta::new(accounts.user_stats, false),
+        AccountMeta::new_readonly(accounts.authority, true),
+        AccountMeta::new(accounts.insurance_fund_vault, false),
+        AccountMeta::new_readonly(drift_signer(), false),
+        AccountMeta::new(accounts.user_token_account, false),
+        AccountMeta::new_readonly(constants::TOKEN_PROGRAM_ID, false),
+    ]));
+    instructions
+}
+
+/// An insurance fund stake with token amounts in whole units of the spot market token.
+#[derive(Debug, Clone, Serialize)]
+pub struct StakeInfo {
+    pub market_index: u16,
+    pub market: String,
+    pub authority: String,
+    pub stake_account: String,
+    pub shares: u128,
+    pub share_percent: f64,
+    /// Insurance fund tokens per share.
+    pub share_value: f64,
+    pub stake_value: f64,
+    pub cost_basis: f64,
+    /// Stake value minus cost basis.
+    pub pnl: f64,
+    pub insurance_fund_balance: f64,
+    pub unstaking_period_secs: i64,
+    pub unstake_requested_shares: u128,
+    pub unstake_requested_value: f64,
+    /// Seconds until a pending unstake request can be completed, 0 if it already can.
+    pub unstake_available_in_secs: Option<i64>,
+}
+
+pub async fn get_stake_info(client: &DriftClient, accounts: &StakeAccounts) -> Result<StakeInfo, DriftDataError> {
+    let spot_market = client.get_spot_market_account(accounts.market_index).await.map_err(|_| DriftDataError {
+        message: format!("Failed to get spot market {}", accounts.market_index)
+    })?;
+    let insurance_fund = &spot_market.insurance_fund;
+    let balance = client.rpc().get_token_account_balance(&accounts.insurance_fund_vault).await.map_err(|e| DriftDataError {
+        message: format!("Failed to get insurance fund vault balance: {}", e)
+    })?;
+    let token_precision = 10f64.powi(accounts.decimals as i32);
+    let tokens = |amount: f64| amount / token_precision;
+    let vault_balance = tokens(balance.amount.parse::<u64>().unwrap_or(0) as f64);
+    let share_value = if insurance_fund.total_shares > 0 { vault_balance / insurance_fund.total_shares as f64 } else { 0.0 };
+
+    let stake = accounts.load_stake(client).await?;
+    // Shares are rebased when the fund's share base moves; the stake's base lags until its next action.
+    let rebase = |shares: u128, if_base: u128| shares / 10u128.pow(insurance_fund.shares_base.saturating_sub(if_base) as u32);
+    let (shares, requested_shares) = stake.as_ref().map_or((0, 0), |s| {
+        (rebase(s.if_shares, s.if_base), rebase(s.last_withdraw_request_shares, s.if_base))
+    });
+    let stake_value = share_value * shares as f64;
+    let cost_basis = stake.as_ref().map_or(0.0, |s| tokens(s.cost_basis as f64));
+    let now = chrono::Utc::now().timestamp();
+
+    Ok(StakeInfo {
+        market_index: accounts.market_index,
+        market: spot_market_name(accounts.market_index),
+        authority: accounts.authority.to_string(),
+        stake_account: accounts.stake.to_string(),
+        shares,
+        share_percent: if insurance_fund.total_shares > 0 { shares as f64 / insurance_fund.total_shares as f64 * 100.0 } else { 0.0 },
+        share_value,
+        stake_value,
+        cost_basis,
+        pnl: stake_value - cost_basis,
+        insurance_fund_balance: vault_balance,
+        unstaking_period_secs: insurance_fund.unstaking_period,
+        unstake_requested_shares: requested_shares,
+        unstake_requested_value: stake.as_ref().map_or(0.0, |s| tokens(s.last_withdraw_request_value as f64)),
+        unstake_available_in_secs: stake.as_ref().filter(|s| s.last_withdraw_request_shares > 0).map(|s| {
+            (s.last_withdraw_request_ts + insurance_fund.unstaking_period - now).max(0)
+        }),
+    })
+}
+
+impl StakeInfo {
+    pub fn to_text(&self) -> String {
+        format!(
+            "{} insurance fund stake of {} ({}):\n- Shares: {} ({:.4}% of the fund)\n- Share Value: {:.9}\n- Stake Value: {:.6}\n- Cost Basis: {:.6}\n- PnL: {:.6}\n- Insurance Fund Balance: {:.6}\n- Unstaking Period: {}s\n- Unstake Request: {}",
+            self.market,
+            self.authority,
+            self.stake_account,
+            self.shares,
+            self.share_percent,
+            self.share_value,
+            self.stake_value,
+            self.cost_basis,
+            self.pnl,
+            self.insurance_fund_balance,
+            self.unstaking_period_secs,
+            match self.unstake_available_in_secs {
+                None => "none".to_string(),
+                Some(0) => format!("{} shares ({:.6}), can be completed now", self.unstake_requested_shares, self.unstake_requested_value),
+                Some(secs) => format!("{} shares ({:.6}), can be completed in {}s", self.unstake_requested_shares, self.unstake_requested_value, secs),
+            },
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftInsuranceStakeArgs {
+    pub action: String,                 // "stake", "request_unstake", "cancel_unstake" or "complete_unstake"
+    pub market_index: u16,              // Spot market of the insurance fund
+    pub amount: Option<f64>,            // Whole tokens, for stake and request_unstake
+    pub authority: Option<String>,
+    pub user_token_account: Option<String>,
+    pub send: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftInsuranceStake;
+
+impl DriftInsuranceStake {
+    pub async fn execute(args: DriftInsuranceStakeArgs) -> Result<String, DriftDataError> {
+        let client = init_drift_client().await?;
+        let authority = args.authority.as_deref().map(|v| parse_pubkey(v, "authority")).transpose()?.unwrap_or(client.wallet().signer());
+        let user_token_account = args.user_token_account.as_deref().map(|v| parse_pubkey(v, "user token account")).transpose()?;
+        let accounts = StakeAccounts::load(&client, &authority, args.market_index, user_token_account).await?;
+        let amount = || -> Result<u64, DriftDataError> {
+            let amount = args.amount.ok_or(DriftDataError { message: format!("amount is required to {}", args.action) })?;
+            Ok((amount * 10f64.powi(accounts.decimals as i32)) as u64)
+        };
+
+        let instructions = match args.action.as_str() {
+            "stake" => stake_instructions(&client, &accounts, amount()?).await?,
+            "request_unstake" => request_unstake_instructions(&accounts, amount()?),
+            "cancel_unstake" => cancel_unstake_instructions(&accounts),
+            "complete_unstake" => {
+                let info = get_stake_info(&client, &accounts).await?;
+                match info.unstake_available_in_secs {
+                    None => return Err(DriftDataError { message: format!("{} has no pending unstake request", authority) }),
+                    Some(secs) if secs > 0 => return Err(DriftDataError {
+                        message: format!("The unstake request can be completed in {}s, once the unstaking period has passed", secs)
+                    }),
+                    Some(_) => complete_unstake_instructions(&accounts),
+                }
+            }
+            other => return Err(DriftDataError { message: format!("Unknown insurance fund action {}", other) }),
+        };
+        send_or_serialize(&client, &instructions, &authority, args.send.unwrap_or(false)).await
+    }
+}
+
+impl Tool for DriftInsuranceStake {
+    const NAME: &'static str = "drift_insurance_stake";
+
+    type Error = DriftDataError;
+    type Args = DriftInsuranceStakeArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_insurance_stake".to_string(),
+            description: "Stakes into a Drift spot market's insurance fund, requests to unstake, cancels the request, or completes it once the unstaking period has passed. Transactions are returned unsigned in base64, or signed and sent when send is true and the authority is the agent's own wallet.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "action": { "type": "string", "enum": ["stake", "request_unstake", "cancel_unstake", "complete_unstake"], "description": "Which step to perform." },
+                    "market_index": { "type": "number", "description": "Spot market index of the insurance fund, e.g. 0 for USDC." },
+                    "amount": { "type": "number", "description": "Amount in whole tokens, for stake and request_unstake." },
+                    "authority": { "type": "string", "description": "Staker wallet public key (optional, defaults to the agent's wallet)." },
+                    "user_token_account": { "type": "string", "description": "Token account to stake from or unstake to (optional, defaults to the associated token account)." },
+                    "send": { "type": "boolean", "description": "Sign and send with the agent's wallet instead of returning an unsigned transaction (default: false)." }
+                },
+                "required": ["action", "market_index"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let handle = tokio::task::spawn(Self::execute(args));
+        handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await insurance fund stake".to_string()
+        })?
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftInsuranceStakeInfoArgs {
+    pub market_index: u16,
+    pub authority: Option<String>,
+    pub format: Option<String>,         // "text" (default) or "json"
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftInsuranceStakeInfo;
+
+impl DriftInsuranceStakeInfo {
+    pub async fn execute(args: DriftInsuranceStakeInfoArgs) -> Result<String, DriftDataError> {
+        let client = init_drift_client().await?;
+        let authority = args.authority.as_deref().map(|v| parse_pubkey(v, "authority")).transpose()?.unwrap_or(client.wallet().signer());
+        let accounts = StakeAccounts::load(&client, &authority, args.market_index, None).await?;
+        let info = get_stake_info(&client, &accounts).await?;
+        if args.format.as_deref() == Some("json") {
+            return serde_json::to_string(&info).map_err(|_| DriftDataError {
+                message: "Failed to serialize stake info".to_string()
+            });
+        }
+        Ok(info.to_text())
+    }
+}
+
+impl Tool for DriftInsuranceStakeInfo {
+    const NAME: &'static str = "drift_insurance_stake_info";
+
+    type Error = DriftDataError;
+    type Args = DriftInsuranceStakeInfoArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_insurance_stake_info".to_string(),
+            description: "Shows a wallet's insurance fund stake for a spot market: shares, share value, stake value, cost basis and PnL, the fund's balance and unstaking period, and any pending unstake request with the time left before it can be completed.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "market_index": { "type": "number", "description": "Spot market index of the insurance fund, e.g. 0 for USDC." },
+                    "authority": { "type": "string", "description": "Staker wallet public key (optional, defaults to the agent's wallet)." },
+                    "format": { "type": "string", "enum": ["text", "json"], "description": "Output format (default: text)." }
+                },
+                "required": ["market_index"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let handle = tokio::task::spawn(Self::execute(args));
+        handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await insurance fund stake info".to_string()
+        })?
+    }
+}
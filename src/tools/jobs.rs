@@ -0,0 +1,319 @@
+use std::{collections::HashMap, env, fs, path::PathBuf, sync::{Arc, Mutex, OnceLock}};
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use drift_rs::{types::{accounts::User, MarketType, OrderStatus, PerpPosition}, DriftClient};
+
+use crate::tools::shared::*;
+use crate::tools::{DriftTWAPOrders, DriftTrailingStopOrders, DriftVWAPOrders};
+
+const DEFAULT_JOB_STORE_DIR: &str = ".agent_jobs";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Twap,
+    Vwap,
+    TrailingStop,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Stopped,
+    /// The job could not be reconciled with on-chain state and needs a human to look at it.
+    Flagged,
+}
+
+/// A persisted algorithm job. `args` holds the original tool arguments and `state`
+/// the algorithm specific progress needed to pick up where it left off.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JobRecord {
+    pub id: String,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub args: Value,
+    pub state: Value,
+    pub note: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl JobRecord {
+    pub fn new<A: Serialize, S: Serialize>(id: &str, kind: JobKind, args: &A, state: &S) -> Self {
+        let now = chrono::Utc::now().timestamp();
+        JobRecord {
+            id: id.to_string(),
+            kind,
+            status: JobStatus::Running,
+            args: serde_json::to_value(args).unwrap_or_default(),
+            state: serde_json::to_value(state).unwrap_or_default(),
+            note: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// The sub-account the job trades, taken from its args; jobs without one trade sub-account 0.
+    pub fn sub_account_id(&self) -> u16 {
+        self.args.get("sub_account_id").and_then(|v| v.as_u64()).map_or(0, |id| id as u16)
+    }
+}
+
+/// Stores one JSON file per job in a local directory so running algorithms survive a restart.
+pub struct JobStore {
+    dir: PathBuf,
+}
+
+impl JobStore {
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, DriftDataError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|e| DriftDataError {
+            message: format!("Failed to create job store at {}: {}", dir.display(), e)
+        })?;
+        Ok(JobStore { dir })
+    }
+
+    /// Opens the store at AGENT_JOB_STORE, defaulting to `.agent_jobs` in the working directory.
+    pub fn from_env() -> Result<Self, DriftDataError> {
+        let dir = env::var("AGENT_JOB_STORE").unwrap_or(DEFAULT_JOB_STORE_DIR.to_string());
+        Self::open(dir)
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    pub fn save(&self, job: &JobRecord) -> Result<(), DriftDataError> {
+        let data = serde_json::to_vec_pretty(job).map_err(|_| DriftDataError {
+            message: "Failed to serialize job".to_string()
+        })?;
+        // Write to a temp file first so a crash mid-write never leaves a truncated job behind.
+        let tmp_path = self.dir.join(format!("{}.json.tmp", job.id));
+        fs::write(&tmp_path, data).map_err(|e| DriftDataError {
+            message: format!("Failed to write job {}: {}", job.id, e)
+        })?;
+        fs::rename(&tmp_path, self.path(&job.id)).map_err(|e| DriftDataError {
+            message: format!("Failed to write job {}: {}", job.id, e)
+        })
+    }
+
+    pub fn load(&self, id: &str) -> Result<Option<JobRecord>, DriftDataError> {
+        let path = self.path(id);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read(&path).map_err(|e| DriftDataError {
+            message: format!("Failed to read job {}: {}", id, e)
+        })?;
+        serde_json::from_slice(&data).map(Some).map_err(|_| DriftDataError {
+            message: format!("Failed to parse job {}", id)
+        })
+    }
+
+    pub fn list(&self) -> Result<Vec<JobRecord>, DriftDataError> {
+        let entries = fs::read_dir(&self.dir).map_err(|e| DriftDataError {
+            message: format!("Failed to read job store: {}", e)
+        })?;
+
+        let mut jobs = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().map_or(true, |ext| ext != "json") {
+                continue;
+            }
+            match fs::read(&path).ok().and_then(|data| serde_json::from_slice::<JobRecord>(&data).ok()) {
+                Some(job) => jobs.push(job),
+                None => eprintln!("Skipping unreadable job file {}", path.display()),
+            }
+        }
+        jobs.sort_by_key(|job| job.created_at);
+        Ok(jobs)
+    }
+
+    pub fn running(&self) -> Result<Vec<JobRecord>, DriftDataError> {
+        Ok(self.list()?.into_iter().filter(|job| job.status == JobStatus::Running).collect())
+    }
+
+    pub fn update_state<S: Serialize>(&self, id: &str, state: &S) -> Result<(), DriftDataError> {
+        if let Some(mut job) = self.load(id)? {
+            job.state = serde_json::to_value(state).unwrap_or_default();
+            job.updated_at = chrono::Utc::now().timestamp();
+            self.save(&job)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_status(&self, id: &str, status: JobStatus, note: Option<String>) -> Result<(), DriftDataError> {
+        if let Some(mut job) = self.load(id)? {
+            job.status = status;
+            if note.is_some() {
+                job.note = note;
+            }
+            job.updated_at = chrono::Utc::now().timestamp();
+            self.save(&job)?;
+        }
+        Ok(())
+    }
+}
+
+/// Process wide job store, opened from the environment on first use.
+pub fn job_store() -> Option<&'static JobStore> {
+    static JOB_STORE: OnceLock<Option<JobStore>> = OnceLock::new();
+    JOB_STORE
+        .get_or_init(|| match JobStore::from_env() {
+            Ok(store) => Some(store),
+            Err(err) => {
+                eprintln!("Job persistence disabled: {}", err);
+                None
+            }
+        })
+        .as_ref()
+}
+
+/// Persists progress for a job, logging rather than failing the algorithm if the store is unavailable.
+pub fn persist_job_state<S: Serialize>(id: &str, state: &S) {
+    if let Some(store) = job_store() {
+        if let Err(err) = store.update_state(id, state) {
+            eprintln!("Failed to persist job {}: {}", id, err);
+        }
+    }
+}
+
+pub fn persist_job_status(id: &str, status: JobStatus, note: Option<String>) {
+    if let Some(store) = job_store() {
+        if let Err(err) = store.set_status(id, status, note) {
+            eprintln!("Failed to persist job {}: {}", id, err);
+        }
+    }
+}
+
+/// Stop flags of the jobs running in this process, with the sub-account each one trades.
+fn stop_flags() -> &'static Mutex<HashMap<String, (u16, Arc<AtomicBool>)>> {
+    static STOP_FLAGS: OnceLock<Mutex<HashMap<String, (u16, Arc<AtomicBool>)>>> = OnceLock::new();
+    STOP_FLAGS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Returns the stop flag for a job, registering it on first use. Running jobs poll this
+/// between steps and release it with `release_job_stop_flag` when they finish.
+pub fn job_stop_flag(job: &JobRecord) -> Arc<AtomicBool> {
+    let mut flags = stop_flags().lock().unwrap();
+    flags
+        .entry(job.id.clone())
+        .or_insert_with(|| (job.sub_account_id(), Arc::new(AtomicBool::new(false))))
+        .1
+        .clone()
+}
+
+/// Forgets a finished job's stop flag.
+pub fn release_job_stop_flag(id: &str) {
+    stop_flags().lock().unwrap().remove(id);
+}
+
+/// Signals the jobs in this process trading `sub_account_id` (every job if `None`) to stop and
+/// marks the matching running jobs in the store as stopped, so none of them are resumed on the
+/// next start. Returns the ids of the jobs that were stopped.
+pub fn stop_jobs(sub_account_id: Option<u16>) -> Vec<String> {
+    let matches = |job_sub_account: u16| sub_account_id.map_or(true, |id| id == job_sub_account);
+
+    let mut stopped = Vec::new();
+    for (id, (job_sub_account, flag)) in stop_flags().lock().unwrap().iter() {
+        if matches(*job_sub_account) && !flag.swap(true, Ordering::SeqCst) {
+            stopped.push(id.clone());
+        }
+    }
+
+    if let Some(store) = job_store() {
+        if let Ok(running) = store.running() {
+            for job in running.into_iter().filter(|job| matches(job.sub_account_id())) {
+                persist_job_status(&job.id, JobStatus::Stopped, Some("Stopped by flatten all".to_string()));
+                if !stopped.contains(&job.id) {
+                    stopped.push(job.id);
+                }
+            }
+        }
+    }
+    stopped
+}
+
+#[derive(Debug, Clone)]
+pub struct ResumeReport {
+    pub resumed: Vec<String>,
+    pub flagged: Vec<(String, String)>,
+}
+
+/// Reloads every running job from the store, checks it against the on-chain positions and open
+/// orders of the sub-account it trades and either restarts it or flags it for review.
+pub async fn resume_jobs(client: &DriftClient) -> Result<ResumeReport, DriftDataError> {
+    let store = job_store().ok_or(DriftDataError {
+        message: "Job store is not available".to_string()
+    })?;
+
+    let mut report = ResumeReport { resumed: Vec::new(), flagged: Vec::new() };
+    let mut users: HashMap<u16, User> = HashMap::new();
+
+    for mut job in store.running()? {
+        // Each job trades the sub-account named in its args, so reconcile against that one.
+        let sub_account_id = job.sub_account_id();
+        if !users.contains_key(&sub_account_id) {
+            let user = client
+                .get_user_account(&sub_account_pubkey(client.wallet(), Some(sub_account_id)))
+                .await
+                .map_err(|_| DriftDataError {
+                    message: format!("Failed to get sub-account {}", sub_account_id)
+                })?;
+            users.insert(sub_account_id, user);
+        }
+        let user = &users[&sub_account_id];
+
+        let state_before = job.state.clone();
+        let verdict = match job.kind {
+            JobKind::Twap => DriftTWAPOrders::reconcile_job(&mut job, user),
+            JobKind::Vwap => DriftVWAPOrders::reconcile_job(&mut job, user),
+            JobKind::TrailingStop => DriftTrailingStopOrders::reconcile_job(&mut job, user),
+        };
+
+        match verdict {
+            Ok(()) => {
+                if job.state != state_before {
+                    store.save(&job)?;
+                }
+                match job.kind {
+                    JobKind::Twap => DriftTWAPOrders::spawn_job(job.clone()),
+                    JobKind::Vwap => DriftVWAPOrders::spawn_job(job.clone()),
+                    JobKind::TrailingStop => DriftTrailingStopOrders::spawn_job(job.clone()),
+                }
+                report.resumed.push(job.id);
+            }
+            Err(reason) => {
+                eprintln!("Flagging job {}: {}", job.id, reason);
+                store.set_status(&job.id, JobStatus::Flagged, Some(reason.clone()))?;
+                report.flagged.push((job.id, reason));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+pub(crate) fn find_perp_position(positions: &[PerpPosition], market_index: u16) -> Option<&PerpPosition> {
+    positions.iter().find(|p| p.market_index == market_index && p.base_asset_amount != 0)
+}
+
+/// Whether `user` has an open order in perp market `market_index`.
+pub(crate) fn has_open_orders(user: &User, market_index: u16) -> bool {
+    user.orders.iter().any(|o| o.status == OrderStatus::Open && o.market_type == MarketType::Perp && o.market_index == market_index)
+}
+
+/// Whether `user` has an open order in perp market `market_index` under client order id `user_order_id`.
+pub(crate) fn has_open_order(user: &User, market_index: u16, user_order_id: u8) -> bool {
+    user.orders.iter().any(|o| {
+        o.status == OrderStatus::Open
+            && o.market_type == MarketType::Perp
+            && o.market_index == market_index
+            && o.user_order_id == user_order_id
+    })
+}
@@ -0,0 +1,306 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use anchor_lang::InstructionData;
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use spl_associated_token_account::{get_associated_token_address, instruction::create_associated_token_account_idempotent};
+use drift_rs::{
+    constants,
+    drift_idl,
+    types::accounts::SpotMarket,
+    DriftClient, Wallet,
+};
+
+use crate::data::*;
+use crate::tools::shared::*;
+use crate::tools::margin::spot_balance;
+use crate::tools::user::send_or_serialize;
+use crate::tools::vault_manager::drift_signer;
+
+/// Rates and utilization are stored with 1e6 precision.
+const SPOT_RATE_PRECISION: f64 = 1_000_000.0;
+/// Scaled balance times cumulative interest, divided by this, is whole tokens.
+const SPOT_TOKEN_SCALE: f64 = 1e19;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpotMarketRates {
+    pub market_index: u16,
+    pub market: String,
+    pub total_deposits: f64,
+    pub total_borrows: f64,
+    pub utilization_percent: f64,
+    pub deposit_apr_percent: f64,
+    pub deposit_apy_percent: f64,
+    pub borrow_apr_percent: f64,
+    pub borrow_apy_percent: f64,
+}
+
+/// Interest compounds on every market update, close enough to continuous for an APY.
+fn apr_to_apy(apr: f64) -> f64 {
+    apr.exp() - 1.0
+}
+
+/// Deposit and borrow rates of a spot market from its utilization and interest rate curve.
+pub fn spot_market_rates(spot_market: &SpotMarket) -> SpotMarketRates {
+    let total_deposits = spot_market.deposit_balance as f64 * spot_market.cumulative_deposit_interest as f64 / SPOT_TOKEN_SCALE;
+    let total_borrows = spot_market.borrow_balance as f64 * spot_market.cumulative_borrow_interest as f64 / SPOT_TOKEN_SCALE;
+    let utilization = if total_deposits > 0.0 { (total_borrows / total_deposits).min(1.0) } else { 0.0 };
+
+    let optimal_utilization = spot_market.optimal_utilization as f64 / SPOT_RATE_PRECISION;
+    let optimal_borrow_rate = spot_market.optimal_borrow_rate as f64 / SPOT_RATE_PRECISION;
+    let max_borrow_rate = spot_market.max_borrow_rate as f64 / SPOT_RATE_PRECISION;
+    let borrow_rate = if utilization <= optimal_utilization {
+        if optimal_utilization > 0.0 { utilization / optimal_utilization * optimal_borrow_rate } else { 0.0 }
+    } else {
+        optimal_borrow_rate
+            + (utilization - optimal_utilization) / (1.0 - optimal_utilization) * (max_borrow_rate - optimal_borrow_rate)
+    };
+    // The insurance fund takes its share of the interest before it reaches depositors.
+    let insurance_fund_factor = spot_market.insurance_fund.total_factor as f64 / SPOT_RATE_PRECISION;
+    let deposit_rate = borrow_rate * utilization * (1.0 - insurance_fund_factor);
+
+    SpotMarketRates {
+        market_index: spot_market.market_index,
+        market: spot_market_name(spot_market.market_index),
+        total_deposits,
+        total_borrows,
+        utilization_percent: utilization * 100.0,
+        deposit_apr_percent: deposit_rate * 100.0,
+        deposit_apy_percent: apr_to_apy(deposit_rate) * 100.0,
+        borrow_apr_percent: borrow_rate * 100.0,
+        borrow_apy_percent: apr_to_apy(borrow_rate) * 100.0,
+    }
+}
+
+/// Rates of `market_indexes`, or of every spot market in the market registry when empty.
+pub async fn get_spot_market_rates(client: &DriftClient, market_indexes: &[u16]) -> Result<Vec<SpotMarketRates>, DriftDataError> {
+    let list_all = market_indexes.is_empty();
+    let mut market_indexes = market_indexes.to_vec();
+    if list_all {
+        market_indexes = market_registry().spot.iter().map(|market| market.market_index).collect();
+    }
+
+    let mut rates = Vec::new();
+    for market_index in market_indexes {
+        match client.get_spot_market_account(market_index).await {
+            Ok(spot_market) => rates.push(spot_market_rates(&spot_market)),
+            // Listed markets that are not live yet are skipped when listing everything.
+            Err(_) if list_all => continue,
+            Err(_) => return Err(DriftDataError { message: format!("Failed to get spot market {}", market_index) }),
+        }
+    }
+    Ok(rates)
+}
+
+/// Moves tokens into (lend, repay) or out of (withdraw, borrow) a sub-account's spot balance.
+/// Repay and withdraw are reduce-only so they never flip the balance. Drift lets the account's
+/// delegate deposit, so lend and repay are signed by the agent wallet from its own token account
+/// when it is the authority or delegate; withdraw and borrow must be signed by the authority.
+/// The transaction is sent when `send` is set and the signer is the agent keypair, and returned
+/// unsigned for the signer otherwise.
+pub async fn spot_lending(
+    client: &DriftClient,
+    action: &str,
+    market_index: u16,
+    amount: Option<f64>,
+    sub_account_id: Option<u16>,
+    authority: Option<&str>,
+    send: bool,
+) -> Result<String, DriftDataError> {
+    let wallet = client.wallet();
+    let owner = authority.map(|v| parse_pubkey(v, "authority")).transpose()?.unwrap_or(*wallet.authority());
+    let user_pubkey = Wallet::derive_user_account(&owner, sub_account_id.unwrap_or(0));
+    let user = client.get_user_account(&user_pubkey).await.map_err(|_| DriftDataError {
+        message: format!("Failed to get sub-account {} of {}", sub_account_id.unwrap_or(0), owner)
+    })?;
+    let spot_market = client.get_spot_market_account(market_index).await.map_err(|_| DriftDataError {
+        message: format!("Failed to get spot market {}", market_index)
+    })?;
+
+    let is_deposit = match action {
+        "lend" | "repay" => true,
+        "withdraw" | "borrow" => false,
+        other => return Err(DriftDataError { message: format!("Unknown lending action {}", other) }),
+    };
+    let reduce_only = matches!(action, "repay" | "withdraw");
+    let signer = if is_deposit && (user.authority == wallet.signer() || user.delegate == wallet.signer()) {
+        wallet.signer()
+    } else {
+        user.authority
+    };
+    if !signer.is_on_curve() {
+        return Err(DriftDataError {
+            message: format!("Only the account authority can {} here, and {} is a program account that cannot sign", action, signer)
+        });
+    }
+
+    let precision = 10f64.powi(spot_market.decimals as i32);
+    let amount = match amount {
+        Some(amount) if amount > 0.0 => (amount * precision) as u64,
+        Some(_) => return Err(DriftDataError { message: "amount must be positive".to_string() }),
+        None if reduce_only => {
+            let balance = spot_balance(&user, &spot_market);
+            let owed = if action == "repay" { -balance } else { balance };
+            if owed <= 0 {
+                return Err(DriftDataError {
+                    message: format!("Nothing to {} in {}", action, spot_market_name(market_index))
+                });
+            }
+            // Reduce-only caps the transfer at the balance, so cover interest accrued until it lands.
+            owed as u64 + owed as u64 / 1000 + 1
+        }
+        None => return Err(DriftDataError { message: format!("amount is required to {}", action) }),
+    };
+
+    let user_token_account = get_associated_token_address(&signer, &spot_market.mint);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*constants::state_account(), false), // Drift State
+        AccountMeta::new(user_pubkey, false), // User
+        AccountMeta::new(Wallet::derive_stats_account(&owner), false), // User Stats
+        AccountMeta::new_readonly(signer, true), // Authority
+        AccountMeta::new(constants::derive_spot_market_vault(market_index), false), // Spot Market Vault
+    ];
+    if !is_deposit {
+        accounts.push(AccountMeta::new_readonly(drift_signer(), false)); // Drift Signer
+    }
+    accounts.push(AccountMeta::new(user_token_account, false)); // User Token Account
+    accounts.push(AccountMeta::new_readonly(constants::TOKEN_PROGRAM_ID, false)); // Token Program
+    accounts.extend(user_remaining_accounts(client, &user, &[market_index], &[]).await?);
+
+    let data = if is_deposit {
+        InstructionData::data(&drift_idl::instructions::Deposit { market_index, amount, reduce_only })
+    } else {
+        InstructionData::data(&drift_idl::instructions::Withdraw { market_index, amount, reduce_only })
+    };
+    let mut instructions = Vec::new();
+    if !is_deposit {
+        instructions.push(create_associated_token_account_idempotent(&signer, &signer, &spot_market.mint, &constants::TOKEN_PROGRAM_ID));
+    }
+    instructions.push(Instruction { program_id: constants::PROGRAM_ID, accounts, data });
+
+    let result = send_or_serialize(client, &instructions, &signer, send).await?;
+    Ok(format!(
+        "{} {:.6} {}{} on sub-account {} with {}'s token account.\n{}",
+        action,
+        amount as f64 / precision,
+        spot_market_name(market_index),
+        if reduce_only { " (capped at the balance)" } else { "" },
+        sub_account_id.unwrap_or(0),
+        signer,
+        result,
+    ))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftSpotLendingArgs {
+    pub action: String,                 // "lend", "withdraw", "borrow" or "repay"
+    pub market_index: u16,
+    pub amount: Option<f64>,            // Whole tokens; repay and withdraw default to the full balance
+    pub sub_account_id: Option<u16>,
+    pub authority: Option<String>,
+    pub send: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftSpotLending;
+
+impl Tool for DriftSpotLending {
+    const NAME: &'static str = "drift_spot_lending";
+
+    type Error = DriftDataError;
+    type Args = DriftSpotLendingArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_spot_lending".to_string(),
+            description: "Lends a spot asset by depositing it into a Drift sub-account to earn the deposit rate, withdraws lent assets, borrows against the account's collateral, or repays a borrow. Repay and withdraw never go past the current balance and default to all of it. Lend and repay are signed by the agent wallet from its own token account when it is the account's authority or delegate; withdraw and borrow must be signed by the account authority. Returns an unsigned base64 transaction for the signer, or signs and sends it when send is true and the signer is the agent's own wallet.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "action": { "type": "string", "enum": ["lend", "withdraw", "borrow", "repay"], "description": "Which operation to perform." },
+                    "market_index": { "type": "integer", "description": "Spot market index, e.g. 0 for USDC or 1 for SOL." },
+                    "amount": { "type": "number", "description": "Amount in whole tokens (optional for repay and withdraw, defaults to the full balance)." },
+                    "sub_account_id": { "type": "integer", "description": "Sub-account to use (optional, defaults to 0)." },
+                    "authority": { "type": "string", "description": "Authority wallet of the Drift account (optional, defaults to the account the agent trades for)." },
+                    "send": { "type": "boolean", "description": "Sign and send with the agent's wallet instead of returning an unsigned transaction (default: false)." }
+                },
+                "required": ["action", "market_index"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let handle = tokio::task::spawn(async move {
+            let client = init_drift_client().await?;
+            spot_lending(&client, &args.action, args.market_index, args.amount, args.sub_account_id, args.authority.as_deref(), args.send.unwrap_or(false)).await
+        });
+        handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await spot_lending".to_string()
+        })?
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftSpotRatesArgs {
+    pub market_indexes: Option<Vec<u16>>,
+    pub format: Option<String>,         // "text" (default) or "json"
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftSpotRates;
+
+impl Tool for DriftSpotRates {
+    const NAME: &'static str = "drift_spot_rates";
+
+    type Error = DriftDataError;
+    type Args = DriftSpotRatesArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_spot_rates".to_string(),
+            description: "Returns the current deposit and borrow APR/APY, utilization and total deposits and borrows of Drift spot markets.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "market_indexes": { "type": "array", "items": { "type": "integer" }, "description": "Spot markets to report (optional, defaults to every spot market)." },
+                    "format": { "type": "string", "enum": ["text", "json"], "description": "Output format (default: text)." }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let market_indexes = args.market_indexes.unwrap_or_default();
+        let handle = tokio::task::spawn(async move {
+            let client = init_drift_client().await?;
+            get_spot_market_rates(&client, &market_indexes).await
+        });
+        let rates = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await spot rates".to_string()
+        })??;
+
+        if args.format.as_deref() == Some("json") {
+            return serde_json::to_string(&rates).map_err(|_| DriftDataError {
+                message: "Failed to serialize spot rates".to_string()
+            });
+        }
+        Ok(rates.iter().map(|r| format!(
+            "- {} (spot {}): deposit APY {:.2}% (APR {:.2}%), borrow APY {:.2}% (APR {:.2}%), utilization {:.2}%, deposits {:.2}, borrows {:.2}",
+            r.market,
+            r.market_index,
+            r.deposit_apy_percent,
+            r.deposit_apr_percent,
+            r.borrow_apy_percent,
+            r.borrow_apr_percent,
+            r.utilization_percent,
+            r.total_deposits,
+            r.total_borrows,
+        )).collect::<Vec<_>>().join("\n"))
+    }
+}
@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use drift_rs::{
+    math::{
+        constants::{AMM_TO_QUOTE_PRECISION_RATIO, BASE_PRECISION, FUNDING_RATE_BUFFER, PRICE_PRECISION, QUOTE_PRECISION},
+        liquidation::{calculate_collateral, calculate_liquidation_price_and_unrealized_pnl, calculate_margin_requirements},
+    },
+    types::{accounts::{PerpMarket, SpotMarket, User}, MarginRequirementType, MarketId, PerpPosition, SpotBalanceType},
+    DriftClient,
+};
+
+use crate::data::*;
+use crate::tools::shared::*;
+
+/// Average entry price of a perp position in USD, from its quote entry amount.
+pub fn position_entry_price(position: &PerpPosition) -> f64 {
+    if position.base_asset_amount == 0 {
+        return 0.0;
+    }
+    (position.quote_entry_amount as f64 / QUOTE_PRECISION as f64).abs()
+        / (position.base_asset_amount as f64 / BASE_PRECISION as f64).abs()
+}
+
+/// Price at which closing the position would realize zero PnL after fees and funding.
+pub fn position_breakeven_price(position: &PerpPosition) -> f64 {
+    if position.base_asset_amount == 0 {
+        return 0.0;
+    }
+    (position.quote_break_even_amount as f64 / QUOTE_PRECISION as f64).abs()
+        / (position.base_asset_amount as f64 / BASE_PRECISION as f64).abs()
+}
+
+/// Token amount the sub-account holds in a spot market (positive) or owes (negative), in the
+/// market's precision.
+pub fn spot_balance(user: &User, spot_market: &SpotMarket) -> i128 {
+    user.spot_positions
+        .iter()
+        .find(|p| p.market_index == spot_market.market_index && !p.is_available())
+        .map_or(0, |p| {
+            let (cumulative_interest, sign) = match p.balance_type {
+                SpotBalanceType::Deposit => (spot_market.cumulative_deposit_interest, 1),
+                SpotBalanceType::Borrow => (spot_market.cumulative_borrow_interest, -1),
+            };
+            let precision_decrease = 10u128.pow(19 - spot_market.decimals);
+            sign * (p.scaled_balance as u128 * cumulative_interest / precision_decrease) as i128
+        })
+}
+
+/// Net value of an account in USD: every spot balance at its oracle price, borrows negative,
+/// plus the unrealized PnL and unsettled funding of every perp position. Unlike total
+/// collateral no asset weights or margin haircuts are applied, so this is what the account
+/// would be worth if it were closed out at oracle prices.
+pub async fn account_net_value_usd(client: &DriftClient, user: &User) -> Result<f64, DriftDataError> {
+    let mut net_value = 0.0;
+    for position in user.spot_positions.iter().filter(|p| !p.is_available()) {
+        let spot_market = client.get_spot_market_account(position.market_index).await.map_err(|_| DriftDataError {
+            message: format!("Failed to get spot market {}", position.market_index)
+        })?;
+        let price = get_token_oracle_price(MarketId::spot(position.market_index), client).await? as f64 / PRICE_PRECISION as f64;
+        let tokens = spot_balance(user, &spot_market) as f64 / 10f64.powi(spot_market.decimals as i32);
+        net_value += tokens * price;
+    }
+    for position in user.perp_positions.iter().filter(|p| p.base_asset_amount != 0 || p.quote_asset_amount != 0) {
+        let market = client.get_perp_market_account(position.market_index).await.map_err(|_| DriftDataError {
+            message: format!("Failed to get perp market {}", position.market_index)
+        })?;
+        let oracle_price = get_token_oracle_price(MarketId::perp(position.market_index), client).await?;
+        let unrealized_pnl = position.get_unrealized_pnl(oracle_price).map_err(|_| DriftDataError {
+            message: format!("Failed to calculate PnL on perp market {}", position.market_index)
+        })?;
+        net_value += (unrealized_pnl + position_unsettled_funding(position, &market)) as f64 / QUOTE_PRECISION as f64;
+    }
+    Ok(net_value)
+}
+
+/// Funding accrued on a position since it was last settled, in quote precision (1e6).
+/// Positive means the position is owed funding, negative means it owes.
+pub fn position_unsettled_funding(position: &PerpPosition, market: &PerpMarket) -> i128 {
+    if position.base_asset_amount == 0 {
+        return 0;
+    }
+    let cumulative_funding_rate = if position.base_asset_amount > 0 {
+        market.amm.cumulative_funding_rate_long
+    } else {
+        market.amm.cumulative_funding_rate_short
+    };
+    let funding_rate_delta = cumulative_funding_rate - position.last_cumulative_funding_rate as i128;
+
+    // Longs pay when the funding rate is positive, shorts receive.
+    -(funding_rate_delta * position.base_asset_amount as i128)
+        / (PRICE_PRECISION as i128 * FUNDING_RATE_BUFFER as i128)
+        / AMM_TO_QUOTE_PRECISION_RATIO as i128
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionRiskMetrics {
+    pub market_index: u16,
+    pub market_name: String,
+    pub base_asset_amount: f64,
+    pub notional_usd: f64,
+    pub entry_price: f64,
+    pub breakeven_price: f64,
+    pub oracle_price: f64,
+    pub liquidation_price: Option<f64>,
+    pub unrealized_pnl_usd: f64,
+    pub unsettled_funding_usd: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountRiskMetrics {
+    pub sub_account_id: u16,
+    pub total_collateral_usd: f64,
+    pub free_collateral_usd: f64,
+    pub initial_margin_requirement_usd: f64,
+    pub maintenance_margin_requirement_usd: f64,
+    pub total_notional_usd: f64,
+    /// Total perp notional divided by total collateral.
+    pub leverage: f64,
+    /// Total collateral divided by total perp notional, as a percent.
+    pub margin_ratio_percent: Option<f64>,
+    pub positions: Vec<PositionRiskMetrics>,
+}
+
+pub async fn get_account_risk_metrics(client: &DriftClient, sub_account_id: Option<u16>) -> Result<AccountRiskMetrics, DriftDataError> {
+    let user = client
+        .get_user_account(&sub_account_pubkey(client.wallet(), sub_account_id))
+        .await
+        .map_err(|_| DriftDataError {
+            message: "Failed to get_user_account".to_string()
+        })?;
+
+    let collateral = calculate_collateral(client, &user, MarginRequirementType::Initial).map_err(|_| DriftDataError {
+        message: "Failed to calculate collateral".to_string()
+    })?;
+    let margin_requirements = calculate_margin_requirements(client, &user).map_err(|_| DriftDataError {
+        message: "Failed to calculate margin requirements".to_string()
+    })?;
+
+    let registry = market_registry();
+    let mut positions = Vec::new();
+    for position in user.perp_positions.iter().filter(|p| p.is_open_position()) {
+        let market = client.get_perp_market_account(position.market_index).await.map_err(|_| DriftDataError {
+            message: format!("Failed to get perp market {}", position.market_index)
+        })?;
+        let oracle_price = client.oracle_price(MarketId::perp(position.market_index)).await.unwrap_or(0);
+        let liquidation = calculate_liquidation_price_and_unrealized_pnl(client, &user, position.market_index).ok();
+        let base = position.base_asset_amount as f64 / BASE_PRECISION as f64;
+
+        positions.push(PositionRiskMetrics {
+            market_index: position.market_index,
+            market_name: registry.perp_name(position.market_index),
+            base_asset_amount: base,
+            notional_usd: base.abs() * oracle_price as f64 / PRICE_PRECISION as f64,
+            entry_price: position_entry_price(position),
+            breakeven_price: position_breakeven_price(position),
+            oracle_price: oracle_price as f64 / PRICE_PRECISION as f64,
+            // A liquidation price of zero or below means the position cannot be liquidated.
+            liquidation_price: liquidation
+                .as_ref()
+                .filter(|info| info.liquidation_price > 0)
+                .map(|info| info.liquidation_price as f64 / PRICE_PRECISION as f64),
+            unrealized_pnl_usd: liquidation
+                .map(|info| info.unrealized_pnl as f64 / QUOTE_PRECISION as f64)
+                .unwrap_or_else(|| position.get_unrealized_pnl(oracle_price).unwrap_or(0) as f64 / QUOTE_PRECISION as f64),
+            unsettled_funding_usd: position_unsettled_funding(position, &market) as f64 / QUOTE_PRECISION as f64,
+        });
+    }
+
+    let total_collateral_usd = collateral.total as f64 / QUOTE_PRECISION as f64;
+    let total_notional_usd: f64 = positions.iter().map(|p| p.notional_usd).sum();
+
+    Ok(AccountRiskMetrics {
+        sub_account_id: user.sub_account_id,
+        total_collateral_usd,
+        free_collateral_usd: collateral.free as f64 / QUOTE_PRECISION as f64,
+        initial_margin_requirement_usd: margin_requirements.initial as f64 / QUOTE_PRECISION as f64,
+        maintenance_margin_requirement_usd: margin_requirements.maintenance as f64 / QUOTE_PRECISION as f64,
+        total_notional_usd,
+        leverage: if total_collateral_usd > 0.0 { total_notional_usd / total_collateral_usd } else { 0.0 },
+        margin_ratio_percent: if total_notional_usd > 0.0 { Some(total_collateral_usd / total_notional_usd * 100.0) } else { None },
+        positions,
+    })
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftMarginInfoArgs {
+    pub sub_account_id: Option<u16>,
+    pub format: Option<String>, // "text" (default) or "json"
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftMarginInfo;
+
+impl Tool for DriftMarginInfo {
+    const NAME: &'static str = "drift_margin_info";
+
+    type Error = DriftDataError;
+    type Args = DriftMarginInfoArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_margin_info".to_string(),
+            description: "Reports margin and risk metrics for the vault account: total and free collateral, initial and maintenance margin requirements, leverage and margin ratio, and per position the entry, breakeven and liquidation prices, unrealized PnL and unsettled funding.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "sub_account_id": { "type": "integer", "description": "Sub-account to inspect (optional, defaults to 0)." },
+                    "format": { "type": "string", "enum": ["text", "json"], "description": "Output format (default: text)." }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let sub_account_id = args.sub_account_id;
+        let handle = tokio::task::spawn(async move {
+            let client = init_drift_client().await?;
+            get_account_risk_metrics(&client, sub_account_id).await
+        });
+        let metrics = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await get_account_risk_metrics".to_string()
+        })??;
+
+        if args.format.as_deref() == Some("json") {
+            return serde_json::to_string(&metrics).map_err(|_| DriftDataError {
+                message: "Failed to serialize margin info".to_string()
+            });
+        }
+
+        let mut result = format!(
+            "**Account (sub-account {}):**\n- Total Collateral: ${:.2}\n- Free Collateral: ${:.2}\n- Initial Margin Requirement: ${:.2}\n- Maintenance Margin Requirement: ${:.2}\n- Total Notional: ${:.2}\n- Leverage: {:.2}x\n- Margin Ratio: {}\n",
+            metrics.sub_account_id,
+            metrics.total_collateral_usd,
+            metrics.free_collateral_usd,
+            metrics.initial_margin_requirement_usd,
+            metrics.maintenance_margin_requirement_usd,
+            metrics.total_notional_usd,
+            metrics.leverage,
+            metrics.margin_ratio_percent.map_or("n/a".to_string(), |ratio| format!("{:.2}%", ratio)),
+        );
+        result.push_str("\n**Positions:**\n");
+        if metrics.positions.is_empty() {
+            result.push_str("No open perpetual positions.\n");
+        }
+        for p in &metrics.positions {
+            result.push_str(&format!(
+                "- Market: {}\n  Base Amount: {}\n  Notional: ${:.2}\n  Entry Price: ${:.6}\n  Breakeven Price: ${:.6}\n  Oracle Price: ${:.6}\n  Liquidation Price: {}\n  Unrealized PnL: ${:.2}\n  Unsettled Funding: ${:.6}\n",
+                p.market_name,
+                p.base_asset_amount,
+                p.notional_usd,
+                p.entry_price,
+                p.breakeven_price,
+                p.oracle_price,
+                p.liquidation_price.map_or("none".to_string(), |price| format!("${:.6}", price)),
+                p.unrealized_pnl_usd,
+                p.unsettled_funding_usd,
+            ));
+        }
+        Ok(result)
+    }
+}
@@ -1,11 +1,51 @@
 mod shared;
+mod anchor;
 mod vault;
 mod drift;
 mod order;
 mod user;
+mod jobs;
+mod sizing;
+mod flatten;
+mod subaccount;
+mod margin;
+mod risk;
+mod approval;
+mod idempotency;
+mod funding;
+mod orderbook;
+mod history;
+mod settle;
+mod events;
+mod alerts;
+mod depositor;
+mod vault_manager;
+mod performance;
+mod insurance;
+mod lending;
 
 pub use shared::*;
+pub use anchor::*;
 pub use user::*;
 pub use drift::*; 
 pub use vault::*;
-pub use order::*;
\ No newline at end of file
+pub use order::*;
+pub use jobs::*;
+pub use sizing::*;
+pub use flatten::*;
+pub use subaccount::*;
+pub use margin::*;
+pub use risk::*;
+pub use approval::*;
+pub use idempotency::*;
+pub use funding::*;
+pub use orderbook::*;
+pub use history::*;
+pub use settle::*;
+pub use events::*;
+pub use alerts::*;
+pub use depositor::*;
+pub use vault_manager::*;
+pub use performance::*;
+pub use insurance::*;
+pub use lending::*;
@@ -1,33 +1,41 @@
 use std::{collections::HashMap, sync::Arc, time::Duration};
 use std::{env, str::FromStr, sync::OnceLock};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Instant;
 use tokio::{sync::Mutex, time::sleep};
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use solana_sdk::{native_token::LAMPORTS_PER_SOL, signature::Keypair};
+use solana_sdk::signature::Keypair;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use rig::{
     completion::ToolDefinition,
     tool::Tool,
 };
-use drift_rs::{math::constants::{LAMPORTS_PER_SOL_I64, PRICE_PRECISION_U64}, types::{Context, MarketId, MarketType, OrderParams}, DriftClient, Pubkey, Wallet};
+use drift_rs::{math::constants::{BASE_PRECISION, PRICE_PRECISION, PRICE_PRECISION_U64}, types::{accounts::User, Context, MarketId, MarketType, OrderParams}, DriftClient, Pubkey, Wallet};
 
 use crate::tools::shared::*;
+use crate::tools::jobs::*;
+use crate::tools::risk::*;
+use crate::tools::approval::*;
+use crate::tools::idempotency::*;
+use crate::tools::orderbook::*;
+use crate::tools::settle::*;
 
 #[derive(Deserialize, Serialize)]
 pub struct PerpOrder {
     pub market_index: u16,
-    pub amount: i64,
+    pub amount: f64,        // Whole base units, fractions allowed
     pub price: Option<u64>, // Optional price
     pub post_only: Option<bool>,
+    pub reduce_only: Option<bool>,
+    pub max_slippage_bps: Option<f64>,
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct DriftPlacePerpOrdersArgs {
     pub orders: Vec<PerpOrder>, // Accept multiple orders
+    pub sub_account_id: Option<u16>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -53,48 +61,34 @@ impl Tool for DriftPlacePerpOrders {
                             "type": "object",
                             "properties": {
                                 "market_index": { "type": "integer", "description": "The market index for the perp trade." },
-                                "amount": { "type": "integer", "description": "The order amount in base asset units (signed for long/short positions)." },
+                                "amount": { "type": "number", "description": "The order amount in base asset units, fractions allowed (signed for long/short positions)." },
                                 "price": { "type": "integer", "description": "The order price in Drift price precision. If omitted, it is automatically calculated." },
-                                "post_only": { "type": "boolean", "description": "Whether the order should be post-only." }
+                                "post_only": { "type": "boolean", "description": "Whether the order should be post-only." },
+                                "reduce_only": { "type": "boolean", "description": "Whether the order may only reduce an existing position." },
+                                "max_slippage_bps": { "type": "number", "description": "For market orders, refuse the order if the estimated slippage from the order book is above this many basis points (optional)." }
                             },
                             "required": ["market_index", "amount"]
                         }
-                    }
+                    },
+                    "sub_account_id": { "type": "integer", "description": "Sub-account to trade from (optional, defaults to 0)." }
                 }
             }),
         }
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
-        let rpc_url = SOLANA_MAINNET_RPC_URL;
-        let agent_vault: String = env::var("AGENT_VAULT").unwrap();
-        let secret_key_str = env::var("AGENT_KEYPAIR").unwrap();
-        let secret_key_bytes: Vec<u8> = secret_key_str
-            .split(',')
-            .map(|s| s.parse::<u8>().expect("Invalid byte"))
-            .collect();
-    
-        let keypair = Keypair::from_bytes(&secret_key_bytes).expect("Invalid private key");
-        let agent_vault_pubkey = Pubkey::from_str(&agent_vault).unwrap();
-        let mut wallet: Wallet = Wallet::from(keypair);
-        wallet.to_delegated(agent_vault_pubkey);
-
-        let handle = tokio::task::spawn(async move {
-            let client = DriftClient::new(
-                Context::MainNet,
-                RpcClient::new(rpc_url.to_string()),
-                wallet.clone(),
-            )
-            .await
-            .map_err(|_| DriftDataError { 
-                message: "Failed to init Drift client".to_string(),
-            })?;
-        
-            Self::place_perp_orders(args, &client).await
-        });
-        handle.await.map_err(|_| DriftDataError { 
-            message: "Failed to await place_perp_orders".to_string()
-        })?
+        if approval_mode_enabled() {
+            let summary = args.orders.iter().map(|order| format!(
+                "{} {} on market {} at {}{}",
+                if order.amount > 0.0 { "buy" } else { "sell" },
+                order.amount.abs(),
+                order.market_index,
+                order.price.map_or("market".to_string(), |price| price.to_string()),
+                if order.reduce_only.unwrap_or(false) { " (reduce-only)" } else { "" },
+            )).collect::<Vec<_>>().join(", ");
+            return submit_intent(Self::NAME, format!("place perp orders: {}", summary), &args);
+        }
+        Self::execute_place_perp_orders(args).await
     }
     
     // async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
@@ -132,9 +126,52 @@ impl Tool for DriftPlacePerpOrders {
 }
 
 impl DriftPlacePerpOrders {
+    pub async fn execute_place_perp_orders(args: DriftPlacePerpOrdersArgs) -> Result<String, DriftDataError> {
+        let rpc_url = SOLANA_MAINNET_RPC_URL;
+        let agent_vault: String = env::var("AGENT_VAULT").unwrap();
+        let secret_key_str = env::var("AGENT_KEYPAIR").unwrap();
+        let secret_key_bytes: Vec<u8> = secret_key_str
+            .split(',')
+            .map(|s| s.parse::<u8>().expect("Invalid byte"))
+            .collect();
+    
+        let keypair = Keypair::from_bytes(&secret_key_bytes).expect("Invalid private key");
+        let agent_vault_pubkey = Pubkey::from_str(&agent_vault).unwrap();
+        let mut wallet: Wallet = Wallet::from(keypair);
+        wallet.to_delegated(agent_vault_pubkey);
+
+        let handle = tokio::task::spawn(async move {
+            let client = DriftClient::new(
+                Context::MainNet,
+                RpcClient::new(rpc_url.to_string()),
+                wallet.clone(),
+            )
+            .await
+            .map_err(|_| DriftDataError { 
+                message: "Failed to init Drift client".to_string(),
+            })?;
+        
+            Self::place_perp_orders(args, &client).await
+        });
+        handle.await.map_err(|_| DriftDataError { 
+            message: "Failed to await place_perp_orders".to_string()
+        })?
+    }
+
     pub async fn place_perp_orders(
         args: DriftPlacePerpOrdersArgs, drift_client: &DriftClient,
     ) -> Result<String, DriftDataError> {
+        if args.orders.is_empty() {
+            return Err(DriftDataError { message: "orders must contain at least one order".to_string() });
+        }
+
+        // An LLM that repeats a tool call should not open the same position twice.
+        let request_args = serde_json::to_value(&args).unwrap_or_default();
+        let reservation = match reserve_request(DriftPlacePerpOrders::NAME, &request_args) {
+            Ok(reservation) => reservation,
+            Err(duplicate) => return Ok(duplicate),
+        };
+
         // let rpc_url = SOLANA_MAINNET_RPC_URL;
         // let agent_vault: String = env::var("AGENT_VAULT").unwrap();
         // let secret_key_str = env::var("AGENT_KEYPAIR").unwrap();
@@ -159,12 +196,25 @@ impl DriftPlacePerpOrders {
         // })?;
         let client = drift_client.clone();
         let user = client
-            .get_user_account(&drift_client.wallet().default_sub_account())
+            .get_user_account(&sub_account_pubkey(drift_client.wallet(), args.sub_account_id))
             .await
             .map_err(|_| DriftDataError { 
                 message: "Failed to init Drift user".to_string()
             })?;
 
+        let proposed_orders: Vec<ProposedOrder> = args.orders.iter().map(|order| ProposedOrder {
+            market_index: order.market_index,
+            base_asset_amount: to_base_precision(order.amount),
+            reduce_only: order.reduce_only.unwrap_or(false),
+        }).collect();
+        risk_guard().check_orders(&client, &user, &proposed_orders).await?;
+
+        for order in args.orders.iter().filter(|o| o.price.unwrap_or(0) == 0) {
+            if let Some(max_slippage_bps) = order.max_slippage_bps {
+                check_market_order_slippage(order.market_index, order.amount, max_slippage_bps).await?;
+            }
+        }
+
         let mut order_requests = Vec::new();
 
         for order in args.orders {
@@ -174,38 +224,43 @@ impl DriftPlacePerpOrders {
             if price > 0 {
                 order_requests.push(
                     drift_rs::types::NewOrder::limit(market_id)
-                        .amount(order.amount * LAMPORTS_PER_SOL_I64)
+                        .amount(to_base_precision(order.amount))
                         .price(price * PRICE_PRECISION_U64)
                         .post_only(if order.post_only.unwrap_or(false) {
                             drift_rs::types::PostOnlyParam::MustPostOnly
                         } else {
                             drift_rs::types::PostOnlyParam::None
                         })
+                        .reduce_only(order.reduce_only.unwrap_or(false))
                         .build(),
                 );
             } else {
                 order_requests.push(
                     drift_rs::types::NewOrder::market(market_id)
-                        .amount(order.amount * LAMPORTS_PER_SOL_I64)
+                        .amount(to_base_precision(order.amount))
                         .post_only(if order.post_only.unwrap_or(false) {
                             drift_rs::types::PostOnlyParam::MustPostOnly
                         } else {
                             drift_rs::types::PostOnlyParam::None
                         })
+                        .reduce_only(order.reduce_only.unwrap_or(false))
                         .build(),
                 );
             };
         }
 
-        let order_request = order_requests.first().unwrap();
-        let order_response = send_order_with_retry(&client, &client.wallet(), &user, *order_request).await;
+        // Every order goes out in the one transaction, so they land or fail together.
+        let order_count = order_requests.len();
+        let order_response = send_orders_with_retry(&client, client.wallet(), &user, order_requests).await;
 
         match order_response {
-            Ok(tx_signature) => {
+            Ok(receipt) => {
                 client.unsubscribe().await.map_err(|_| DriftDataError { 
                     message: "Failed to unsubscribe".to_string()
                 })?;
-                Ok(format!("Perp order(s) placed successfully: {:?}", tx_signature))
+                let result = format!("{} perp order(s) placed successfully: {}", order_count, receipt);
+                reservation.complete(&result);
+                Ok(result)
             }
             Err(err) => {
                 let err_msg = err.to_string();
@@ -222,7 +277,7 @@ impl DriftPlacePerpOrders {
 #[derive(Deserialize, Serialize, Clone)]
 pub struct TWAPOrder {
     pub market_index: u16,
-    pub total_amount: i64,
+    pub total_amount: f64,
     pub total_duration_secs: u64,
     pub interval_secs: u64,
     pub order_type: String,
@@ -236,6 +291,7 @@ struct TWAPTracker {
 #[derive(Deserialize, Serialize)]
 pub struct DriftTWAPOrdersArgs {
     pub twap_orders: Vec<TWAPOrder>,
+    pub sub_account_id: Option<u16>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -261,28 +317,83 @@ impl Tool for DriftTWAPOrders {
                             "type": "object",
                             "properties": {
                                 "market_index": { "type": "integer", "description": "The market index for the perp trade." },
-                                "total_amount": { "type": "integer", "description": "The total amount of base asset to trade." },
+                                "total_amount": { "type": "number", "description": "The total amount of base asset to trade, fractions allowed." },
                                 "total_duration_secs": { "type": "integer", "description": "The duration over which to execute the TWAP." },
                                 "interval_secs": { "type": "integer", "description": "Interval between each trade execution in seconds." },
                                 "order_type": { "type": "string", "enum": ["market", "limit"], "description": "Order type." }
                             },
                             "required": ["market_index", "total_amount", "total_duration_secs", "interval_secs", "order_type"]
                         }
-                    }
+                    },
+                    "sub_account_id": { "type": "integer", "description": "Sub-account to trade from (optional, defaults to 0)." }
                 }
             }),
         }
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        Self::validate(&args)?;
+        if approval_mode_enabled() {
+            let summary = args.twap_orders.iter().map(|order| format!(
+                "{} {} on market {} over {}s every {}s",
+                order.order_type, order.total_amount, order.market_index, order.total_duration_secs, order.interval_secs
+            )).collect::<Vec<_>>().join(", ");
+            return submit_intent(Self::NAME, format!("TWAP: {}", summary), &args);
+        }
         Self::execute_twap_orders(args).await
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct TWAPJobState {
+    pub order_position: usize,  // Index of the TWAP order currently being worked
+    pub trades_done: u64,       // Slices confirmed for that order
+    #[serde(default)]
+    pub pending_user_order_id: Option<u8>, // Client order id of the slice being sent
+}
+
+/// Consecutive failed sends after which a TWAP job gives up and is flagged.
+const MAX_TWAP_SLICE_FAILURES: u32 = 3;
+
 impl DriftTWAPOrders {
-    pub async fn execute_twap_orders(args: DriftTWAPOrdersArgs) -> Result<String, DriftDataError> {
+    fn tracker() -> Arc<TWAPTracker> {
         static TWAP_TRACKER: OnceLock<Arc<TWAPTracker>> = OnceLock::new();
-        let twap_tracker = TWAP_TRACKER.get_or_init(|| Arc::new(TWAPTracker::default())).clone();
+        TWAP_TRACKER.get_or_init(|| Arc::new(TWAPTracker::default())).clone()
+    }
+
+    /// Number of slices and base amount per slice (base precision) of a TWAP order.
+    fn slices(order: &TWAPOrder) -> Result<(u64, i64), DriftDataError> {
+        let num_trades = order.total_duration_secs.checked_div(order.interval_secs).unwrap_or(0);
+        if num_trades == 0 {
+            return Err(DriftDataError {
+                message: format!(
+                    "TWAP on market {} needs interval_secs above 0 and total_duration_secs of at least interval_secs (got {}s every {}s)",
+                    order.market_index, order.total_duration_secs, order.interval_secs
+                )
+            });
+        }
+        let trade_size = to_base_precision(order.total_amount) / num_trades as i64;
+        if trade_size == 0 {
+            return Err(DriftDataError {
+                message: format!("TWAP on market {} splits {} into {} slices of zero size", order.market_index, order.total_amount, num_trades)
+            });
+        }
+        Ok((num_trades, trade_size))
+    }
+
+    fn validate(args: &DriftTWAPOrdersArgs) -> Result<(), DriftDataError> {
+        if args.twap_orders.is_empty() {
+            return Err(DriftDataError { message: "twap_orders must contain at least one order".to_string() });
+        }
+        for order in &args.twap_orders {
+            Self::slices(order)?;
+        }
+        Ok(())
+    }
+
+    pub async fn execute_twap_orders(args: DriftTWAPOrdersArgs) -> Result<String, DriftDataError> {
+        Self::validate(&args)?;
+        let twap_tracker = Self::tracker();
         let order_id = Uuid::new_v4().to_string();
 
         let tracker = twap_tracker.orders.lock().await;
@@ -291,70 +402,176 @@ impl DriftTWAPOrders {
         }
         drop(tracker);
 
-        let twap_tracker_clone = Arc::clone(&twap_tracker);
-        let order_id_clone = order_id.clone();
+        // A repeated tool call should not start a second job.
+        let request_args = serde_json::to_value(&args).unwrap_or_default();
+        let reservation = match reserve_request(DriftTWAPOrders::NAME, &request_args) {
+            Ok(reservation) => reservation,
+            Err(duplicate) => return Ok(duplicate),
+        };
+
+        let proposed_orders: Vec<ProposedOrder> = args.twap_orders.iter().map(|order| ProposedOrder {
+            market_index: order.market_index,
+            base_asset_amount: to_base_precision(order.total_amount),
+            reduce_only: false,
+        }).collect();
+        check_orders_with_new_client(proposed_orders, args.sub_account_id).await?;
 
+        let job = JobRecord::new(&order_id, JobKind::Twap, &args, &TWAPJobState::default());
+        if let Some(store) = job_store() {
+            store.save(&job)?;
+        }
+        Self::spawn_job(job);
+
+        let result = format!("TWAP orders initiated with ID: {}", order_id);
+        reservation.complete(&result);
+        Ok(result)
+    }
+
+    pub fn spawn_job(job: JobRecord) {
         tokio::spawn(async move {
-            let rpc_url = SOLANA_MAINNET_RPC_URL;
-            let agent_vault: String = env::var("AGENT_VAULT").unwrap();
-            let secret_key_str = env::var("AGENT_KEYPAIR").unwrap();
-            let secret_key_bytes: Vec<u8> = secret_key_str.split(',').map(|s| s.parse::<u8>().expect("Invalid byte")).collect();
-            let kp = Keypair::from_bytes(&secret_key_bytes).expect("Invalid private key");
-            let mut wallet = Wallet::from(kp);
-            let agent_vault_pubkey = Pubkey::from_str(&agent_vault).unwrap();
-            wallet.to_delegated(agent_vault_pubkey);
-            let wallet_clone = wallet.clone();
-            let client = DriftClient::new(
-                Context::MainNet,
-                RpcClient::new(rpc_url.to_string()),
-                wallet_clone,
-            ).await.unwrap();
-
-            let user = client.get_user_account(&wallet.default_sub_account()).await.unwrap();
-
-            for order in args.twap_orders {
-                let market_id = MarketId::perp(order.market_index);
-                let num_trades = order.total_duration_secs / order.interval_secs;
-                let trade_size = order.total_amount / num_trades as i64;
-                for _ in 0..num_trades {
-                    let price  = get_token_oracle_price(market_id, &client).await.unwrap();
-                    let order_price = Some((price as f64 * 1.005) as u64 * PRICE_PRECISION_U64);
-                    let new_order: OrderParams;
-                    if order.order_type == "limit" {
-                        new_order = drift_rs::types::NewOrder::limit(market_id)
-                            .amount(trade_size * LAMPORTS_PER_SOL_I64)
-                            .price(order_price.unwrap_or_default())
-                            .build();
-                    } else {
-                        new_order = drift_rs::types::NewOrder::market(market_id)
-                            .amount(trade_size * LAMPORTS_PER_SOL_I64)
-                            .build();
-                    }
+            let job_id = job.id.clone();
+            if let Err(err) = Self::run_job(job).await {
+                eprintln!("TWAP job {} failed: {}", job_id, err);
+                persist_job_status(&job_id, JobStatus::Flagged, Some(err.to_string()));
+            }
+            release_job_stop_flag(&job_id);
+        });
+    }
 
-                    if let Err(_err) = send_order_with_retry(&client, &wallet, &user, new_order).await {
-                        //eprintln!("Final failure: {:?}", err);
-                        continue;
-                    }
+    async fn run_job(job: JobRecord) -> Result<(), DriftDataError> {
+        let args: DriftTWAPOrdersArgs = serde_json::from_value(job.args.clone()).map_err(|_| DriftDataError {
+            message: "Failed to parse TWAP job args".to_string()
+        })?;
+        let mut state: TWAPJobState = serde_json::from_value(job.state.clone()).unwrap_or_default();
+        let twap_tracker_clone = Self::tracker();
+        let stop_signal = job_stop_flag(&job);
+
+        let client = init_drift_client().await?;
+        let wallet = client.wallet().clone();
+        let sub_account = sub_account_pubkey(&wallet, args.sub_account_id);
+
+        for (position, order) in args.twap_orders.iter().enumerate().skip(state.order_position) {
+            let market_id = MarketId::perp(order.market_index);
+            let (num_trades, trade_size) = Self::slices(order)?;
+            let mut trade = if position == state.order_position { state.trades_done } else { 0 };
+            let mut failures = 0;
+            while trade < num_trades {
+                if stop_signal.load(Ordering::SeqCst) {
+                    println!("TWAP job {} stopped.", job.id);
+                    return Ok(());
+                }
+                let price  = get_token_oracle_price(market_id, &client).await?;
+                let order_price = Some((price as f64 * 1.005) as u64 * PRICE_PRECISION_U64);
+                let mut new_order: OrderParams;
+                if order.order_type == "limit" {
+                    new_order = drift_rs::types::NewOrder::limit(market_id)
+                        .amount(trade_size)
+                        .price(order_price.unwrap_or_default())
+                        .build();
+                } else {
+                    new_order = drift_rs::types::NewOrder::market(market_id)
+                        .amount(trade_size)
+                        .build();
+                }
 
-                    let mut tracker = twap_tracker_clone.orders.lock().await;
-                    *tracker.entry(order_id_clone.clone()).or_insert(0) += 1;
-                    drop(tracker);
-                    sleep(Duration::from_secs(order.interval_secs)).await;
+                // Earlier slices have filled since the last check, so the limits need fresh positions.
+                let user = client.get_user_account(&sub_account).await.map_err(|_| DriftDataError {
+                    message: "Failed to get Drift user".to_string()
+                })?;
+                risk_guard().check_orders(&client, &user, &[ProposedOrder {
+                    market_index: order.market_index,
+                    base_asset_amount: trade_size,
+                    reduce_only: false,
+                }]).await?;
+
+                // Persist the slice's client order id first, so a restart mid-send can find it.
+                new_order.user_order_id = next_user_order_id(&user);
+                state = TWAPJobState { order_position: position, trades_done: trade, pending_user_order_id: Some(new_order.user_order_id) };
+                persist_job_state(&job.id, &state);
+
+                let sent = send_order_with_retry(&client, &wallet, &user, new_order).await;
+                state.pending_user_order_id = None;
+                match sent {
+                    Ok(_) => {
+                        failures = 0;
+                        trade += 1;
+                        state.trades_done = trade;
+                        persist_job_state(&job.id, &state);
+
+                        let mut tracker = twap_tracker_clone.orders.lock().await;
+                        *tracker.entry(job.id.clone()).or_insert(0) += 1;
+                        drop(tracker);
+                    }
+                    Err(err) => {
+                        // The slice did not land; it is retried after the interval.
+                        persist_job_state(&job.id, &state);
+                        failures += 1;
+                        if failures >= MAX_TWAP_SLICE_FAILURES {
+                            return Err(DriftDataError {
+                                message: format!("TWAP slice {} of {} on market {} failed {} times: {}", trade + 1, num_trades, order.market_index, failures, err)
+                            });
+                        }
+                    }
                 }
+                sleep(Duration::from_secs(order.interval_secs)).await;
             }
-        });
-        Ok(format!("TWAP orders initiated with ID: {}", order_id))
+        }
+
+        persist_job_status(&job.id, JobStatus::Completed, None);
+        Ok(())
+    }
+
+    /// A partially worked TWAP must still have its position open; otherwise it was closed
+    /// elsewhere while we were down and continuing would rebuild exposure nobody asked for.
+    /// A slice that was being sent at the restart is counted as done if it is resting on the
+    /// account under its client order id, and flagged if it cannot be found, since it may have
+    /// filled.
+    pub fn reconcile_job(job: &mut JobRecord, user: &User) -> Result<(), String> {
+        let args: DriftTWAPOrdersArgs = serde_json::from_value(job.args.clone())
+            .map_err(|_| "Failed to parse TWAP job args".to_string())?;
+        let mut state: TWAPJobState = serde_json::from_value(job.state.clone()).unwrap_or_default();
+
+        let Some(order) = args.twap_orders.get(state.order_position) else {
+            if state.order_position == 0 {
+                return Err("TWAP job has no orders".to_string());
+            }
+            return Err("TWAP progress points past the last order".to_string());
+        };
+
+        if let Some(user_order_id) = state.pending_user_order_id.take() {
+            if !has_open_order(user, order.market_index, user_order_id) {
+                return Err(format!(
+                    "TWAP slice with client order id {} on market {} was being sent at the restart and is not resting; it may have filled",
+                    user_order_id, order.market_index
+                ));
+            }
+            state.trades_done += 1;
+            job.state = serde_json::to_value(&state).unwrap_or_default();
+        }
+
+        if state.trades_done == 0 && state.order_position == 0 {
+            return Ok(());
+        }
+
+        match find_perp_position(&user.perp_positions, order.market_index) {
+            Some(position) if (position.base_asset_amount > 0) == (order.total_amount > 0.0) => Ok(()),
+            // Limit slices may all still be resting without a position yet.
+            None if order.order_type == "limit" && has_open_orders(user, order.market_index) => Ok(()),
+            Some(_) => Err(format!("Position in market {} has flipped direction", order.market_index)),
+            None => Err(format!("No open position in market {} for partially executed TWAP", order.market_index)),
+        }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DriftVWAPOrdersArgs {
     pub market_index: Option<i32>,
-    pub size_per_order: Option<i64>,
+    pub size_per_order: Option<f64>,
     pub timeframe: Option<i32>,
     pub history_warm_up: Option<i32>,
     pub stop_signal: Option<bool>,
     pub duration_secs: Option<i32>,
+    pub sub_account_id: Option<u16>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -412,11 +629,12 @@ impl Tool for DriftVWAPOrders {
                 "type": "object",
                 "properties": {
                     "market_index": { "type": "integer", "description": "The market index for the perp trade." },
-                    "size_per_order": { "type": "integer", "description": "The size of each order to place." },
+                    "size_per_order": { "type": "number", "description": "The size of each order to place, in base asset units (fractions allowed)." },
                     "timeframe": { "type": "integer", "description": "The interval in seconds to recalculate VWAP. (default is 10 seconds)" },
                     "history_warm_up": { "type": "integer", "description": "The number of intervals to skip for warm-up (default is 5)." },
                     "stop_signal": { "type": "boolean", "description": "A flag to stop the VWAP strategy." },
-                    "duration_secs": { "type": "integer", "description": "The duration in seconds for how long to run the VWAP strategy (optional). If not provided, runs indefinitely." }
+                    "duration_secs": { "type": "integer", "description": "The duration in seconds for how long to run the VWAP strategy (optional). If not provided, runs indefinitely." },
+                    "sub_account_id": { "type": "integer", "description": "Sub-account to trade from (optional, defaults to 0)." }
                 },
                 "required": ["market_index", "size_per_order", "timeframe"]
             }),
@@ -424,20 +642,39 @@ impl Tool for DriftVWAPOrders {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
-        static VWAP_TRACKER: OnceLock<Arc<VWAPTracker>> = OnceLock::new();
-        let vwap_tracker = VWAP_TRACKER.get_or_init(|| Arc::new(VWAPTracker::default())).clone();
+        if approval_mode_enabled() && !Self::is_query_request(&args) {
+            let summary = format!(
+                "VWAP: {} per order on market {} every {}s{}",
+                args.size_per_order.unwrap_or(0.0),
+                args.market_index.unwrap_or(0),
+                args.timeframe.unwrap_or(0),
+                args.duration_secs.map_or(String::new(), |secs| format!(" for {}s", secs))
+            );
+            return submit_intent(Self::NAME, summary, &args);
+        }
+        Self::execute_vwap_orders(args).await
+    }
+}
 
-        let is_query_request = args.history_warm_up.is_none()
+impl DriftVWAPOrders {
+    fn is_query_request(args: &DriftVWAPOrdersArgs) -> bool {
+        args.history_warm_up.is_none()
         && args.stop_signal.is_none()
         && args.duration_secs.is_none()
-        && args.size_per_order.unwrap_or(0) == 0
-        && args.timeframe.unwrap_or(0) == 0;
+        && args.size_per_order.unwrap_or(0.0) == 0.0
+        && args.timeframe.unwrap_or(0) == 0
+    }
+
+    pub async fn execute_vwap_orders(args: DriftVWAPOrdersArgs) -> Result<String, DriftDataError> {
+        let vwap_tracker = Self::tracker();
+
+        let is_query_request = Self::is_query_request(&args);
 
         println!("Is query request: {}", is_query_request);
         println!("history_warm_up: {:?}", args.history_warm_up);
         println!("stop_signal: {}", args.stop_signal.is_none());
         println!("duration_secs: {}", args.duration_secs.is_none());
-        println!("size_per_order: {}", args.size_per_order.unwrap_or(0));
+        println!("size_per_order: {}", args.size_per_order.unwrap_or(0.0));
         println!("timeframe: {}", args.timeframe.unwrap_or(0));
 
         if is_query_request {
@@ -452,100 +689,171 @@ impl Tool for DriftVWAPOrders {
             ));
         }
 
-        let history_warm_up = args.history_warm_up.unwrap_or(5);
-        let market_index = args.market_index;
-        let size_per_order = args.size_per_order;
-        let recalculation_interval = args.timeframe;
-
-        let stop_signal = Arc::new(AtomicBool::new(false));
+        // A repeated tool call should not start a second job.
+        let request_args = serde_json::to_value(&args).unwrap_or_default();
+        let reservation = match reserve_request(DriftVWAPOrders::NAME, &request_args) {
+            Ok(reservation) => reservation,
+            Err(duplicate) => return Ok(duplicate),
+        };
 
         let duration_secs = args.duration_secs.unwrap_or(0);
-        let stop_time = if duration_secs > 0 {
-            Some(Instant::now() + Duration::from_secs(duration_secs as u64))
-        } else {
-            None
+        let state = VWAPJobState {
+            ends_at: if duration_secs > 0 {
+                Some(chrono::Utc::now().timestamp() + duration_secs as i64)
+            } else {
+                None
+            },
+            ..Default::default()
         };
 
+        check_orders_with_new_client(vec![ProposedOrder {
+            market_index: args.market_index.unwrap_or(0) as u16,
+            base_asset_amount: to_base_precision(args.size_per_order.unwrap_or(0.0)),
+            reduce_only: false,
+        }], args.sub_account_id).await?;
+
         let order_id = uuid::Uuid::new_v4().to_string();
+        let job = JobRecord::new(&order_id, JobKind::Vwap, &args, &state);
+        if let Some(store) = job_store() {
+            store.save(&job)?;
+        }
+        Self::spawn_job(job);
 
-        let vwap_tracker_clone = Arc::clone(&vwap_tracker);
-        let order_id_clone = order_id.clone();
+        let result = format!("VWAP orders initiated with ID: {}", order_id);
+        reservation.complete(&result);
+        Ok(result)
+    }
 
-        tokio::spawn(async move {
-            let rpc_url = SOLANA_MAINNET_RPC_URL;
-            let agent_vault: String = env::var("AGENT_VAULT").unwrap();
-            let secret_key_str = env::var("AGENT_KEYPAIR").unwrap();
-            let secret_key_bytes: Vec<u8> = secret_key_str
-                .split(',')
-                .map(|s| s.parse::<u8>().expect("Invalid byte"))
-                .collect();
-            let kp = Keypair::from_bytes(&secret_key_bytes).expect("Invalid private key");
-
-            let mut wallet: Wallet = Wallet::from(kp);
-
-            let agent_vault_pubkey = Pubkey::from_str(&agent_vault).unwrap();
-            wallet.to_delegated(agent_vault_pubkey);
-            let wallet_clone = wallet.clone(); 
-            let client = DriftClient::new(
-                Context::MainNet,
-                RpcClient::new(rpc_url.to_string()),
-                wallet_clone,
-            ).await.unwrap();
-
-            let user = client
-                .get_user_account(&wallet.default_sub_account())
-                .await
-                .map_err(|_| DriftDataError { 
-                    message: "Failed to init Drift user".to_string()
-                }).unwrap();
-
-            let mut interval_counter = 0;
-
-            loop {
-                if stop_signal.load(Ordering::SeqCst) || stop_time.map_or(false, |t| Instant::now() > t) {
-                    println!("VWAP strategy stopped.");
-                    break;
-                }
+}
 
-                if interval_counter < history_warm_up {
-                    interval_counter += 1;
-                    sleep(Duration::from_secs(recalculation_interval.unwrap_or(0) as u64)).await;
-                    continue;
-                }
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct VWAPJobState {
+    pub ends_at: Option<i64>,       // Unix timestamp at which the strategy stops, if bounded
+    pub intervals_warmed_up: i32,
+    pub orders_placed: u64,
+    #[serde(default)]
+    pub pending_user_order_id: Option<u8>, // Client order id of the order being sent
+}
 
-                let market_id = MarketId::from((market_index.unwrap_or(0) as u16, MarketType::Perp)); 
-                let price = client.oracle_price(market_id).await.unwrap();
-                let price_scaled = (price as f64) / 1_000_000.0;
-                let vwap_price = calculate_vwap(&vwap_tracker, price_scaled).await.unwrap();
+impl DriftVWAPOrders {
+    fn tracker() -> Arc<VWAPTracker> {
+        static VWAP_TRACKER: OnceLock<Arc<VWAPTracker>> = OnceLock::new();
+        VWAP_TRACKER.get_or_init(|| Arc::new(VWAPTracker::default())).clone()
+    }
 
-                
+    pub fn spawn_job(job: JobRecord) {
+        tokio::spawn(async move {
+            let job_id = job.id.clone();
+            if let Err(err) = Self::run_job(job).await {
+                eprintln!("VWAP job {} failed: {}", job_id, err);
+                persist_job_status(&job_id, JobStatus::Flagged, Some(err.to_string()));
+            }
+            release_job_stop_flag(&job_id);
+        });
+    }
 
-                let order_price = (vwap_price * PRICE_PRECISION_U64 as f64) as u64; 
+    async fn run_job(job: JobRecord) -> Result<(), DriftDataError> {
+        let args: DriftVWAPOrdersArgs = serde_json::from_value(job.args.clone()).map_err(|_| DriftDataError {
+            message: "Failed to parse VWAP job args".to_string()
+        })?;
+        let mut state: VWAPJobState = serde_json::from_value(job.state.clone()).unwrap_or_default();
 
-                let order = drift_rs::types::NewOrder::limit(market_id)
-                    .amount(size_per_order.unwrap_or(0) * LAMPORTS_PER_SOL_I64)
-                    .price(order_price)
-                    .build();
+        let vwap_tracker = Self::tracker();
+        let history_warm_up = args.history_warm_up.unwrap_or(5);
+        let market_index = args.market_index;
+        let size_per_order = args.size_per_order;
+        let recalculation_interval = args.timeframe;
 
-                client
-                    .sign_and_send(drift_rs::TransactionBuilder::new(
-                        client.program_data(),
-                        wallet.default_sub_account(),
-                        std::borrow::Cow::Borrowed(&user),
-                        true,
-                    ).place_orders(vec![order]).build()).await.unwrap();
+        let stop_signal = job_stop_flag(&job);
 
-                let mut tracker = vwap_tracker_clone.orders.lock().await;
-                tracker.entry(order_id_clone.clone()).or_insert(0);
-                drop(tracker);
+        let client = init_drift_client().await?;
+        let wallet = client.wallet().clone();
+        let sub_account = sub_account_pubkey(&wallet, args.sub_account_id);
 
+        loop {
+            if stop_signal.load(Ordering::SeqCst) {
+                println!("VWAP strategy stopped.");
+                return Ok(());
+            }
+            if state.ends_at.map_or(false, |t| chrono::Utc::now().timestamp() > t) {
+                println!("VWAP strategy stopped.");
+                break;
+            }
+
+            if state.intervals_warmed_up < history_warm_up {
+                state.intervals_warmed_up += 1;
+                persist_job_state(&job.id, &state);
                 sleep(Duration::from_secs(recalculation_interval.unwrap_or(0) as u64)).await;
+                continue;
             }
-        });
 
-        Ok(format!("VWAP orders initiated with ID: {}", order_id))
+            let market_id = MarketId::from((market_index.unwrap_or(0) as u16, MarketType::Perp)); 
+            let price = client.oracle_price(market_id).await.map_err(|_| DriftDataError {
+                message: "Failed to get oracle price".to_string()
+            })?;
+            let price_scaled = (price as f64) / 1_000_000.0;
+            let vwap_price = calculate_vwap(&vwap_tracker, price_scaled).await?;
+
+            let order_price = (vwap_price * PRICE_PRECISION_U64 as f64) as u64; 
+
+            let mut order = drift_rs::types::NewOrder::limit(market_id)
+                .amount(to_base_precision(size_per_order.unwrap_or(0.0)))
+                .price(order_price)
+                .build();
+
+            // Earlier orders may have filled since the last check, so the limits need fresh positions.
+            let user = client.get_user_account(&sub_account).await.map_err(|_| DriftDataError {
+                message: "Failed to get Drift user".to_string()
+            })?;
+            risk_guard().check_orders(&client, &user, &[ProposedOrder {
+                market_index: market_index.unwrap_or(0) as u16,
+                base_asset_amount: to_base_precision(size_per_order.unwrap_or(0.0)),
+                reduce_only: false,
+            }]).await?;
+
+            order.user_order_id = next_user_order_id(&user);
+            state.pending_user_order_id = Some(order.user_order_id);
+            persist_job_state(&job.id, &state);
+            send_order_with_retry(&client, &wallet, &user, order).await.map_err(|e| DriftDataError {
+                message: format!("Failed to place VWAP order: {}", e)
+            })?;
+
+            state.orders_placed += 1;
+            state.pending_user_order_id = None;
+            persist_job_state(&job.id, &state);
+
+            let mut tracker = vwap_tracker.orders.lock().await;
+            tracker.entry(job.id.clone()).or_insert(0);
+            drop(tracker);
+
+            sleep(Duration::from_secs(recalculation_interval.unwrap_or(0) as u64)).await;
+        }
+
+        persist_job_status(&job.id, JobStatus::Completed, None);
+        Ok(())
     }
 
+    /// VWAP only places resting limit orders, so it can always pick up again unless its
+    /// run window closed while the process was down. An order that was being sent at the
+    /// restart and is resting under its client order id is counted, so it is not placed again.
+    pub fn reconcile_job(job: &mut JobRecord, user: &User) -> Result<(), String> {
+        let args: DriftVWAPOrdersArgs = serde_json::from_value(job.args.clone())
+            .map_err(|_| "Failed to parse VWAP job args".to_string())?;
+        let mut state: VWAPJobState = serde_json::from_value(job.state.clone()).unwrap_or_default();
+        if let Some(ends_at) = state.ends_at {
+            if chrono::Utc::now().timestamp() > ends_at {
+                return Err("VWAP run window ended while the job was offline".to_string());
+            }
+        }
+
+        if let Some(user_order_id) = state.pending_user_order_id.take() {
+            if has_open_order(user, args.market_index.unwrap_or(0) as u16, user_order_id) {
+                state.orders_placed += 1;
+            }
+            job.state = serde_json::to_value(&state).unwrap_or_default();
+        }
+        Ok(())
+    }
 }
 
 
@@ -560,7 +868,8 @@ pub struct DriftTrailingStopOrderArgs {
     pub position_type: Option<String>,
     pub entry_price: Option<f64>,
     pub trailing_stop_percentage: Option<f64>, 
-    pub total_amount: Option<i64>
+    pub total_amount: Option<f64>,
+    pub sub_account_id: Option<u16>,
 }
 #[derive(Deserialize, Serialize)]
 pub struct DriftTrailingStopOrders;
@@ -582,22 +891,59 @@ impl Tool for DriftTrailingStopOrders {
                         "market_index": { "type": "integer", "description": "The market index for the perp trade." },
                         "position_type": { "type": "string", "enum": ["long", "short"], "description": "Position type." },
                         "trailing_stop_percentage": { "type": "number", "description": "The trailing stop percentage." },
-                        "total_amount": { "type": "integer", "description": "The total amount of base asset to trade." },
-                        "entry_price": { "type": "number", "description": "The price for limit orders (optional, if not provided, a market order is used)." }
+                        "total_amount": { "type": "number", "description": "The total amount of base asset to trade, fractions allowed; the direction comes from position_type." },
+                        "entry_price": { "type": "number", "description": "The price for limit orders (optional, if not provided, a market order is used)." },
+                        "sub_account_id": { "type": "integer", "description": "Sub-account to trade from (optional, defaults to 0)." }
                     },
             }),
         }
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        Self::is_short(&args)?;
+        if approval_mode_enabled() {
+            let summary = format!(
+                "trailing stop: {} {} on market {} with a {}% trailing stop",
+                args.position_type.clone().unwrap_or_default(),
+                args.total_amount.unwrap_or(0.0),
+                args.market_index.unwrap_or(0),
+                args.trailing_stop_percentage.unwrap_or(5.0)
+            );
+            return submit_intent(Self::NAME, summary, &args);
+        }
         Self::execute_trailing_stop_orders(args).await
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct TrailingStopJobState {
+    pub opened: bool,           // Whether the entry order has been sent
+    pub entry_price: f64,       // Prices in USD
+    pub highest_price: f64,
+    pub lowest_price: f64,
+    pub stop_price: f64,
+    #[serde(default)]
+    pub pending_user_order_id: Option<u8>, // Client order id of the close order being sent
+}
+
 impl DriftTrailingStopOrders {
-    pub async fn execute_trailing_stop_orders(args: DriftTrailingStopOrderArgs) -> Result<String, DriftDataError> {
+    fn tracker() -> Arc<TrailingStopTracker> {
         static TRAILING_STOP_TRACKER: OnceLock<Arc<TrailingStopTracker>> = OnceLock::new();
-        let trailing_stop_tracker = TRAILING_STOP_TRACKER.get_or_init(|| Arc::new(TrailingStopTracker::default())).clone();
+        TRAILING_STOP_TRACKER.get_or_init(|| Arc::new(TrailingStopTracker::default())).clone()
+    }
+
+    /// Whether the job trades a short; a missing `position_type` means long.
+    fn is_short(args: &DriftTrailingStopOrderArgs) -> Result<bool, DriftDataError> {
+        match args.position_type.as_deref() {
+            None | Some("long") => Ok(false),
+            Some("short") => Ok(true),
+            Some(other) => Err(DriftDataError { message: format!("Unknown position_type {}, expected long or short", other) }),
+        }
+    }
+
+    pub async fn execute_trailing_stop_orders(args: DriftTrailingStopOrderArgs) -> Result<String, DriftDataError> {
+        let is_short = Self::is_short(&args)?;
+        let trailing_stop_tracker = Self::tracker();
     
         let order_id = uuid::Uuid::new_v4().to_string();
     
@@ -606,102 +952,172 @@ impl DriftTrailingStopOrders {
             return Ok(format!("Current trailing stop progress: {:?}", *tracker));
         }
         drop(tracker);
+
+        // A repeated tool call should not start a second job.
+        let request_args = serde_json::to_value(&args).unwrap_or_default();
+        let reservation = match reserve_request(DriftTrailingStopOrders::NAME, &request_args) {
+            Ok(reservation) => reservation,
+            Err(duplicate) => return Ok(duplicate),
+        };
+
+        let base_amount = to_base_precision(args.total_amount.unwrap_or(0.0)).abs();
+        check_orders_with_new_client(vec![ProposedOrder {
+            market_index: args.market_index.unwrap_or(0),
+            base_asset_amount: if is_short { -base_amount } else { base_amount },
+            reduce_only: false,
+        }], args.sub_account_id).await?;
+
+        let job = JobRecord::new(&order_id, JobKind::TrailingStop, &args, &TrailingStopJobState::default());
+        if let Some(store) = job_store() {
+            store.save(&job)?;
+        }
+        Self::spawn_job(job);
     
-        let trailing_stop_tracker_clone = Arc::clone(&trailing_stop_tracker);
-        let order_id_clone = order_id.clone();
+        let result = format!("Trailing stop orders initiated with ID: {}", order_id);
+        reservation.complete(&result);
+        Ok(result)
+    }
 
+    pub fn spawn_job(job: JobRecord) {
         tokio::spawn(async move {
-            let rpc_url = SOLANA_MAINNET_RPC_URL;
-            let agent_vault: String = env::var("AGENT_VAULT").unwrap();
-            let secret_key_str = env::var("AGENT_KEYPAIR").unwrap();
-            let secret_key_bytes: Vec<u8> = secret_key_str
-                .split(',')
-                .map(|s| s.parse::<u8>().expect("Invalid byte"))
-                .collect();
-            let kp = Keypair::from_bytes(&secret_key_bytes).expect("Invalid private key");
-    
-            let mut wallet: Wallet = Wallet::from(kp);
-            let agent_vault_pubkey = Pubkey::from_str(&agent_vault).unwrap();
-            wallet.to_delegated(agent_vault_pubkey);
-            let wallet_clone = wallet.clone();
-            let client = DriftClient::new(
-                Context::MainNet,
-                RpcClient::new(rpc_url.to_string()),
-                wallet_clone,
-            ).await.unwrap();
-    
-            let user = client
-                .get_user_account(&wallet.default_sub_account())
-                .await
-                .map_err(|_| DriftDataError { 
-                    message: "Failed to init Drift user".to_string()
-                }).unwrap();
-    
-            let market_id = MarketId::perp(args.market_index.unwrap());
-            let entry_price  = get_token_oracle_price(market_id, &client).await.unwrap() as f64 / LAMPORTS_PER_SOL as f64;
-            //let entry_price = client.oracle_price(market_id).await.unwrap() as f64 / LAMPORTS_PER_SOL as f64;
-            let trailing_stop_percentage = args.trailing_stop_percentage.unwrap_or(5.0) / 100.0;
+            let job_id = job.id.clone();
+            if let Err(err) = Self::run_job(job).await {
+                eprintln!("Trailing stop job {} failed: {}", job_id, err);
+                persist_job_status(&job_id, JobStatus::Flagged, Some(err.to_string()));
+            }
+            release_job_stop_flag(&job_id);
+        });
+    }
+
+    async fn run_job(job: JobRecord) -> Result<(), DriftDataError> {
+        let args: DriftTrailingStopOrderArgs = serde_json::from_value(job.args.clone()).map_err(|_| DriftDataError {
+            message: "Failed to parse trailing stop job args".to_string()
+        })?;
+        let mut state: TrailingStopJobState = serde_json::from_value(job.state.clone()).unwrap_or_default();
+        let trailing_stop_tracker_clone = Self::tracker();
+        let stop_signal = job_stop_flag(&job);
+
+        let client = init_drift_client().await?;
+        let wallet = client.wallet().clone();
+        let sub_account = sub_account_pubkey(&wallet, args.sub_account_id);
+
+        let market_index = args.market_index.ok_or(DriftDataError {
+            message: "Missing market_index".to_string()
+        })?;
+        let base_amount = to_base_precision(args.total_amount.ok_or(DriftDataError {
+            message: "Missing total_amount".to_string()
+        })?).abs();
+        let is_short = Self::is_short(&args)?;
+        // Signed size of the entry; the close is its opposite.
+        let entry_amount = if is_short { -base_amount } else { base_amount };
+        let market_id = MarketId::perp(market_index);
+        let trailing_stop_fraction = args.trailing_stop_percentage.unwrap_or(5.0) / 100.0;
+        let stop_from = |price: f64| if is_short { price * (1.0 + trailing_stop_fraction) } else { price * (1.0 - trailing_stop_fraction) };
+
+        if !state.opened {
+            let entry_price  = get_token_oracle_price(market_id, &client).await? as f64 / PRICE_PRECISION as f64;
             let new_order = drift_rs::types::NewOrder::market(market_id)
-                .amount(args.total_amount.unwrap() * LAMPORTS_PER_SOL_I64)
+                .amount(entry_amount)
                 //.price(entry_price_scaled) // TODO: limit orders
                 .build();
-            //client.sign_and_send(drift_rs::TransactionBuilder::new(
-            //    client.program_data(),
-            //    wallet.default_sub_account(),
-            //    std::borrow::Cow::Borrowed(&user),
-            //    true,
-            //).place_orders(vec![new_order]).build()).await.unwrap();
-
-            if let Err(err) = send_order_with_retry(&client, &wallet, &user, new_order).await {
-                eprintln!("Failed to send open Trailing Stop order: {:?}", err);
-                return;
-            }
 
-            let mut highest_price = entry_price;
-            let mut lowest_price = entry_price;
-            let mut stop_price = entry_price * (1.0 - trailing_stop_percentage / 100.0);
+            let user = client.get_user_account(&sub_account).await.map_err(|_| DriftDataError {
+                message: "Failed to get Drift user".to_string()
+            })?;
+            risk_guard().check_orders(&client, &user, &[ProposedOrder {
+                market_index,
+                base_asset_amount: entry_amount,
+                reduce_only: false,
+            }]).await?;
+
+            send_order_with_retry(&client, &wallet, &user, new_order).await.map_err(|err| DriftDataError {
+                message: format!("Failed to send open Trailing Stop order: {}", err)
+            })?;
+
+            state = TrailingStopJobState {
+                opened: true,
+                entry_price,
+                highest_price: entry_price,
+                lowest_price: entry_price,
+                stop_price: stop_from(entry_price),
+                ..Default::default()
+            };
+            persist_job_state(&job.id, &state);
+        }
     
-            loop {
-                let current_price  = get_token_oracle_price(market_id, &client).await.unwrap() as f64 / LAMPORTS_PER_SOL as f64;
-                //let current_price = client.oracle_price(market_id).await.unwrap() as f64 / LAMPORTS_PER_SOL as f64;
-            
-                if let Some(position_type) = &args.position_type {
-                    if (position_type == "long" && current_price < stop_price) ||
-                       (position_type == "short" && current_price > stop_price) {
-                        println!("Trailing stop price hit: {}. Closing position", stop_price);
-                        let close_order = drift_rs::types::NewOrder::market(market_id)
-                            .amount(if position_type == "long" {
-                                -args.total_amount.unwrap() * LAMPORTS_PER_SOL_I64
-                            } else {
-                                args.total_amount.unwrap() * LAMPORTS_PER_SOL_I64
-                            })
-                            .build();
-                        client.sign_and_send(drift_rs::TransactionBuilder::new(
-                            client.program_data(),
-                            wallet.default_sub_account(),
-                            std::borrow::Cow::Borrowed(&user),
-                            true,
-                        ).place_orders(vec![close_order]).build()).await.unwrap();
-                        break;
-                    }
-                    if position_type == "long" && current_price > highest_price {
-                        highest_price = current_price;
-                        stop_price = highest_price * (1.0 - trailing_stop_percentage / 100.0);
-                    } else if position_type == "short" && current_price < lowest_price {
-                        lowest_price = current_price;
-                        stop_price = lowest_price * (1.0 + trailing_stop_percentage / 100.0);
-                    }
-                } else {
-                    break;
-                }
-                let mut tracker = trailing_stop_tracker_clone.orders.lock().await;
-                *tracker.entry(order_id_clone.clone()).or_insert(current_price) = stop_price;
-                drop(tracker);
-                sleep(Duration::from_secs(3)).await;
+        loop {
+            if stop_signal.load(Ordering::SeqCst) {
+                println!("Trailing stop job {} stopped.", job.id);
+                return Ok(());
             }
-        });
-    
-        Ok(format!("Trailing stop orders initiated with ID: {}", order_id))
+            let current_price  = get_token_oracle_price(market_id, &client).await? as f64 / PRICE_PRECISION as f64;
+
+            if (!is_short && current_price < state.stop_price) || (is_short && current_price > state.stop_price) {
+                println!("Trailing stop price hit: {}. Closing position", state.stop_price);
+                let mut close_order = drift_rs::types::NewOrder::market(market_id)
+                    .amount(-entry_amount)
+                    .reduce_only(true)
+                    .build();
+                // The position has moved since the entry, so check and send against the current account.
+                let user = client.get_user_account(&sub_account).await.map_err(|_| DriftDataError {
+                    message: "Failed to get Drift user".to_string()
+                })?;
+                risk_guard().check_orders(&client, &user, &[ProposedOrder {
+                    market_index,
+                    base_asset_amount: -entry_amount,
+                    reduce_only: true,
+                }]).await?;
+                close_order.user_order_id = next_user_order_id(&user);
+                state.pending_user_order_id = Some(close_order.user_order_id);
+                persist_job_state(&job.id, &state);
+                send_order_with_retry(&client, &wallet, &user, close_order).await.map_err(|e| DriftDataError {
+                    message: format!("Failed to send close Trailing Stop order: {}", e)
+                })?;
+                break;
+            }
+            if !is_short && current_price > state.highest_price {
+                state.highest_price = current_price;
+                state.stop_price = stop_from(state.highest_price);
+                persist_job_state(&job.id, &state);
+            } else if is_short && current_price < state.lowest_price {
+                state.lowest_price = current_price;
+                state.stop_price = stop_from(state.lowest_price);
+                persist_job_state(&job.id, &state);
+            }
+            let mut tracker = trailing_stop_tracker_clone.orders.lock().await;
+            *tracker.entry(job.id.clone()).or_insert(current_price) = state.stop_price;
+            drop(tracker);
+            sleep(Duration::from_secs(3)).await;
+        }
+
+        persist_job_status(&job.id, JobStatus::Completed, None);
+        Ok(())
+    }
+
+    /// Once the entry has gone out the job only makes sense while that position is still open.
+    /// An entry that was never confirmed is flagged rather than re-sent, to avoid doubling up,
+    /// and so is a close order still resting from before the restart.
+    pub fn reconcile_job(job: &mut JobRecord, user: &User) -> Result<(), String> {
+        let args: DriftTrailingStopOrderArgs = serde_json::from_value(job.args.clone())
+            .map_err(|_| "Failed to parse trailing stop job args".to_string())?;
+        let state: TrailingStopJobState = serde_json::from_value(job.state.clone()).unwrap_or_default();
+        let market_index = args.market_index.ok_or("Missing market_index".to_string())?;
+
+        if !state.opened {
+            return Err("Entry order was not confirmed before the restart".to_string());
+        }
+        if let Some(user_order_id) = state.pending_user_order_id {
+            if has_open_order(user, market_index, user_order_id) {
+                return Err(format!("Close order with client order id {} is still resting in market {}", user_order_id, market_index));
+            }
+        }
+
+        let expected_sign = if Self::is_short(&args).map_err(|e| e.message)? { -1 } else { 1 };
+        match find_perp_position(&user.perp_positions, market_index) {
+            Some(position) if position.base_asset_amount.signum() == expected_sign => Ok(()),
+            Some(_) => Err(format!("Position in market {} has flipped direction", market_index)),
+            None => Err(format!("Position in market {} was closed while the job was offline", market_index)),
+        }
     }
 }
 
@@ -711,6 +1127,8 @@ pub struct DriftClosePerpPositionArgs {
     pub market_index: u16,     // The market index of the asset
     pub position_type: Option<String>, // "long" or "short"
     pub percentage: Option<f64>,       // 0.0 to 1.0 (e.g., 1.0 = 100%, 0.5 = 50%)
+    pub sub_account_id: Option<u16>,
+    pub settle_pnl: Option<bool>,      // Settle the market's PnL once the close has filled
 }
 
 #[derive(Deserialize, Serialize)]
@@ -732,7 +1150,9 @@ impl Tool for DriftClosePerpPosition {
                 "properties": {
                     "market_index": { "type": "integer", "description": "The market index for the perp trade." },
                     "position_type": { "type": "string", "enum": ["long", "short"], "description": "The position type to close." },
-                    "percentage": { "type": "number", "minimum": 0.01, "maximum": 1.0, "description": "Percentage of the position to close (1.0 = 100%)." }
+                    "percentage": { "type": "number", "minimum": 0.01, "maximum": 1.0, "description": "Percentage of the position to close (1.0 = 100%)." },
+                    "sub_account_id": { "type": "integer", "description": "Sub-account holding the position (optional, defaults to 0)." },
+                    "settle_pnl": { "type": "boolean", "description": "Settle the market's PnL into USDC once the close has filled (default: false)." }
                 },
                 "required": ["market_index"]
             }),
@@ -740,6 +1160,15 @@ impl Tool for DriftClosePerpPosition {
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        if approval_mode_enabled() {
+            let summary = format!(
+                "close {}% of {} position on market {}",
+                args.percentage.unwrap_or(1.0) * 100.0,
+                args.position_type.clone().unwrap_or("any".to_string()),
+                args.market_index
+            );
+            return submit_intent(Self::NAME, summary, &args);
+        }
         tokio::spawn(async move {
             Self::execute_close_perp_position(args).await
         })
@@ -751,7 +1180,14 @@ impl Tool for DriftClosePerpPosition {
 }
 
 impl DriftClosePerpPosition {
-    async fn execute_close_perp_position(args: DriftClosePerpPositionArgs) -> Result<String, DriftDataError> {
+    pub async fn execute_close_perp_position(args: DriftClosePerpPositionArgs) -> Result<String, DriftDataError> {
+        // A repeated partial close would close the same percentage of what is left.
+        let request_args = serde_json::to_value(&args).unwrap_or_default();
+        let reservation = match reserve_request(DriftClosePerpPosition::NAME, &request_args) {
+            Ok(reservation) => reservation,
+            Err(duplicate) => return Ok(duplicate),
+        };
+
         let rpc_url = SOLANA_MAINNET_RPC_URL.to_string();
         let agent_vault = env::var("AGENT_VAULT").expect("Missing AGENT_VAULT env variable");
         let agent_vault_clone = agent_vault.clone();
@@ -784,6 +1220,7 @@ impl DriftClosePerpPosition {
             account_pubkey: None, // Defaults to agent vault
             market_index: Some(args.market_index), // Filter by market index
             position_type: args.position_type.clone(), // Filter by long/short
+            sub_account_id: args.sub_account_id,
         }, &client)
         .await;
         //.unwrap();
@@ -829,6 +1266,7 @@ impl DriftClosePerpPosition {
         }
 
         let position = &positions[0];
+        let base_before_close = position.base_asset_amount;
         let close_percent = args.percentage.unwrap_or(1.0);
 
         let close_amount = ((position.base_asset_amount as f64) * close_percent).round() as i64;
@@ -837,9 +1275,11 @@ impl DriftClosePerpPosition {
         }
         let close_order = PerpOrder {
             market_index: args.market_index,
-            amount: -close_amount / LAMPORTS_PER_SOL_I64, // Negative to close the position
+            amount: -close_amount as f64 / BASE_PRECISION as f64, // Negative to close the position
             price: None,
             post_only: None,
+            reduce_only: Some(true),
+            max_slippage_bps: None,
         };
         // let close_result = DriftPlacePerpOrders::place_perp_orders(DriftPlacePerpOrdersArgs {
         //     orders: vec![close_order],
@@ -849,9 +1289,20 @@ impl DriftClosePerpPosition {
         // Ok(format!("Position closed successfully: {}", close_result.unwrap()))
         match DriftPlacePerpOrders::place_perp_orders(DriftPlacePerpOrdersArgs {
             orders: vec![close_order],
+            sub_account_id: args.sub_account_id,
         }, &client)
         .await {
-            Ok(result) => Ok(format!("Position closed successfully: {}", result)),
+            Ok(result) => {
+                let mut message = format!("Position closed successfully: {}", result);
+                if args.settle_pnl.unwrap_or(false) {
+                    match settle_pnl_after_close(&client, args.sub_account_id, args.market_index, base_before_close).await {
+                        Ok(settled) => message.push_str(&format!("\nSettled PnL:\n{}", format_settled_pnl(&settled))),
+                        Err(e) => message.push_str(&format!("\nFailed to settle PnL: {}", e)),
+                    }
+                }
+                reservation.complete(&message);
+                Ok(message)
+            }
             Err(e) => {
                 eprintln!("Error closing position: {:?}", e);  // Log the error
                 return Ok(format!("Error: Failed to close position: {:?}", e));
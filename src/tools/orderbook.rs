@@ -0,0 +1,276 @@
+use std::env;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use drift_rs::math::constants::{BASE_PRECISION, PRICE_PRECISION};
+
+use crate::tools::shared::*;
+use crate::data::perp_market_name;
+
+const DEFAULT_DRIFT_DLOB_URL: &str = "https://dlob.drift.trade";
+/// Levels per side fetched and shown by default; the slippage estimate never sees fewer.
+const DEFAULT_BOOK_DEPTH: usize = 20;
+
+/// Base URL of the Drift DLOB server, from DRIFT_DLOB_URL so a local stand-in can be used.
+pub fn drift_dlob_url() -> String {
+    env::var("DRIFT_DLOB_URL").unwrap_or(DEFAULT_DRIFT_DLOB_URL.to_string())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct L2Level {
+    pub price: f64,
+    pub size: f64,
+}
+
+/// L2 book with bids best (highest) first and asks best (lowest) first.
+#[derive(Debug, Clone, Serialize)]
+pub struct L2Book {
+    pub market_index: u16,
+    pub bids: Vec<L2Level>,
+    pub asks: Vec<L2Level>,
+}
+
+impl L2Book {
+    pub fn mid_price(&self) -> Option<f64> {
+        match (self.bids.first(), self.asks.first()) {
+            (Some(bid), Some(ask)) => Some((bid.price + ask.price) / 2.0),
+            _ => None,
+        }
+    }
+}
+
+fn parse_levels(levels: &Value) -> Vec<L2Level> {
+    levels
+        .as_array()
+        .map(|levels| {
+            levels.iter().filter_map(|level| {
+                Some(L2Level {
+                    price: value_as_f64(&level["price"])? / PRICE_PRECISION as f64,
+                    size: value_as_f64(&level["size"])? / BASE_PRECISION as f64,
+                })
+            }).collect()
+        })
+        .unwrap_or_default()
+}
+
+pub async fn get_l2_book(market_index: u16, depth: usize) -> Result<L2Book, DriftDataError> {
+    let url = format!("{}/l2", drift_dlob_url());
+    let response: Value = reqwest::Client::new()
+        .get(&url)
+        .query(&[
+            ("marketIndex", market_index.to_string()),
+            ("marketType", "perp".to_string()),
+            ("depth", depth.to_string()),
+            // Without the vAMM's liquidity the book is far thinner than what market orders fill against.
+            ("includeVamm", "true".to_string()),
+        ])
+        .send()
+        .await
+        .map_err(|e| DriftDataError { message: format!("Failed to fetch L2 book: {}", e) })?
+        .json()
+        .await
+        .map_err(|e| DriftDataError { message: format!("Failed to parse L2 book: {}", e) })?;
+
+    let mut bids = parse_levels(&response["bids"]);
+    let mut asks = parse_levels(&response["asks"]);
+    bids.sort_by(|a, b| b.price.total_cmp(&a.price));
+    asks.sort_by(|a, b| a.price.total_cmp(&b.price));
+    Ok(L2Book { market_index, bids, asks })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SlippageEstimate {
+    /// Signed size in whole units of the asset; positive buys from the asks.
+    pub size: f64,
+    pub filled_size: f64,
+    pub average_price: f64,
+    pub mid_price: f64,
+    /// Distance of the average fill price from mid, against us, in basis points.
+    pub slippage_bps: f64,
+    pub fully_filled: bool,
+}
+
+/// Walks the book to estimate the average fill of a market order of signed `size`.
+pub fn estimate_slippage(book: &L2Book, size: f64) -> Result<SlippageEstimate, DriftDataError> {
+    let mid_price = book.mid_price().ok_or(DriftDataError {
+        message: format!("Order book for market {} is empty on one side", book.market_index)
+    })?;
+    let levels = if size > 0.0 { &book.asks } else { &book.bids };
+
+    let mut remaining = size.abs();
+    let mut filled_size = 0.0;
+    let mut cost = 0.0;
+    for level in levels {
+        if remaining <= 0.0 {
+            break;
+        }
+        let take = level.size.min(remaining);
+        filled_size += take;
+        cost += take * level.price;
+        remaining -= take;
+    }
+    if filled_size <= 0.0 {
+        return Err(DriftDataError { message: format!("No liquidity in market {}", book.market_index) });
+    }
+
+    let average_price = cost / filled_size;
+    let slippage_bps = if size > 0.0 {
+        (average_price - mid_price) / mid_price * 10_000.0
+    } else {
+        (mid_price - average_price) / mid_price * 10_000.0
+    };
+    Ok(SlippageEstimate {
+        size,
+        filled_size,
+        average_price,
+        mid_price,
+        slippage_bps,
+        fully_filled: remaining <= 0.0,
+    })
+}
+
+/// Estimates slippage for a market order and rejects it if the visible book cannot fill it
+/// or the estimate is above `max_slippage_bps`.
+pub async fn check_market_order_slippage(market_index: u16, size: f64, max_slippage_bps: f64) -> Result<SlippageEstimate, DriftDataError> {
+    let book = get_l2_book(market_index, DEFAULT_BOOK_DEPTH).await?;
+    let estimate = estimate_slippage(&book, size)?;
+    if !estimate.fully_filled {
+        return Err(DriftDataError {
+            message: format!(
+                "Market order rejected: the visible book on market {} only fills {} of {}",
+                market_index, estimate.filled_size, size.abs()
+            ),
+        });
+    }
+    if estimate.slippage_bps > max_slippage_bps {
+        return Err(DriftDataError {
+            message: format!(
+                "Market order rejected: estimated slippage of {:.1} bps on market {} is above the limit of {:.1} bps (average fill ${:.6}, mid ${:.6})",
+                estimate.slippage_bps, market_index, max_slippage_bps, estimate.average_price, estimate.mid_price
+            ),
+        });
+    }
+    Ok(estimate)
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftOrderbookArgs {
+    pub market_index: u16,
+    pub depth: Option<usize>,
+    pub size: Option<f64>,      // Signed size in whole units to estimate slippage for, positive to buy
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftOrderbook;
+
+impl Tool for DriftOrderbook {
+    const NAME: &'static str = "drift_orderbook";
+
+    type Error = DriftDataError;
+    type Args = DriftOrderbookArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_orderbook".to_string(),
+            description: "Fetches the L2 order book of a Drift perp market and, given a size, estimates the average fill price and slippage of a market order of that size. Use it before placing large market orders.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "market_index": { "type": "integer", "description": "The market index of the perp market." },
+                    "depth": { "type": "integer", "description": format!("Number of price levels per side to show (default: {}).", DEFAULT_BOOK_DEPTH) },
+                    "size": { "type": "number", "description": "Order size in whole units to estimate slippage for; positive to buy, negative to sell (optional)." }
+                },
+                "required": ["market_index"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let depth = args.depth.unwrap_or(DEFAULT_BOOK_DEPTH);
+        // Showing fewer levels still fetches the full default depth for the slippage estimate.
+        let book = get_l2_book(args.market_index, depth.max(DEFAULT_BOOK_DEPTH)).await?;
+
+        let mut result = format!("**{} order book:**\n", perp_market_name(args.market_index));
+        result.push_str(&format!(
+            "- Mid Price: {}\n- Asks:\n",
+            book.mid_price().map_or("n/a".to_string(), |mid| format!("${:.6}", mid))
+        ));
+        for level in book.asks.iter().take(depth).rev() {
+            result.push_str(&format!("  ${:.6} x {}\n", level.price, level.size));
+        }
+        result.push_str("- Bids:\n");
+        for level in book.bids.iter().take(depth) {
+            result.push_str(&format!("  ${:.6} x {}\n", level.price, level.size));
+        }
+
+        if let Some(size) = args.size {
+            let estimate = estimate_slippage(&book, size)?;
+            result.push_str(&format!(
+                "\n**Market {} of {}:**\n- Average Fill Price: ${:.6}\n- Estimated Slippage: {:.1} bps\n- Fillable From Visible Book: {}{}\n",
+                if size > 0.0 { "buy" } else { "sell" },
+                size.abs(),
+                estimate.average_price,
+                estimate.slippage_bps,
+                estimate.fully_filled,
+                if estimate.fully_filled { String::new() } else { format!(" (only {})", estimate.filled_size) },
+            ));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(price: f64, size: f64) -> L2Level {
+        L2Level { price, size }
+    }
+
+    fn book() -> L2Book {
+        L2Book {
+            market_index: 0,
+            bids: vec![level(99.0, 1.0), level(98.0, 2.0)],
+            asks: vec![level(101.0, 1.0), level(102.0, 2.0)],
+        }
+    }
+
+    #[test]
+    fn buy_walks_the_asks() {
+        let estimate = estimate_slippage(&book(), 2.0).unwrap();
+        assert_eq!(estimate.average_price, 101.5);
+        assert_eq!(estimate.mid_price, 100.0);
+        assert!((estimate.slippage_bps - 150.0).abs() < 1e-9);
+        assert!(estimate.fully_filled);
+    }
+
+    #[test]
+    fn sell_walks_the_bids() {
+        let estimate = estimate_slippage(&book(), -3.0).unwrap();
+        assert!((estimate.average_price - 295.0 / 3.0).abs() < 1e-9);
+        assert!((estimate.slippage_bps - (100.0 - 295.0 / 3.0) / 100.0 * 10_000.0).abs() < 1e-9);
+        assert_eq!(estimate.filled_size, 3.0);
+        assert!(estimate.fully_filled);
+    }
+
+    #[test]
+    fn order_larger_than_the_book_is_partially_filled() {
+        let estimate = estimate_slippage(&book(), 5.0).unwrap();
+        assert_eq!(estimate.filled_size, 3.0);
+        assert!((estimate.average_price - 305.0 / 3.0).abs() < 1e-9);
+        assert!(!estimate.fully_filled);
+    }
+
+    #[test]
+    fn empty_side_has_no_estimate() {
+        let mut book = book();
+        book.asks.clear();
+        let err = estimate_slippage(&book, 1.0).err().unwrap();
+        assert!(err.message.contains("empty on one side"));
+    }
+}
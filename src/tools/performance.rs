@@ -0,0 +1,432 @@
+use std::{env, fs, io::Write, path::PathBuf, str::FromStr, sync::{Arc, Mutex, OnceLock}};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::time::sleep;
+
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use drift_rs::{DriftClient, Pubkey};
+
+use crate::scheduler::Schedule;
+use crate::tools::shared::*;
+use crate::tools::vault::*;
+
+const DEFAULT_PERFORMANCE_STORE_PATH: &str = ".agent_vault_performance.jsonl";
+const DEFAULT_PERFORMANCE_SCHEDULE: &str = "1h";
+const ONE_YEAR_SECS: f64 = 365.0 * 24.0 * 3600.0;
+
+/// One recorded point of a vault's equity, share price and cumulative flows.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VaultSnapshot {
+    pub ts: i64,
+    pub vault_address: String,
+    pub equity: f64,
+    pub equity_usd: f64,
+    pub share_price: f64,
+    pub total_shares: u128,
+    pub shares_base: u32,
+    pub net_deposits: f64,
+    pub total_deposits: f64,
+    pub total_withdraws: f64,
+}
+
+impl From<&VaultInfo> for VaultSnapshot {
+    fn from(info: &VaultInfo) -> Self {
+        VaultSnapshot {
+            ts: chrono::Utc::now().timestamp(),
+            vault_address: info.vault_address.clone(),
+            equity: info.equity,
+            equity_usd: info.equity_usd,
+            share_price: info.share_price,
+            total_shares: info.total_shares,
+            shares_base: info.shares_base,
+            net_deposits: info.net_deposits,
+            total_deposits: info.total_deposits,
+            total_withdraws: info.total_withdraws,
+        }
+    }
+}
+
+/// Vault snapshots, appended as JSON lines to AGENT_PERFORMANCE_STORE (default `.agent_vault_performance.jsonl`).
+pub struct PerformanceStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+pub fn performance_store() -> &'static PerformanceStore {
+    static PERFORMANCE_STORE: OnceLock<PerformanceStore> = OnceLock::new();
+    PERFORMANCE_STORE.get_or_init(|| PerformanceStore {
+        path: PathBuf::from(env::var("AGENT_PERFORMANCE_STORE").unwrap_or(DEFAULT_PERFORMANCE_STORE_PATH.to_string())),
+        lock: Mutex::new(()),
+    })
+}
+
+impl PerformanceStore {
+    pub fn record(&self, snapshot: &VaultSnapshot) -> Result<(), DriftDataError> {
+        let line = serde_json::to_string(snapshot).map_err(|_| DriftDataError {
+            message: "Failed to serialize vault snapshot".to_string()
+        })?;
+        let _guard = self.lock.lock().unwrap();
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(|e| DriftDataError {
+                message: format!("Failed to write vault snapshot to {}: {}", self.path.display(), e)
+            })
+    }
+
+    /// Snapshots of `vault_address` taken at or after `since`, oldest first.
+    pub fn snapshots(&self, vault_address: &str, since: i64) -> Vec<VaultSnapshot> {
+        let _guard = self.lock.lock().unwrap();
+        let mut snapshots: Vec<VaultSnapshot> = fs::read_to_string(&self.path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str::<VaultSnapshot>(line).ok())
+            .filter(|snapshot| snapshot.vault_address == vault_address && snapshot.ts >= since)
+            .collect();
+        snapshots.sort_by_key(|snapshot| snapshot.ts);
+        snapshots
+    }
+}
+
+/// Fetches the vault and records a snapshot of it.
+pub async fn record_vault_snapshot(client: &DriftClient, vault: &Pubkey) -> Result<VaultSnapshot, DriftDataError> {
+    let info = get_vault_info(client, vault).await?;
+    let snapshot = VaultSnapshot::from(&info);
+    performance_store().record(&snapshot)?;
+    Ok(snapshot)
+}
+
+/// Starts the background recorder that snapshots each vault on AGENT_PERFORMANCE_SCHEDULE
+/// (an interval such as "1h" or a cron expression, default hourly).
+pub fn spawn_vault_recorder(client: Arc<DriftClient>, vaults: Vec<Pubkey>) -> Result<(), DriftDataError> {
+    let schedule = Schedule::from_str(&env::var("AGENT_PERFORMANCE_SCHEDULE").unwrap_or(DEFAULT_PERFORMANCE_SCHEDULE.to_string()))?;
+    tokio::spawn(async move {
+        loop {
+            for vault in &vaults {
+                if let Err(err) = record_vault_snapshot(&client, vault).await {
+                    eprintln!("Failed to record vault {} snapshot: {}", vault, err);
+                }
+            }
+            sleep(schedule.next_delay()).await;
+        }
+    });
+    Ok(())
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct VaultPerformance {
+    pub vault_address: String,
+    pub from_ts: i64,
+    pub to_ts: i64,
+    pub snapshots: usize,
+    pub start_equity: f64,
+    pub end_equity: f64,
+    /// Deposits minus withdrawals over the period, in vault tokens.
+    pub net_flows: f64,
+    /// Change in equity not explained by flows, in vault tokens.
+    pub pnl: f64,
+    pub time_weighted_return_percent: f64,
+    pub annualized_return_percent: Option<f64>,
+    pub max_drawdown_percent: f64,
+    pub current_drawdown_percent: f64,
+    /// Annualized volatility of the per-snapshot log returns, each scaled by its own interval.
+    pub volatility_percent: Option<f64>,
+    pub sharpe_ratio: Option<f64>,
+}
+
+/// Return between two snapshots with flows removed. Share price already excludes flows; when the
+/// shares were rebased in between, equity is used with the period's flows assumed at its start.
+fn period_return(prev: &VaultSnapshot, next: &VaultSnapshot) -> f64 {
+    if prev.shares_base == next.shares_base && prev.total_shares > 0 && next.total_shares > 0 && prev.share_price > 0.0 {
+        return next.share_price / prev.share_price - 1.0;
+    }
+    let invested = prev.equity + (next.net_deposits - prev.net_deposits);
+    if invested > 0.0 { next.equity / invested - 1.0 } else { 0.0 }
+}
+
+/// Time-weighted return, drawdown, volatility and Sharpe over `snapshots` (oldest first).
+/// `risk_free_rate` is annual, as a fraction.
+pub fn compute_vault_performance(snapshots: &[VaultSnapshot], risk_free_rate: f64) -> Option<VaultPerformance> {
+    let (first, last) = (snapshots.first()?, snapshots.last()?);
+    let returns: Vec<f64> = snapshots.windows(2).map(|pair| period_return(&pair[0], &pair[1])).collect();
+
+    // Drawdowns are measured on the flow-free growth index, not on equity.
+    let mut index = 1.0;
+    let mut peak = 1.0;
+    let mut max_drawdown: f64 = 0.0;
+    for r in &returns {
+        index *= 1.0 + r;
+        peak = f64::max(peak, index);
+        max_drawdown = max_drawdown.max(1.0 - index / peak);
+    }
+    let twr = index - 1.0;
+
+    let elapsed = (last.ts - first.ts) as f64;
+    let annualized_return = (elapsed > 0.0 && index > 0.0).then(|| index.powf(ONE_YEAR_SECS / elapsed) - 1.0);
+
+    // Snapshots are not evenly spaced (on-demand recordings land between scheduled ones), so each
+    // log return is measured against the length of its own period, in years.
+    let timed_returns: Vec<(f64, f64)> = snapshots
+        .windows(2)
+        .zip(&returns)
+        .map(|(pair, r)| ((1.0 + r).ln(), (pair[1].ts - pair[0].ts) as f64 / ONE_YEAR_SECS))
+        .filter(|(log_return, years)| *years > 0.0 && log_return.is_finite())
+        .collect();
+    let (volatility, sharpe_ratio) = if timed_returns.len() >= 2 {
+        let years: f64 = timed_returns.iter().map(|(_, years)| years).sum();
+        let drift = timed_returns.iter().map(|(log_return, _)| log_return).sum::<f64>() / years;
+        let variance = timed_returns
+            .iter()
+            .map(|(log_return, years)| (log_return - drift * years).powi(2) / years)
+            .sum::<f64>()
+            / (timed_returns.len() - 1) as f64;
+        let volatility = variance.sqrt();
+        let excess = drift - (1.0 + risk_free_rate).ln();
+        (Some(volatility), (volatility > 0.0).then(|| excess / volatility))
+    } else {
+        (None, None)
+    };
+
+    let net_flows = last.net_deposits - first.net_deposits;
+    Some(VaultPerformance {
+        vault_address: first.vault_address.clone(),
+        from_ts: first.ts,
+        to_ts: last.ts,
+        snapshots: snapshots.len(),
+        start_equity: first.equity,
+        end_equity: last.equity,
+        net_flows,
+        pnl: last.equity - first.equity - net_flows,
+        time_weighted_return_percent: twr * 100.0,
+        annualized_return_percent: annualized_return.map(|r| r * 100.0),
+        max_drawdown_percent: max_drawdown * 100.0,
+        current_drawdown_percent: (1.0 - index / peak) * 100.0,
+        volatility_percent: volatility.map(|v| v * 100.0),
+        sharpe_ratio,
+    })
+}
+
+impl VaultPerformance {
+    pub fn to_text(&self) -> String {
+        let date = |ts: i64| chrono::DateTime::from_timestamp(ts, 0).map_or(ts.to_string(), |t| t.to_rfc3339());
+        let percent = |value: Option<f64>| value.map_or("n/a".to_string(), |v| format!("{:.2}%", v));
+        format!(
+            "Vault {} performance from {} to {} ({} snapshots):\n- Equity: {:.6} -> {:.6}\n- Net Flows: {:.6}\n- PnL: {:.6}\n- Time-Weighted Return: {:.2}%\n- Annualized Return: {}\n- Max Drawdown: {:.2}%\n- Current Drawdown: {:.2}%\n- Volatility (annualized): {}\n- Sharpe Ratio: {}",
+            self.vault_address,
+            date(self.from_ts),
+            date(self.to_ts),
+            self.snapshots,
+            self.start_equity,
+            self.end_equity,
+            self.net_flows,
+            self.pnl,
+            self.time_weighted_return_percent,
+            percent(self.annualized_return_percent),
+            self.max_drawdown_percent,
+            self.current_drawdown_percent,
+            percent(self.volatility_percent),
+            self.sharpe_ratio.map_or("n/a".to_string(), |s| format!("{:.2}", s)),
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftVaultPerformanceArgs {
+    pub vault_address: Option<String>,
+    pub days: Option<f64>,              // Lookback, default all recorded snapshots
+    pub record: Option<bool>,           // Take a snapshot now before computing
+    pub format: Option<String>,         // "text" (default) or "json"
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftVaultPerformance;
+
+impl DriftVaultPerformance {
+    pub async fn execute(args: DriftVaultPerformanceArgs) -> Result<String, DriftDataError> {
+        let vault = args.vault_address.or(env::var("AGENT_VAULT").ok()).ok_or(DriftDataError {
+            message: "No vault address given and AGENT_VAULT is not set".to_string()
+        })?;
+        let vault_pubkey = Pubkey::from_str(&vault).map_err(|_| DriftDataError {
+            message: format!("Invalid vault address {}", vault)
+        })?;
+        if args.record.unwrap_or(false) {
+            let client = init_drift_client().await?;
+            record_vault_snapshot(&client, &vault_pubkey).await?;
+        }
+
+        let since = args.days.map_or(0, |days| chrono::Utc::now().timestamp() - (days * 86400.0) as i64);
+        let snapshots = performance_store().snapshots(&vault_pubkey.to_string(), since);
+        let risk_free_rate = env::var("AGENT_RISK_FREE_RATE_PERCENT").ok().and_then(|rate| rate.parse::<f64>().ok()).unwrap_or(0.0) / 100.0;
+        let performance = match compute_vault_performance(&snapshots, risk_free_rate) {
+            Some(performance) if performance.snapshots >= 2 => performance,
+            _ => return Ok(format!(
+                "Not enough snapshots recorded for vault {} ({} found); at least 2 are needed.",
+                vault_pubkey, snapshots.len()
+            )),
+        };
+
+        if args.format.as_deref() == Some("json") {
+            return serde_json::to_string(&performance).map_err(|_| DriftDataError {
+                message: "Failed to serialize vault performance".to_string()
+            });
+        }
+        Ok(performance.to_text())
+    }
+}
+
+impl Tool for DriftVaultPerformance {
+    const NAME: &'static str = "drift_vault_performance";
+
+    type Error = DriftDataError;
+    type Args = DriftVaultPerformanceArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_vault_performance".to_string(),
+            description: "Shows how a vault has performed from locally recorded snapshots: time-weighted return (deposits and withdrawals are not counted as profit), annualized return, max and current drawdown, annualized volatility and Sharpe ratio, plus equity, net flows and PnL over the period.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "vault_address": { "type": "string", "description": "The public key of the vault (optional, defaults to AGENT_VAULT)." },
+                    "days": { "type": "number", "description": "Lookback in days (optional, defaults to every recorded snapshot)." },
+                    "record": { "type": "boolean", "description": "Record a fresh snapshot before computing (default: false)." },
+                    "format": { "type": "string", "enum": ["text", "json"], "description": "Output format (default: text)." }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let handle = tokio::task::spawn(Self::execute(args));
+        handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await vault performance".to_string()
+        })?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 86400;
+
+    fn snapshot(ts: i64, equity: f64, share_price: f64, net_deposits: f64) -> VaultSnapshot {
+        VaultSnapshot {
+            ts,
+            vault_address: "vault".to_string(),
+            equity,
+            equity_usd: equity,
+            share_price,
+            total_shares: 1_000_000,
+            shares_base: 0,
+            net_deposits,
+            total_deposits: net_deposits,
+            total_withdraws: 0.0,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn period_return_uses_share_price_across_flows() {
+        let prev = snapshot(0, 100.0, 1.0, 100.0);
+        // 100 deposited, then 5% growth on the share price.
+        let next = snapshot(DAY, 210.0, 1.05, 200.0);
+        assert_close(period_return(&prev, &next), 0.05);
+    }
+
+    #[test]
+    fn period_return_uses_equity_net_of_flows_across_a_rebase() {
+        let prev = snapshot(0, 100.0, 1.0, 100.0);
+        // The rebase changes the share price scale, so it cannot be compared.
+        let next = VaultSnapshot { shares_base: 1, ..snapshot(DAY, 165.0, 10.0, 150.0) };
+        assert_close(period_return(&prev, &next), 165.0 / 150.0 - 1.0);
+    }
+
+    #[test]
+    fn period_return_is_zero_without_capital() {
+        let prev = VaultSnapshot { total_shares: 0, ..snapshot(0, 0.0, 0.0, 0.0) };
+        let next = VaultSnapshot { total_shares: 0, ..snapshot(DAY, 0.0, 0.0, 0.0) };
+        assert_close(period_return(&prev, &next), 0.0);
+    }
+
+    #[test]
+    fn flows_are_not_counted_as_performance() {
+        let snapshots = vec![
+            snapshot(0, 100.0, 1.0, 100.0),
+            snapshot(DAY, 1100.0, 1.0, 1100.0),
+            snapshot(2 * DAY, 1045.0, 1.1, 900.0),
+        ];
+        let performance = compute_vault_performance(&snapshots, 0.0).unwrap();
+        assert_close(performance.net_flows, 800.0);
+        assert_close(performance.pnl, 145.0);
+        assert_close(performance.time_weighted_return_percent, 10.0);
+        assert_close(performance.max_drawdown_percent, 0.0);
+    }
+
+    #[test]
+    fn drawdown_follows_the_growth_index() {
+        let snapshots = vec![
+            snapshot(0, 100.0, 1.0, 100.0),
+            snapshot(DAY, 120.0, 1.2, 100.0),
+            snapshot(2 * DAY, 90.0, 0.9, 100.0),
+            snapshot(3 * DAY, 100.0, 1.0, 100.0),
+        ];
+        let performance = compute_vault_performance(&snapshots, 0.0).unwrap();
+        assert_close(performance.max_drawdown_percent, 25.0);
+        assert_close(performance.current_drawdown_percent, (1.0 - 1.0 / 1.2) * 100.0);
+        assert_close(performance.time_weighted_return_percent, 0.0);
+    }
+
+    #[test]
+    fn irregular_snapshots_at_a_constant_rate_have_no_volatility() {
+        // 20% a year compounded continuously, sampled at uneven times like an on-demand recording
+        // between scheduled ones.
+        let rate = 0.2_f64;
+        let price = |ts: i64| (rate * ts as f64 / ONE_YEAR_SECS).exp();
+        let snapshots: Vec<VaultSnapshot> = [0, 3600, DAY, 7 * DAY, 7 * DAY + 60, 30 * DAY]
+            .into_iter()
+            .map(|ts| snapshot(ts, 100.0 * price(ts), price(ts), 100.0))
+            .collect();
+        let performance = compute_vault_performance(&snapshots, 0.0).unwrap();
+        assert!(performance.volatility_percent.unwrap() < 1e-6);
+        assert!((performance.annualized_return_percent.unwrap() - (rate.exp() - 1.0) * 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn volatility_does_not_depend_on_sampling() {
+        // The same daily path, once sampled daily and once with extra snapshots that sit on it.
+        let daily = [1.0, 1.02, 0.99, 1.03, 1.01];
+        let regular: Vec<VaultSnapshot> = daily
+            .iter()
+            .enumerate()
+            .map(|(day, price)| snapshot(day as i64 * DAY, 100.0 * price, *price, 100.0))
+            .collect();
+        let mut irregular = regular.clone();
+        // A snapshot halfway through day one, on the geometric path between its endpoints.
+        let mid_price = (daily[0] * daily[1]).sqrt();
+        irregular.insert(1, snapshot(DAY / 2, 100.0 * mid_price, mid_price, 100.0));
+
+        let regular = compute_vault_performance(&regular, 0.0).unwrap();
+        let irregular = compute_vault_performance(&irregular, 0.0).unwrap();
+        assert_close(regular.time_weighted_return_percent, irregular.time_weighted_return_percent);
+        let (a, b) = (regular.volatility_percent.unwrap(), irregular.volatility_percent.unwrap());
+        assert!((a - b).abs() / a < 0.2, "volatility {} vs {}", a, b);
+    }
+
+    #[test]
+    fn a_single_snapshot_has_no_period_statistics() {
+        let performance = compute_vault_performance(&[snapshot(0, 100.0, 1.0, 100.0)], 0.0).unwrap();
+        assert_eq!(performance.annualized_return_percent, None);
+        assert_eq!(performance.volatility_percent, None);
+        assert!(compute_vault_performance(&[], 0.0).is_none());
+    }
+}
@@ -0,0 +1,313 @@
+use std::{collections::{HashMap, VecDeque}, env, fs, path::PathBuf, sync::{Mutex, OnceLock}, time::{Duration, Instant}};
+use serde::{Deserialize, Serialize};
+
+use drift_rs::{
+    math::constants::{BASE_PRECISION, PRICE_PRECISION, QUOTE_PRECISION},
+    types::{accounts::User, MarketId},
+    DriftClient,
+};
+
+use crate::tools::shared::*;
+use crate::tools::margin::account_net_value_usd;
+
+const DEFAULT_RISK_STATE_PATH: &str = ".agent_risk_state.json";
+
+/// Limits enforced on every order before it is sent. A `None` limit is not enforced.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RiskConfig {
+    pub max_order_notional_usd: Option<f64>,
+    pub max_total_exposure_usd: Option<f64>,
+    pub max_leverage: Option<f64>,
+    pub allowed_markets: Option<Vec<u16>>,
+    pub max_orders_per_minute: Option<usize>,
+    pub max_daily_loss_usd: Option<f64>,
+}
+
+impl RiskConfig {
+    /// Reads limits from RISK_MAX_ORDER_NOTIONAL_USD, RISK_MAX_TOTAL_EXPOSURE_USD, RISK_MAX_LEVERAGE,
+    /// RISK_ALLOWED_MARKETS (comma separated perp market indexes), RISK_MAX_ORDERS_PER_MINUTE and
+    /// RISK_MAX_DAILY_LOSS_USD.
+    pub fn from_env() -> Self {
+        fn parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+            env::var(name).ok().and_then(|v| v.trim().parse().ok())
+        }
+
+        RiskConfig {
+            max_order_notional_usd: parse("RISK_MAX_ORDER_NOTIONAL_USD"),
+            max_total_exposure_usd: parse("RISK_MAX_TOTAL_EXPOSURE_USD"),
+            max_leverage: parse("RISK_MAX_LEVERAGE"),
+            allowed_markets: env::var("RISK_ALLOWED_MARKETS").ok().map(|v| {
+                v.split(',').filter_map(|s| s.trim().parse().ok()).collect()
+            }),
+            max_orders_per_minute: parse("RISK_MAX_ORDERS_PER_MINUTE"),
+            max_daily_loss_usd: parse("RISK_MAX_DAILY_LOSS_USD"),
+        }
+    }
+}
+
+/// An order as seen by the risk guard.
+#[derive(Debug, Clone, Copy)]
+pub struct ProposedOrder {
+    pub market_index: u16,
+    /// Signed base amount in base precision (1e9).
+    pub base_asset_amount: i64,
+    pub reduce_only: bool,
+}
+
+/// Equity and lifetime net deposits of an account at its first reading of a UTC day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DayStart {
+    /// UTC date as `YYYY-MM-DD`.
+    date: String,
+    equity_usd: f64,
+    net_deposits_usd: f64,
+}
+
+/// Lifetime deposits minus withdrawals of `user`, including transfers between sub-accounts.
+fn net_deposits_usd(user: &User) -> f64 {
+    (user.total_deposits as f64 - user.total_withdraws as f64) / QUOTE_PRECISION as f64
+}
+
+/// Pre-trade checks shared by every order path. Reduce-only orders are counted towards the rate
+/// limit but never rejected, so closing exposure is not blocked by the limits meant to stop adding it.
+pub struct RiskGuard {
+    config: Mutex<RiskConfig>,
+    order_times: Mutex<VecDeque<Instant>>,
+    /// Day start per user account, persisted at AGENT_RISK_STATE (default `.agent_risk_state.json`)
+    /// so a restart does not reset the daily loss.
+    day_starts: Mutex<HashMap<String, DayStart>>,
+    state_path: PathBuf,
+}
+
+pub fn risk_guard() -> &'static RiskGuard {
+    static RISK_GUARD: OnceLock<RiskGuard> = OnceLock::new();
+    RISK_GUARD.get_or_init(|| {
+        let state_path = PathBuf::from(env::var("AGENT_RISK_STATE").unwrap_or(DEFAULT_RISK_STATE_PATH.to_string()));
+        let day_starts = fs::read_to_string(&state_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        RiskGuard {
+            config: Mutex::new(RiskConfig::from_env()),
+            order_times: Mutex::new(VecDeque::new()),
+            day_starts: Mutex::new(day_starts),
+            state_path,
+        }
+    })
+}
+
+fn reject(reason: String) -> DriftDataError {
+    DriftDataError { message: format!("Order rejected by risk guard: {}", reason) }
+}
+
+impl RiskGuard {
+    pub fn configure(&self, config: RiskConfig) {
+        *self.config.lock().unwrap() = config;
+    }
+
+    pub fn config(&self) -> RiskConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Checks `orders` against the configured limits for the account `user`. On success the
+    /// orders are counted towards the per-minute rate limit.
+    pub async fn check_orders(&self, client: &DriftClient, user: &User, orders: &[ProposedOrder]) -> Result<(), DriftDataError> {
+        let config = self.config();
+        let risk_increasing: Vec<&ProposedOrder> = orders.iter().filter(|o| !o.reduce_only).collect();
+
+        let reserved_at = self.reserve_order_slots(&config, orders.len(), !risk_increasing.is_empty())?;
+        let result = self.check_limits(client, user, &config, &risk_increasing).await;
+        if result.is_err() {
+            self.release_order_slots(reserved_at, orders.len());
+        }
+        result
+    }
+
+    /// Counts `count` orders towards the rate limit, rejecting them first if `enforce` and they
+    /// would go over it. Checking and counting under one lock, before the checks that await,
+    /// keeps concurrent orders from all passing against the same count.
+    fn reserve_order_slots(&self, config: &RiskConfig, count: usize, enforce: bool) -> Result<Instant, DriftDataError> {
+        let now = Instant::now();
+        let mut order_times = self.order_times.lock().unwrap();
+        let window_start = now - Duration::from_secs(60);
+        while order_times.front().map_or(false, |t| *t < window_start) {
+            order_times.pop_front();
+        }
+        if let Some(max_per_minute) = config.max_orders_per_minute.filter(|_| enforce) {
+            if order_times.len() + count > max_per_minute {
+                return Err(reject(format!(
+                    "{} orders in the last minute, limit is {} per minute",
+                    order_times.len(), max_per_minute
+                )));
+            }
+        }
+        order_times.extend(std::iter::repeat(now).take(count));
+        Ok(now)
+    }
+
+    /// Uncounts orders reserved at `reserved_at` that were then rejected.
+    fn release_order_slots(&self, reserved_at: Instant, count: usize) {
+        let mut order_times = self.order_times.lock().unwrap();
+        for _ in 0..count {
+            match order_times.iter().rposition(|t| *t == reserved_at) {
+                Some(position) => { order_times.remove(position); }
+                None => break,
+            }
+        }
+    }
+
+    async fn check_limits(&self, client: &DriftClient, user: &User, config: &RiskConfig, risk_increasing: &[&ProposedOrder]) -> Result<(), DriftDataError> {
+        if risk_increasing.is_empty() {
+            // Reduce-only orders still take the day's first equity reading, but are never
+            // rejected, not even when the account cannot be valued.
+            if config.max_daily_loss_usd.is_some() {
+                if let Ok(equity_usd) = account_net_value_usd(client, user).await {
+                    self.daily_loss(user, equity_usd);
+                }
+            }
+            return Ok(());
+        }
+        self.check_risk_increasing(client, user, config, risk_increasing).await
+    }
+
+    /// Takes the day's first equity reading of every sub-account if none was taken yet today, so
+    /// the daily loss counts from when the agent started rather than from its first order.
+    pub async fn record_day_starts(&self, client: &DriftClient) -> Result<(), DriftDataError> {
+        if self.config().max_daily_loss_usd.is_none() {
+            return Ok(());
+        }
+        for (sub_account_id, user_pubkey) in list_sub_account_pubkeys(client).await? {
+            // Sub-accounts can be deleted, leaving gaps in the id range.
+            let Ok(user) = client.get_user_account(&user_pubkey).await else {
+                continue;
+            };
+            let equity_usd = account_net_value_usd(client, &user).await.map_err(|e| DriftDataError {
+                message: format!("Failed to value sub-account {}: {}", sub_account_id, e.message)
+            })?;
+            self.daily_loss(&user, equity_usd);
+        }
+        Ok(())
+    }
+
+    async fn check_risk_increasing(&self, client: &DriftClient, user: &User, config: &RiskConfig, orders: &[&ProposedOrder]) -> Result<(), DriftDataError> {
+        if let Some(allowed_markets) = &config.allowed_markets {
+            if let Some(order) = orders.iter().find(|o| !allowed_markets.contains(&o.market_index)) {
+                return Err(reject(format!(
+                    "market {} is not in the allowed markets {:?}",
+                    order.market_index, allowed_markets
+                )));
+            }
+        }
+
+        let mut new_exposure_usd = 0.0;
+        for order in orders {
+            let price = get_token_oracle_price(MarketId::perp(order.market_index), client).await? as f64 / PRICE_PRECISION as f64;
+            let notional = (order.base_asset_amount as f64 / BASE_PRECISION as f64).abs() * price;
+            if let Some(max_notional) = config.max_order_notional_usd {
+                if notional > max_notional {
+                    return Err(reject(format!(
+                        "order notional ${:.2} on market {} exceeds the maximum of ${:.2} per order",
+                        notional, order.market_index, max_notional
+                    )));
+                }
+            }
+            new_exposure_usd += notional;
+        }
+
+        if config.max_total_exposure_usd.is_none() && config.max_leverage.is_none() && config.max_daily_loss_usd.is_none() {
+            return Ok(());
+        }
+
+        let mut current_exposure_usd = 0.0;
+        for position in user.perp_positions.iter().filter(|p| p.base_asset_amount != 0) {
+            let price = get_token_oracle_price(MarketId::perp(position.market_index), client).await? as f64 / PRICE_PRECISION as f64;
+            current_exposure_usd += (position.base_asset_amount as f64 / BASE_PRECISION as f64).abs() * price;
+        }
+        let exposure_after = current_exposure_usd + new_exposure_usd;
+
+        if let Some(max_exposure) = config.max_total_exposure_usd {
+            if exposure_after > max_exposure {
+                return Err(reject(format!(
+                    "total exposure would be ${:.2}, above the maximum of ${:.2} (current ${:.2})",
+                    exposure_after, max_exposure, current_exposure_usd
+                )));
+            }
+        }
+
+        if config.max_leverage.is_none() && config.max_daily_loss_usd.is_none() {
+            return Ok(());
+        }
+
+        let equity_usd = account_net_value_usd(client, user).await?;
+
+        if let Some(max_leverage) = config.max_leverage {
+            let leverage_after = if equity_usd > 0.0 { exposure_after / equity_usd } else { f64::INFINITY };
+            if leverage_after > max_leverage {
+                return Err(reject(format!(
+                    "leverage would be {:.2}x, above the maximum of {:.2}x",
+                    leverage_after, max_leverage
+                )));
+            }
+        }
+
+        if let Some(max_daily_loss) = config.max_daily_loss_usd {
+            let daily_loss = self.daily_loss(user, equity_usd);
+            if daily_loss >= max_daily_loss {
+                return Err(reject(format!(
+                    "daily loss of ${:.2} has reached the limit of ${:.2}; only reduce-only orders are allowed until tomorrow (UTC)",
+                    daily_loss, max_daily_loss
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Loss of `user` since its first equity reading of the current UTC day, not counting
+    /// deposits and withdrawals made since.
+    fn daily_loss(&self, user: &User, equity_usd: f64) -> f64 {
+        let today = chrono::Utc::now().date_naive().to_string();
+        let account = format!("{}/{}", user.authority, user.sub_account_id);
+        let net_deposits = net_deposits_usd(user);
+
+        let mut day_starts = self.day_starts.lock().unwrap();
+        match day_starts.get(&account) {
+            Some(start) if start.date == today => {
+                let transfers = net_deposits - start.net_deposits_usd;
+                (start.equity_usd + transfers - equity_usd).max(0.0)
+            }
+            _ => {
+                day_starts.insert(account, DayStart { date: today, equity_usd, net_deposits_usd: net_deposits });
+                self.persist_day_starts(&day_starts);
+                0.0
+            }
+        }
+    }
+
+    /// Writes the day starts, logging rather than failing the order if the file cannot be written.
+    fn persist_day_starts(&self, day_starts: &HashMap<String, DayStart>) {
+        let Ok(contents) = serde_json::to_string_pretty(day_starts) else {
+            return;
+        };
+        let tmp_path = self.state_path.with_extension("json.tmp");
+        if let Err(e) = fs::write(&tmp_path, contents).and_then(|_| fs::rename(&tmp_path, &self.state_path)) {
+            eprintln!("Failed to write risk state to {}: {}", self.state_path.display(), e);
+        }
+    }
+}
+
+/// Runs the risk guard on its own client, for algorithm tools that validate their orders up front
+/// so a rejection reaches the caller instead of failing later in the background job.
+pub async fn check_orders_with_new_client(orders: Vec<ProposedOrder>, sub_account_id: Option<u16>) -> Result<(), DriftDataError> {
+    tokio::task::spawn(async move {
+        let client = init_drift_client().await?;
+        let user = client.get_user_account(&sub_account_pubkey(client.wallet(), sub_account_id)).await.map_err(|_| DriftDataError {
+            message: "Failed to init Drift user".to_string()
+        })?;
+        risk_guard().check_orders(&client, &user, &orders).await
+    })
+    .await
+    .map_err(|_| DriftDataError {
+        message: "Failed to await risk check".to_string()
+    })?
+}
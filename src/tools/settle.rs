@@ -0,0 +1,179 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use anchor_lang::InstructionData;
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use tokio::time::sleep;
+use drift_rs::{
+    constants,
+    drift_idl,
+    math::constants::QUOTE_PRECISION,
+    DriftClient,
+};
+
+use crate::tools::shared::*;
+use crate::data::perp_market_name;
+
+/// How long to wait for a close order to fill before settling after it.
+const SETTLE_AFTER_CLOSE_POLLS: usize = 10;
+const SETTLE_AFTER_CLOSE_POLL_MS: u64 = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettledPnl {
+    pub market_index: u16,
+    pub market_name: String,
+    /// PnL moved into the USDC balance; negative means a loss was paid.
+    pub amount_usd: f64,
+    pub signature: Option<String>,
+    pub error: Option<String>,
+}
+
+fn settle_pnl_instruction(client: &DriftClient, user_pubkey: drift_rs::Pubkey, market_index: u16, remaining_accounts: Vec<AccountMeta>) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*constants::state_account(), false), // Drift State
+        AccountMeta::new(user_pubkey, false), // User
+        AccountMeta::new_readonly(client.wallet().signer(), true), // Authority
+        AccountMeta::new_readonly(constants::derive_spot_market_vault(0), false), // USDC Spot Market Vault
+    ];
+    accounts.extend(remaining_accounts);
+
+    Instruction {
+        program_id: constants::PROGRAM_ID,
+        accounts,
+        data: InstructionData::data(&drift_idl::instructions::SettlePnl { market_index }),
+    }
+}
+
+/// Settles perp PnL into the USDC balance for `market_index`, or for every market holding a
+/// closed position with unsettled PnL. Each market is settled in its own transaction so one
+/// failure does not block the rest.
+pub async fn settle_pnl(client: &DriftClient, sub_account_id: Option<u16>, market_index: Option<u16>) -> Result<Vec<SettledPnl>, DriftDataError> {
+    let user_pubkey = sub_account_pubkey(client.wallet(), sub_account_id);
+    let user = client.get_user_account(&user_pubkey).await.map_err(|_| DriftDataError {
+        message: "Failed to get_user_account".to_string()
+    })?;
+
+    // For a closed position the remaining quote amount is exactly the unsettled PnL.
+    let market_indexes: Vec<u16> = match market_index {
+        Some(index) => vec![index],
+        None => user
+            .perp_positions
+            .iter()
+            .filter(|p| p.base_asset_amount == 0 && p.quote_asset_amount != 0)
+            .map(|p| p.market_index)
+            .collect(),
+    };
+
+    let mut results = Vec::new();
+    for market_index in market_indexes {
+        let before = user.perp_positions.iter().find(|p| p.market_index == market_index).map_or(0, |p| p.quote_asset_amount);
+        let mut result = SettledPnl {
+            market_index,
+            market_name: perp_market_name(market_index),
+            amount_usd: 0.0,
+            signature: None,
+            error: None,
+        };
+
+        let remaining_accounts = user_remaining_accounts(client, &user, &[0], &[market_index]).await?;
+        let instruction = settle_pnl_instruction(client, user_pubkey, market_index, remaining_accounts);
+        match send_instructions(client, &[instruction]).await {
+            Ok(signature) => {
+                result.signature = Some(signature.to_string());
+                let after = client
+                    .get_user_account(&user_pubkey)
+                    .await
+                    .ok()
+                    .and_then(|u| u.perp_positions.iter().find(|p| p.market_index == market_index).map(|p| p.quote_asset_amount))
+                    .unwrap_or(0);
+                result.amount_usd = (before - after) as f64 / QUOTE_PRECISION as f64;
+            }
+            Err(err) => result.error = Some(err.to_string()),
+        }
+        results.push(result);
+    }
+    Ok(results)
+}
+
+/// Waits for the position in `market_index` to change from `base_asset_amount` (the close
+/// order filling), then settles that market.
+pub async fn settle_pnl_after_close(
+    client: &DriftClient,
+    sub_account_id: Option<u16>,
+    market_index: u16,
+    base_asset_amount: i64,
+) -> Result<Vec<SettledPnl>, DriftDataError> {
+    let user_pubkey = sub_account_pubkey(client.wallet(), sub_account_id);
+    for _ in 0..SETTLE_AFTER_CLOSE_POLLS {
+        let current = client
+            .get_user_account(&user_pubkey)
+            .await
+            .ok()
+            .and_then(|u| u.perp_positions.iter().find(|p| p.market_index == market_index).map(|p| p.base_asset_amount))
+            .unwrap_or(0);
+        if current != base_asset_amount {
+            break;
+        }
+        sleep(Duration::from_millis(SETTLE_AFTER_CLOSE_POLL_MS)).await;
+    }
+    settle_pnl(client, sub_account_id, Some(market_index)).await
+}
+
+pub fn format_settled_pnl(results: &[SettledPnl]) -> String {
+    if results.is_empty() {
+        return "No unsettled PnL on closed positions.".to_string();
+    }
+    results.iter().map(|r| match (&r.signature, &r.error) {
+        (_, Some(error)) => format!("- {}: failed to settle: {}", r.market_name, error),
+        (Some(signature), None) => format!("- {}: settled ${:.6} ({})", r.market_name, r.amount_usd, signature),
+        (None, None) => format!("- {}: nothing settled", r.market_name),
+    }).collect::<Vec<_>>().join("\n")
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftSettlePnlArgs {
+    pub market_index: Option<u16>,
+    pub sub_account_id: Option<u16>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftSettlePnl;
+
+impl Tool for DriftSettlePnl {
+    const NAME: &'static str = "drift_settle_pnl";
+
+    type Error = DriftDataError;
+    type Args = DriftSettlePnlArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_settle_pnl".to_string(),
+            description: "Settles realized perp PnL into the vault's USDC balance, for one market or for every market with a closed position, and reports the amounts settled.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "market_index": { "type": "integer", "description": "Perp market to settle (optional, defaults to every market with a closed position and unsettled PnL)." },
+                    "sub_account_id": { "type": "integer", "description": "Sub-account to settle (optional, defaults to 0)." }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let handle = tokio::task::spawn(async move {
+            let client = init_drift_client().await?;
+            settle_pnl(&client, args.sub_account_id, args.market_index).await
+        });
+        let results = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await settle_pnl".to_string()
+        })??;
+
+        Ok(format_settled_pnl(&results))
+    }
+}
@@ -1,11 +1,25 @@
 
-use std::time::Duration;
+use std::{env, str::FromStr, time::{Duration, Instant}};
 
+use drift_rs::math::constants::BASE_PRECISION;
 use drift_rs::types::accounts::User;
-use drift_rs::types::{MarketId, OrderParams};
-use drift_rs::{DriftClient, Wallet};
+use drift_rs::types::{Context, MarketId, OrderParams};
+use drift_rs::{constants, DriftClient, Pubkey, Wallet};
+use solana_client::{
+    client_error::ClientErrorKind,
+    nonblocking::rpc_client::RpcClient,
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::{AccountMeta, Instruction},
+    message::{Message, VersionedMessage},
+    signature::{Keypair, Signature},
+};
 use tokio::time::sleep;
 
+use crate::tools::idempotency::*;
+
 #[derive(Debug, thiserror::Error)]
 #[error("{message}")]
 pub struct DriftDataError {
@@ -18,6 +32,168 @@ pub const SOLANA_MAINNET_RPC_URL: &str = "https://lb.drpc.org/ogrpc?network=sola
 
 const MAX_RETRIES: usize = 5;
 const RETRY_DELAY_MS: u64 = 1000;
+const ORDER_STATUS_POLL_MS: u64 = 500;
+/// Upper bound on following one order attempt, in case the RPC stops reporting block heights.
+/// A blockhash is valid for 150 blocks, roughly 60-90 seconds.
+const MAX_ORDER_CONFIRM_SECS: u64 = 120;
+
+/// Parses a public key argument, naming it as `what` in the error.
+pub fn parse_pubkey(value: &str, what: &str) -> Result<Pubkey, DriftDataError> {
+    Pubkey::from_str(value).map_err(|_| DriftDataError { message: format!("Invalid {} {}", what, value) })
+}
+
+/// The Drift data API and DLOB server return numbers as strings; accept either.
+pub fn value_as_f64(value: &serde_json::Value) -> Option<f64> {
+    value.as_f64().or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+/// Parses a keypair stored as comma separated bytes in the env variable `var`.
+pub fn load_keypair_from_env(var: &str) -> Result<Keypair, DriftDataError> {
+    let secret_key_str = env::var(var).map_err(|_| DriftDataError {
+        message: format!("Missing {} env variable", var)
+    })?;
+    let secret_key_bytes = secret_key_str
+        .split(',')
+        .map(|s| s.trim().parse::<u8>())
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| DriftDataError {
+            message: format!("Invalid byte in {}", var)
+        })?;
+    Keypair::from_bytes(&secret_key_bytes).map_err(|_| DriftDataError {
+        message: "Invalid private key".to_string()
+    })
+}
+
+/// Loads the agent keypair from AGENT_KEYPAIR and delegates it to AGENT_VAULT.
+pub fn load_agent_wallet() -> Result<Wallet, DriftDataError> {
+    let agent_vault = env::var("AGENT_VAULT").map_err(|_| DriftDataError {
+        message: "Missing AGENT_VAULT env variable".to_string()
+    })?;
+    let keypair = load_keypair_from_env("AGENT_KEYPAIR")?;
+    let agent_vault_pubkey = Pubkey::from_str(&agent_vault).map_err(|_| DriftDataError {
+        message: "Invalid AGENT_VAULT pubkey".to_string()
+    })?;
+
+    let mut wallet = Wallet::from(keypair);
+    wallet.to_delegated(agent_vault_pubkey);
+    Ok(wallet)
+}
+
+/// Creates a mainnet Drift client for the delegated agent wallet.
+pub async fn init_drift_client() -> Result<DriftClient, DriftDataError> {
+    let wallet = load_agent_wallet()?;
+    DriftClient::new(
+        Context::MainNet,
+        RpcClient::new(SOLANA_MAINNET_RPC_URL.to_string()),
+        wallet,
+    )
+    .await
+    .map_err(|_| DriftDataError {
+        message: "Failed to init Drift client".to_string()
+    })
+}
+
+/// Converts a base amount in whole units of the asset (fractions allowed) to Drift base precision (1e9).
+pub fn to_base_precision(amount: f64) -> i64 {
+    (amount * BASE_PRECISION as f64).round() as i64
+}
+
+/// Resolves an optional sub-account id to the Drift user account of the wallet, defaulting to sub-account 0.
+pub fn sub_account_pubkey(wallet: &Wallet, sub_account_id: Option<u16>) -> Pubkey {
+    sub_account_id.map_or(wallet.default_sub_account(), |id| wallet.sub_account(id))
+}
+
+/// Signs and sends raw instructions with the wallet's signer as fee payer.
+pub async fn send_instructions(
+    client: &DriftClient,
+    instructions: &[Instruction],
+) -> Result<solana_sdk::signature::Signature, DriftDataError> {
+    let message = Message::new(instructions, Some(&client.wallet().signer()));
+    client
+        .sign_and_send(VersionedMessage::Legacy(message))
+        .await
+        .map_err(|e| DriftDataError {
+            message: format!("Failed to send transaction: {}", e)
+        })
+}
+
+/// Builds the remaining accounts Drift needs to margin-check `user`: every oracle, then every
+/// spot market, then every perp market the user touches, plus any extra markets passed in.
+/// Spot markets listed in `writable_spot_markets` are marked writable.
+pub async fn user_remaining_accounts(
+    client: &DriftClient,
+    user: &User,
+    writable_spot_markets: &[u16],
+    extra_perp_markets: &[u16],
+) -> Result<Vec<AccountMeta>, DriftDataError> {
+    let mut spot_indexes: Vec<u16> = user
+        .spot_positions
+        .iter()
+        .filter(|s| !s.is_available())
+        .map(|s| s.market_index)
+        .chain(writable_spot_markets.iter().copied())
+        .collect();
+    let mut perp_indexes: Vec<u16> = user
+        .perp_positions
+        .iter()
+        .filter(|p| p.is_open_position() || p.quote_asset_amount != 0)
+        .map(|p| p.market_index)
+        .chain(extra_perp_markets.iter().copied())
+        .collect();
+    if !perp_indexes.is_empty() {
+        // Perp PnL is settled in the quote spot market.
+        spot_indexes.push(0);
+    }
+    spot_indexes.sort_unstable();
+    spot_indexes.dedup();
+    perp_indexes.sort_unstable();
+    perp_indexes.dedup();
+
+    let mut oracles: Vec<Pubkey> = Vec::new();
+    let mut spot_metas = Vec::new();
+    for index in &spot_indexes {
+        let market = client.get_spot_market_account(*index).await.map_err(|_| DriftDataError {
+            message: format!("Failed to get spot market {}", index)
+        })?;
+        oracles.push(market.oracle);
+        let pubkey = constants::derive_spot_market_account(*index);
+        spot_metas.push(if writable_spot_markets.contains(index) {
+            AccountMeta::new(pubkey, false)
+        } else {
+            AccountMeta::new_readonly(pubkey, false)
+        });
+    }
+    let mut perp_metas = Vec::new();
+    for index in &perp_indexes {
+        let market = client.get_perp_market_account(*index).await.map_err(|_| DriftDataError {
+            message: format!("Failed to get perp market {}", index)
+        })?;
+        oracles.push(market.amm.oracle);
+        perp_metas.push(AccountMeta::new(constants::derive_perp_market_account(*index), false));
+    }
+    oracles.sort_unstable();
+    oracles.dedup();
+
+    Ok(oracles
+        .into_iter()
+        .map(|oracle| AccountMeta::new_readonly(oracle, false))
+        .chain(spot_metas)
+        .chain(perp_metas)
+        .collect())
+}
+
+/// Lists the sub-accounts created under the wallet's authority (the vault when delegated)
+/// as `(sub_account_id, user_account_pubkey)` pairs.
+pub async fn list_sub_account_pubkeys(client: &DriftClient) -> Result<Vec<(u16, Pubkey)>, DriftDataError> {
+    let wallet = client.wallet();
+    let user_stats = client.get_user_stats(wallet.authority()).await.map_err(|_| DriftDataError {
+        message: "Failed to get user stats".to_string()
+    })?;
+
+    Ok((0..user_stats.number_of_sub_accounts_created)
+        .map(|sub_account_id| (sub_account_id, wallet.sub_account(sub_account_id)))
+        .collect())
+}
 
 pub async fn get_token_oracle_price(market_id: MarketId, client: &DriftClient) -> Result<i64, DriftDataError> {
     let mut attempt = 0;
@@ -38,12 +214,36 @@ pub async fn get_token_oracle_price(market_id: MarketId, client: &DriftClient) -
     Err(DriftDataError{message:"Max retries reached".to_string()})
 }
 
+/// Sends `new_order` for `user`, retrying with backoff; see `send_orders_with_retry`.
 pub async fn send_order_with_retry(
     client: &DriftClient,
     wallet: &Wallet,
     user: &User,
     new_order: OrderParams,
-) -> Result<solana_sdk::signature::Signature, DriftDataError> {
+) -> Result<OrderReceipt, DriftDataError> {
+    send_orders_with_retry(client, wallet, user, vec![new_order]).await
+}
+
+/// Sends `new_orders` for `user` in one transaction, retrying with backoff. Each order is tagged
+/// with a client order id (unless it already has one) and each attempt is followed until it is
+/// confirmed, fails, or its blockhash expires; only an attempt that can no longer land is
+/// retried, so a slow confirmation does not place the orders twice.
+pub async fn send_orders_with_retry(
+    client: &DriftClient,
+    wallet: &Wallet,
+    user: &User,
+    mut new_orders: Vec<OrderParams>,
+) -> Result<OrderReceipt, DriftDataError> {
+    if new_orders.is_empty() {
+        return Err(DriftDataError { message: "No orders to send".to_string() });
+    }
+    let user_pubkey = wallet.sub_account(user.sub_account_id);
+    // The caller's account may be stale, so compare against a fresh one.
+    let before = client.get_user_account(&user_pubkey).await.unwrap_or_else(|_| user.clone());
+    for order in new_orders.iter_mut().filter(|order| order.user_order_id == 0) {
+        order.user_order_id = next_user_order_id(&before);
+    }
+    let user_order_ids: Vec<u8> = new_orders.iter().map(|order| order.user_order_id).collect();
     let mut attempt = 0;
 
     loop {
@@ -52,24 +252,87 @@ pub async fn send_order_with_retry(
             return Err(DriftDataError{ message: "Max retries reached for sending order".to_string()});
         }
 
-        match client.sign_and_send(
-            drift_rs::TransactionBuilder::new(
-                client.program_data(),
-                wallet.default_sub_account(),
-                std::borrow::Cow::Borrowed(user),
-                true,
-            )
-            .place_orders(vec![new_order])
-            .build(),
+        let message = drift_rs::TransactionBuilder::new(
+            client.program_data(),
+            user_pubkey,
+            std::borrow::Cow::Borrowed(&before),
+            true,
         )
+        .place_orders(new_orders.clone())
+        .build();
+
+        if let Ok(Some(signature)) = send_order_attempt(client, wallet, message, user_pubkey, &before, &new_orders).await {
+            return Ok(OrderReceipt { user_order_ids, signature });
+        }
+
+        attempt += 1;
+        let delay = RETRY_DELAY_MS * (2_u64.pow(attempt as u32));
+        sleep(Duration::from_millis(delay)).await;
+    }
+}
+
+/// Signs and sends one order transaction, then polls its signature status and the account for
+/// the orders' client ids until it lands or its blockhash expires. Returns the signature if the
+/// orders landed and `None` if this attempt can no longer land.
+async fn send_order_attempt(
+    client: &DriftClient,
+    wallet: &Wallet,
+    message: VersionedMessage,
+    user_pubkey: Pubkey,
+    before: &User,
+    orders: &[OrderParams],
+) -> Result<Option<Signature>, DriftDataError> {
+    let rpc = client.rpc();
+    let (blockhash, last_valid_block_height) = rpc
+        .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
         .await
-        {
-            Ok(tx_signature) => return Ok(tx_signature),
-            Err(_err) => {
-                attempt += 1;
-                let delay = RETRY_DELAY_MS * (2_u64.pow(attempt as u32));
-                sleep(Duration::from_millis(delay)).await;
+        .map_err(|e| DriftDataError { message: format!("Failed to get blockhash: {}", e) })?;
+    let transaction = wallet.sign_tx(message, blockhash).map_err(|e| DriftDataError {
+        message: format!("Failed to sign order: {}", e)
+    })?;
+    let signature = transaction.signatures[0];
+
+    if let Err(err) = rpc.send_transaction(&transaction).await {
+        // A failed simulation means the transaction was never forwarded; any other error may
+        // have happened after it was, so keep following it.
+        if let ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(_), ..
+        }) = err.kind() {
+            return Ok(None);
+        }
+    }
+
+    // The orders go out in one transaction, so any of them landing means all of them did.
+    let landed = |after: &User| orders.iter().any(|order| order_landed(before, after, order));
+    let started = Instant::now();
+    loop {
+        if let Ok(statuses) = rpc.get_signature_statuses(&[signature]).await {
+            if let Some(Some(status)) = statuses.value.first() {
+                if status.err.is_some() {
+                    return Ok(None);
+                }
+                if status.satisfies_commitment(CommitmentConfig::confirmed()) {
+                    return Ok(Some(signature));
+                }
             }
         }
+        if client.get_user_account(&user_pubkey).await.map_or(false, |after| landed(&after)) {
+            return Ok(Some(signature));
+        }
+
+        let expired = rpc.get_block_height().await.map_or(false, |height| height > last_valid_block_height);
+        if expired || started.elapsed() > Duration::from_secs(MAX_ORDER_CONFIRM_SECS) {
+            // One last look, the status of a transaction that landed right at expiry can lag.
+            let confirmed_late = rpc
+                .get_signature_statuses_with_history(&[signature])
+                .await
+                .ok()
+                .and_then(|statuses| statuses.value.into_iter().next().flatten())
+                .map_or(false, |status| status.err.is_none() && status.satisfies_commitment(CommitmentConfig::confirmed()));
+            let landed_late = confirmed_late
+                || client.get_user_account(&user_pubkey).await.map_or(false, |after| landed(&after));
+            return Ok(landed_late.then_some(signature));
+        }
+        sleep(Duration::from_millis(ORDER_STATUS_POLL_MS)).await;
     }
-}
\ No newline at end of file
+}
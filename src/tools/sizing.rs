@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use drift_rs::{
+    math::{
+        constants::{BASE_PRECISION, MARGIN_PRECISION, PRICE_PRECISION, QUOTE_PRECISION},
+        liquidation::calculate_collateral,
+    },
+    types::{MarginRequirementType, MarketId},
+    DriftClient,
+};
+
+use crate::tools::shared::*;
+
+/// How the caller wants the position sized.
+#[derive(Debug, Clone, Copy)]
+pub enum SizingMode {
+    /// A fixed USD notional.
+    UsdNotional(f64),
+    /// A percent (0-100) of account equity, used as notional.
+    EquityPercent(f64),
+    /// Lose at most `risk_usd` if price moves `stop_distance` (in USD) against the position.
+    Risk { risk_usd: f64, stop_distance: f64 },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionSize {
+    pub market_index: u16,
+    pub oracle_price: f64,
+    /// Signed base amount in Drift base precision (1e9), rounded down to the market step size.
+    pub base_asset_amount: i64,
+    /// Signed base amount in whole units of the asset.
+    pub amount: f64,
+    pub notional_usd: f64,
+    pub free_collateral_usd: f64,
+    /// True if the requested size was reduced to fit within free collateral.
+    pub capped_by_collateral: bool,
+}
+
+/// Converts a sizing request into a base amount that can be placed on `market_index`.
+pub async fn calculate_position_size(
+    client: &DriftClient,
+    market_index: u16,
+    mode: SizingMode,
+    is_long: bool,
+    sub_account_id: Option<u16>,
+) -> Result<PositionSize, DriftDataError> {
+    let market_id = MarketId::perp(market_index);
+    let oracle_price = get_token_oracle_price(market_id, client).await? as f64 / PRICE_PRECISION as f64;
+    if oracle_price <= 0.0 {
+        return Err(DriftDataError { message: format!("Invalid oracle price for market {}", market_index) });
+    }
+
+    let perp_market = client.get_perp_market_account(market_index).await.map_err(|_| DriftDataError {
+        message: format!("Failed to get perp market {}", market_index)
+    })?;
+    let user = client
+        .get_user_account(&sub_account_pubkey(client.wallet(), sub_account_id))
+        .await
+        .map_err(|_| DriftDataError {
+            message: "Failed to get_user_account".to_string()
+        })?;
+    let collateral = calculate_collateral(client, &user, MarginRequirementType::Initial).map_err(|_| DriftDataError {
+        message: "Failed to calculate collateral".to_string()
+    })?;
+    let equity_usd = collateral.total as f64 / QUOTE_PRECISION as f64;
+    let free_collateral_usd = (collateral.free as f64 / QUOTE_PRECISION as f64).max(0.0);
+
+    let requested_notional = match mode {
+        SizingMode::UsdNotional(usd) => usd,
+        SizingMode::EquityPercent(percent) => equity_usd * percent / 100.0,
+        SizingMode::Risk { risk_usd, stop_distance } => {
+            if stop_distance <= 0.0 {
+                return Err(DriftDataError { message: "Stop distance must be positive".to_string() });
+            }
+            risk_usd / stop_distance * oracle_price
+        }
+    };
+    if requested_notional <= 0.0 {
+        return Err(DriftDataError { message: "Requested size must be positive".to_string() });
+    }
+
+    // Largest notional the free collateral can margin at this market's initial margin ratio.
+    let max_leverage = MARGIN_PRECISION as f64 / perp_market.margin_ratio_initial.max(1) as f64;
+    let max_notional = free_collateral_usd * max_leverage;
+    let capped_by_collateral = requested_notional > max_notional;
+    let notional = requested_notional.min(max_notional);
+
+    let step_size = perp_market.amm.order_step_size.max(1) as i64;
+    let raw_base = (notional / oracle_price * BASE_PRECISION as f64) as i64;
+    let base = raw_base - raw_base % step_size;
+    if base <= 0 || (base as u64) < perp_market.amm.min_order_size {
+        return Err(DriftDataError {
+            message: format!(
+                "Size of ${:.2} is below the minimum order size for market {} (free collateral ${:.2})",
+                notional, market_index, free_collateral_usd
+            ),
+        });
+    }
+
+    let base_asset_amount = if is_long { base } else { -base };
+    Ok(PositionSize {
+        market_index,
+        oracle_price,
+        base_asset_amount,
+        amount: base_asset_amount as f64 / BASE_PRECISION as f64,
+        notional_usd: base as f64 / BASE_PRECISION as f64 * oracle_price,
+        free_collateral_usd,
+        capped_by_collateral,
+    })
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftPositionSizeArgs {
+    pub market_index: u16,
+    pub position_type: Option<String>,        // "long" (default) or "short"
+    pub usd_notional: Option<f64>,
+    pub equity_percent: Option<f64>,
+    pub risk_amount: Option<f64>,
+    pub stop_distance: Option<f64>,           // Distance to the stop in USD per unit
+    pub sub_account_id: Option<u16>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftPositionSize;
+
+impl Tool for DriftPositionSize {
+    const NAME: &'static str = "drift_position_size";
+
+    type Error = DriftDataError;
+    type Args = DriftPositionSizeArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_position_size".to_string(),
+            description: "Calculates a valid base amount for a perp market from a USD notional, a percent of account equity, or a risk amount with a stop distance. Use this before placing orders instead of dividing by price by hand. Provide exactly one of usd_notional, equity_percent or risk_amount (with stop_distance).".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "market_index": { "type": "integer", "description": "The market index for the perp trade." },
+                    "position_type": { "type": "string", "enum": ["long", "short"], "description": "Direction of the position (default: long)." },
+                    "usd_notional": { "type": "number", "description": "Position size in USD." },
+                    "equity_percent": { "type": "number", "description": "Position size as a percent (0-100) of account equity." },
+                    "risk_amount": { "type": "number", "description": "Maximum USD loss if the stop is hit." },
+                    "stop_distance": { "type": "number", "description": "Distance from entry to stop in USD per unit, required with risk_amount." },
+                    "sub_account_id": { "type": "integer", "description": "Sub-account whose collateral is used (optional, defaults to 0)." }
+                },
+                "required": ["market_index"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let mode = match (args.usd_notional, args.equity_percent, args.risk_amount) {
+            (Some(usd), None, None) => SizingMode::UsdNotional(usd),
+            (None, Some(percent), None) => SizingMode::EquityPercent(percent),
+            (None, None, Some(risk_usd)) => SizingMode::Risk {
+                risk_usd,
+                stop_distance: args.stop_distance.ok_or(DriftDataError {
+                    message: "stop_distance is required with risk_amount".to_string()
+                })?,
+            },
+            _ => return Err(DriftDataError {
+                message: "Provide exactly one of usd_notional, equity_percent or risk_amount".to_string()
+            }),
+        };
+        let is_long = args.position_type.as_deref() != Some("short");
+
+        let handle = tokio::task::spawn(async move {
+            let client = init_drift_client().await?;
+            calculate_position_size(&client, args.market_index, mode, is_long, args.sub_account_id).await
+        });
+        let size = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await calculate_position_size".to_string()
+        })??;
+
+        Ok(format!(
+            "Market index {}:\n- Oracle Price: ${:.6}\n- Amount: {}\n- Base Asset Amount (1e9): {}\n- Notional: ${:.2}\n- Free Collateral: ${:.2}\n- Capped By Collateral: {}",
+            size.market_index, size.oracle_price, size.amount, size.base_asset_amount, size.notional_usd, size.free_collateral_usd, size.capped_by_collateral
+        ))
+    }
+}
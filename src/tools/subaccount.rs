@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use anchor_lang::InstructionData;
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use solana_sdk::{instruction::{AccountMeta, Instruction}, pubkey::Pubkey, system_program, sysvar};
+use drift_rs::{
+    constants,
+    drift_idl,
+    types::OrderStatus,
+    DriftClient, Wallet,
+};
+
+use crate::tools::shared::*;
+use crate::tools::user::send_or_serialize;
+
+fn decode_name(name: &[u8; 32]) -> String {
+    String::from_utf8_lossy(name).trim_end_matches(['\0', ' ']).to_string()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubAccountSummary {
+    pub sub_account_id: u16,
+    pub user: String,
+    pub name: String,
+    pub open_perp_positions: usize,
+    pub open_spot_positions: usize,
+    pub open_orders: usize,
+}
+
+pub async fn list_sub_accounts(client: &DriftClient) -> Result<Vec<SubAccountSummary>, DriftDataError> {
+    let mut summaries = Vec::new();
+    for (sub_account_id, user_pubkey) in list_sub_account_pubkeys(client).await? {
+        // Sub-accounts can be deleted, leaving gaps in the id range.
+        let Ok(user) = client.get_user_account(&user_pubkey).await else {
+            continue;
+        };
+        summaries.push(SubAccountSummary {
+            sub_account_id,
+            user: user_pubkey.to_string(),
+            name: decode_name(&user.name),
+            open_perp_positions: user.perp_positions.iter().filter(|p| p.is_open_position()).count(),
+            open_spot_positions: user.spot_positions.iter().filter(|s| !s.is_available()).count(),
+            open_orders: user.orders.iter().filter(|o| o.status == OrderStatus::Open).count(),
+        });
+    }
+    Ok(summaries)
+}
+
+/// The wallet that owns the Drift accounts: `authority` if given, otherwise the account the
+/// agent trades for. Only a wallet can sign, so a program-owned authority such as the vault is
+/// rejected rather than returned as a transaction nobody can sign.
+fn resolve_authority(client: &DriftClient, authority: Option<&str>) -> Result<Pubkey, DriftDataError> {
+    let authority = authority.map(|v| parse_pubkey(v, "authority")).transpose()?.unwrap_or(*client.wallet().authority());
+    if !authority.is_on_curve() {
+        return Err(DriftDataError {
+            message: format!("{} is a program account and cannot sign; pass the authority wallet", authority)
+        });
+    }
+    Ok(authority)
+}
+
+/// Creates the next sub-account of `authority`. Drift only lets the authority initialize users,
+/// so this is sent when the agent keypair is the authority and otherwise returned unsigned.
+pub async fn create_sub_account(client: &DriftClient, authority: &Pubkey, name: &str, send: bool) -> Result<(u16, String), DriftDataError> {
+    let user_stats = client.get_user_stats(authority).await.map_err(|_| DriftDataError {
+        message: format!("Failed to get user stats of {}", authority)
+    })?;
+    let sub_account_id = user_stats.number_of_sub_accounts_created;
+    let mut name_bytes = [b' '; 32];
+    for (slot, byte) in name_bytes.iter_mut().zip(name.bytes()) {
+        *slot = byte;
+    }
+
+    let instruction = Instruction {
+        program_id: constants::PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(Wallet::derive_user_account(authority, sub_account_id), false), // User
+            AccountMeta::new(Wallet::derive_stats_account(authority), false), // User Stats
+            AccountMeta::new(*constants::state_account(), false), // Drift State
+            AccountMeta::new_readonly(*authority, true), // Authority
+            AccountMeta::new(*authority, true), // Payer
+            AccountMeta::new_readonly(sysvar::rent::ID, false), // Rent
+            AccountMeta::new_readonly(system_program::ID, false), // System Program
+        ],
+        data: InstructionData::data(&drift_idl::instructions::InitializeUser {
+            sub_account_id,
+            name: name_bytes,
+        }),
+    };
+
+    let result = send_or_serialize(client, &[instruction], authority, send).await?;
+    Ok((sub_account_id, result))
+}
+
+/// Moves spot collateral between two sub-accounts of `authority`. Drift checks the signer
+/// against the user stats authority, so this is sent when the agent keypair is the authority
+/// and otherwise returned unsigned.
+pub async fn transfer_collateral(
+    client: &DriftClient,
+    authority: &Pubkey,
+    from_sub_account_id: u16,
+    to_sub_account_id: u16,
+    spot_market_index: u16,
+    amount: u64,
+    send: bool,
+) -> Result<String, DriftDataError> {
+    let from_user_pubkey = Wallet::derive_user_account(authority, from_sub_account_id);
+    let to_user_pubkey = Wallet::derive_user_account(authority, to_sub_account_id);
+    let from_user = client.get_user_account(&from_user_pubkey).await.map_err(|_| DriftDataError {
+        message: format!("Failed to get sub-account {}", from_sub_account_id)
+    })?;
+
+    let mut accounts = vec![
+        AccountMeta::new(from_user_pubkey, false), // From User
+        AccountMeta::new(to_user_pubkey, false), // To User
+        AccountMeta::new(Wallet::derive_stats_account(authority), false), // User Stats
+        AccountMeta::new_readonly(*authority, true), // Authority
+        AccountMeta::new_readonly(*constants::state_account(), false), // Drift State
+        AccountMeta::new_readonly(constants::derive_spot_market_vault(spot_market_index), false), // Spot Market Vault
+    ];
+    accounts.extend(user_remaining_accounts(client, &from_user, &[spot_market_index], &[]).await?);
+
+    let instruction = Instruction {
+        program_id: constants::PROGRAM_ID,
+        accounts,
+        data: InstructionData::data(&drift_idl::instructions::TransferDeposit {
+            market_index: spot_market_index,
+            amount,
+        }),
+    };
+
+    send_or_serialize(client, &[instruction], authority, send).await
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftListSubAccountsArgs {}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftListSubAccounts;
+
+impl Tool for DriftListSubAccounts {
+    const NAME: &'static str = "drift_list_sub_accounts";
+
+    type Error = DriftDataError;
+    type Args = DriftListSubAccountsArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_list_sub_accounts".to_string(),
+            description: "Lists the Drift sub-accounts of the vault with their ids, names and counts of open positions and orders. Use the ids as sub_account_id in other tools.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        }
+    }
+
+    async fn call(&self, _args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let handle = tokio::task::spawn(async move {
+            let client = init_drift_client().await?;
+            list_sub_accounts(&client).await
+        });
+        let summaries = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await list_sub_accounts".to_string()
+        })??;
+
+        if summaries.is_empty() {
+            return Ok("No sub-accounts found.".to_string());
+        }
+        Ok(summaries.iter().map(|s| {
+            format!(
+                "- Sub-account {}: {}\n  User: {}\n  Open Perp Positions: {}\n  Open Spot Positions: {}\n  Open Orders: {}\n",
+                s.sub_account_id, s.name, s.user, s.open_perp_positions, s.open_spot_positions, s.open_orders
+            )
+        }).collect::<Vec<_>>().join("\n"))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftCreateSubAccountArgs {
+    pub name: String,
+    pub authority: Option<String>,
+    pub send: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftCreateSubAccount;
+
+impl Tool for DriftCreateSubAccount {
+    const NAME: &'static str = "drift_create_sub_account";
+
+    type Error = DriftDataError;
+    type Args = DriftCreateSubAccountArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_create_sub_account".to_string(),
+            description: "Creates the next Drift sub-account of an authority wallet and returns its sub_account_id. Only the authority can sign it: returns an unsigned base64 transaction for the authority, or signs and sends it when send is true and the authority is the agent's own wallet.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "Name of the new sub-account (max 32 characters)." },
+                    "authority": { "type": "string", "description": "Authority wallet public key (optional, defaults to the account the agent trades for)." },
+                    "send": { "type": "boolean", "description": "Sign and send with the agent's wallet instead of returning an unsigned transaction (default: false)." }
+                },
+                "required": ["name"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let handle = tokio::task::spawn(async move {
+            let client = init_drift_client().await?;
+            let authority = resolve_authority(&client, args.authority.as_deref())?;
+            create_sub_account(&client, &authority, &args.name, args.send.unwrap_or(false)).await
+        });
+        let (sub_account_id, result) = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await create_sub_account".to_string()
+        })??;
+
+        Ok(format!("Sub-account {}: {}", sub_account_id, result))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftTransferCollateralArgs {
+    pub from_sub_account_id: u16,
+    pub to_sub_account_id: u16,
+    pub spot_market_index: Option<u16>,
+    pub amount: f64,
+    pub authority: Option<String>,
+    pub send: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftTransferCollateral;
+
+impl Tool for DriftTransferCollateral {
+    const NAME: &'static str = "drift_transfer_collateral";
+
+    type Error = DriftDataError;
+    type Args = DriftTransferCollateralArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_transfer_collateral".to_string(),
+            description: "Moves spot collateral (USDC by default) from one Drift sub-account to another of the same authority. Only the authority can sign it: returns an unsigned base64 transaction for the authority, or signs and sends it when send is true and the authority is the agent's own wallet.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "from_sub_account_id": { "type": "integer", "description": "Sub-account to move collateral from." },
+                    "to_sub_account_id": { "type": "integer", "description": "Sub-account to move collateral to." },
+                    "spot_market_index": { "type": "integer", "description": "Spot market of the collateral (default: 0, USDC)." },
+                    "amount": { "type": "number", "description": "Amount in token units, e.g. 100.5 USDC." },
+                    "authority": { "type": "string", "description": "Authority wallet public key (optional, defaults to the account the agent trades for)." },
+                    "send": { "type": "boolean", "description": "Sign and send with the agent's wallet instead of returning an unsigned transaction (default: false)." }
+                },
+                "required": ["from_sub_account_id", "to_sub_account_id", "amount"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let spot_market_index = args.spot_market_index.unwrap_or(0);
+        let (from_sub_account_id, to_sub_account_id, token_amount) = (args.from_sub_account_id, args.to_sub_account_id, args.amount);
+        let handle = tokio::task::spawn(async move {
+            let client = init_drift_client().await?;
+            let authority = resolve_authority(&client, args.authority.as_deref())?;
+            let spot_market = client.get_spot_market_account(spot_market_index).await.map_err(|_| DriftDataError {
+                message: format!("Failed to get spot market {}", spot_market_index)
+            })?;
+            let amount = (token_amount * 10f64.powi(spot_market.decimals as i32)) as u64;
+            transfer_collateral(&client, &authority, from_sub_account_id, to_sub_account_id, spot_market_index, amount, args.send.unwrap_or(false)).await
+        });
+        let result = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await transfer_collateral".to_string()
+        })??;
+
+        Ok(format!(
+            "Transfer of {} from sub-account {} to {}: {}",
+            token_amount, from_sub_account_id, to_sub_account_id, result
+        ))
+    }
+}
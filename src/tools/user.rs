@@ -1,109 +1,421 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    transaction::Transaction,
+};
+use spl_associated_token_account::{get_associated_token_address, instruction::create_associated_token_account_idempotent};
 
-use anchor_lang::InstructionData;
-use drift_rs::drift_idl;
+use drift_rs::{constants, math::constants::PRICE_PRECISION, types::MarketId, DriftClient, Pubkey};
 use rig::{
     completion::ToolDefinition,
     tool::Tool,
 };
 
-#[derive(Debug, thiserror::Error)]
-#[error("Drift transaction error")]
-pub struct DriftError;
+use crate::tools::shared::*;
+use crate::tools::vault::*;
+use crate::tools::vault_manager::{drift_signer, percent_to_precision, RequestWithdrawArgs, WithdrawUnit};
+use crate::tools::depositor::*;
+use crate::tools::anchor::*;
+
+/// Accounts used by the vault program's depositor `deposit` and `withdraw` instructions.
+pub struct DepositorAccounts {
+    pub vault: Pubkey,
+    pub authority: Pubkey,
+    pub state: ContractState,
+    pub mint: Pubkey,
+    pub decimals: u32,
+    pub user_token_account: Pubkey,
+    pub remaining_accounts: Vec<AccountMeta>,
+}
+
+impl DepositorAccounts {
+    /// Resolves the vault and the depositor's token account, defaulting to the associated token
+    /// account of the vault's deposit mint.
+    pub async fn load(client: &DriftClient, vault: &Pubkey, authority: &Pubkey, user_token_account: Option<Pubkey>) -> Result<Self, DriftDataError> {
+        let state = fetch_vault_state(&client.rpc(), vault).await?;
+        let spot_market = client.get_spot_market_account(state.spot_market_index).await.map_err(|_| DriftDataError {
+            message: format!("Failed to get spot market {}", state.spot_market_index)
+        })?;
+        let vault_user = client.get_user_account(&state.user).await.map_err(|_| DriftDataError {
+            message: "Failed to get the vault's user account".to_string()
+        })?;
+        let remaining_accounts = user_remaining_accounts(client, &vault_user, &[state.spot_market_index], &[]).await?;
+
+        Ok(DepositorAccounts {
+            vault: *vault,
+            authority: *authority,
+            mint: spot_market.mint,
+            decimals: spot_market.decimals,
+            user_token_account: user_token_account.unwrap_or_else(|| get_associated_token_address(authority, &spot_market.mint)),
+            state,
+            remaining_accounts,
+        })
+    }
+
+    fn accounts(&self, with_drift_signer: bool) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new(self.vault, false), // Vault
+            AccountMeta::new(vault_depositor_address(&self.vault, &self.authority), false), // Vault Depositor
+            AccountMeta::new_readonly(self.authority, true), // Authority
+            AccountMeta::new(self.state.token_account, false), // Vault Token Account
+            AccountMeta::new(self.state.user_stats, false), // Drift User Stats
+            AccountMeta::new(self.state.user, false), // Drift User
+            AccountMeta::new_readonly(*constants::state_account(), false), // Drift State
+            AccountMeta::new(constants::derive_spot_market_vault(self.state.spot_market_index), false), // Drift Spot Market Vault
+        ];
+        if with_drift_signer {
+            accounts.push(AccountMeta::new_readonly(drift_signer(), false)); // Drift Signer
+        }
+        accounts.extend([
+            AccountMeta::new(self.user_token_account, false), // User Token Account
+            AccountMeta::new_readonly(constants::PROGRAM_ID, false), // Drift Program
+            AccountMeta::new_readonly(constants::TOKEN_PROGRAM_ID, false), // Token Program
+        ]);
+        accounts.extend(self.remaining_accounts.iter().cloned());
+        accounts
+    }
+
+    fn withdraw_request_accounts(&self) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new(self.vault, false), // Vault
+            AccountMeta::new(vault_depositor_address(&self.vault, &self.authority), false), // Vault Depositor
+            AccountMeta::new_readonly(self.authority, true), // Authority
+            AccountMeta::new_readonly(self.state.user_stats, false), // Drift User Stats
+            AccountMeta::new_readonly(self.state.user, false), // Drift User
+            AccountMeta::new_readonly(*constants::state_account(), false), // Drift State
+        ];
+        accounts.extend(self.remaining_accounts.iter().cloned());
+        accounts
+    }
+
+    pub async fn load_depositor(&self, client: &DriftClient) -> Result<VaultDepositor, DriftDataError> {
+        let address = vault_depositor_address(&self.vault, &self.authority);
+        fetch_anchor_account(&client.rpc(), &address).await.map_err(|e| DriftDataError {
+            message: format!("No depositor account for {} in vault {}: {}", self.authority, self.vault, e.message)
+        })
+    }
+
+    /// Creates the depositor's associated token account first when it does not exist yet.
+    async fn create_token_account_if_missing(&self, client: &DriftClient, instructions: &mut Vec<Instruction>) {
+        if client.rpc().get_account(&self.user_token_account).await.is_err()
+            && self.user_token_account == get_associated_token_address(&self.authority, &self.mint)
+        {
+            instructions.push(create_associated_token_account_idempotent(
+                &self.authority,
+                &self.authority,
+                &self.mint,
+                &constants::TOKEN_PROGRAM_ID,
+            ));
+        }
+    }
+}
+
+/// Instructions for a depositor to deposit `amount` (spot market precision) into a vault,
+/// creating the depositor's VaultDepositor account first if needed.
+pub async fn depositor_deposit_instructions(client: &DriftClient, accounts: &DepositorAccounts, amount: u64) -> Result<Vec<Instruction>, DriftDataError> {
+    let mut instructions = Vec::new();
+    let vault_depositor = vault_depositor_address(&accounts.vault, &accounts.authority);
+    if client.rpc().get_account(&vault_depositor).await.is_err() {
+        if accounts.state.permissioned {
+            return Err(DriftDataError {
+                message: format!("{} has no depositor account and the vault is permissioned; ask the manager to create it", accounts.authority)
+            });
+        }
+        instructions.push(anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "initialize_vault_depositor", &(), vec![
+            AccountMeta::new_readonly(accounts.vault, false), // Vault
+            AccountMeta::new(vault_depositor, false), // Vault Depositor
+            AccountMeta::new_readonly(accounts.authority, true), // Authority
+            AccountMeta::new(accounts.authority, true), // Payer
+            AccountMeta::new_readonly(solana_sdk::sysvar::rent::id(), false), // Rent
+            AccountMeta::new_readonly(solana_sdk::system_program::id(), false), // System Program
+        ]));
+    }
+    instructions.push(anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "deposit", &amount, accounts.accounts(false)));
+    Ok(instructions)
+}
+
+/// Instructions for a depositor to complete a withdraw request whose redeem period has passed,
+/// creating the depositor's associated token account first if needed.
+pub async fn depositor_withdraw_instructions(client: &DriftClient, accounts: &DepositorAccounts) -> Result<Vec<Instruction>, DriftDataError> {
+    let request = accounts.load_depositor(client).await?.last_withdraw_request;
+    if request.shares == 0 {
+        return Err(DriftDataError { message: format!("{} has no pending withdraw request", accounts.authority) });
+    }
+    let available_in = request.ts + accounts.state.redeem_period - chrono::Utc::now().timestamp();
+    if available_in > 0 {
+        return Err(DriftDataError {
+            message: format!("The withdraw request can be completed in {}s, once the vault's redeem period has passed", available_in)
+        });
+    }
+
+    let mut instructions = Vec::new();
+    accounts.create_token_account_if_missing(client, &mut instructions).await;
+    instructions.push(anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "withdraw", &(), accounts.accounts(true)));
+    Ok(instructions)
+}
+
+/// Instruction requesting a withdrawal of `withdraw_amount` in `withdraw_unit`; the request can be
+/// completed with `depositor_withdraw_instructions` once the vault's redeem period has passed.
+pub fn depositor_request_withdraw_instructions(accounts: &DepositorAccounts, withdraw_amount: u64, withdraw_unit: WithdrawUnit) -> Vec<Instruction> {
+    let args = RequestWithdrawArgs { withdraw_amount, withdraw_unit };
+    vec![anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "request_withdraw", &args, accounts.withdraw_request_accounts())]
+}
+
+pub fn depositor_cancel_withdraw_request_instructions(accounts: &DepositorAccounts) -> Vec<Instruction> {
+    vec![anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "cancel_request_withdraw", &(), accounts.withdraw_request_accounts())]
+}
+
+/// Serializes `instructions` into an unsigned legacy transaction paid by `payer`, base64 encoded
+/// for a frontend wallet to sign and send.
+pub async fn unsigned_transaction_base64(client: &DriftClient, instructions: &[Instruction], payer: &Pubkey) -> Result<String, DriftDataError> {
+    let mut transaction = Transaction::new_unsigned(Message::new(instructions, Some(payer)));
+    transaction.message.recent_blockhash = client.rpc().get_latest_blockhash().await.map_err(|_| DriftDataError {
+        message: "Failed to get latest blockhash".to_string()
+    })?;
+    let bytes = bincode::serialize(&transaction).map_err(|_| DriftDataError {
+        message: "Failed to serialize transaction".to_string()
+    })?;
+    Ok(BASE64.encode(bytes))
+}
+
+/// Signs and sends `instructions` when `authority` is the loaded agent keypair, otherwise
+/// returns them as an unsigned base64 transaction for the authority to sign.
+pub(crate) async fn send_or_serialize(client: &DriftClient, instructions: &[Instruction], authority: &Pubkey, send: bool) -> Result<String, DriftDataError> {
+    if send {
+        if *authority != client.wallet().signer() {
+            return Err(DriftDataError {
+                message: format!("Cannot sign for {}; omit send to get an unsigned transaction", authority)
+            });
+        }
+        let signature = send_instructions(client, instructions).await?;
+        return Ok(format!("Transaction sent.\nSignature: {}", signature));
+    }
+    let transaction = unsigned_transaction_base64(client, instructions, authority).await?;
+    Ok(format!("Unsigned transaction (base64, fee payer and signer {}):\n{}", authority, transaction))
+}
+
+/// Resolves the vault (default AGENT_VAULT) and depositor (default the agent keypair) of a request.
+async fn resolve_depositor(
+    client: &DriftClient,
+    vault_address: Option<&str>,
+    authority: Option<&str>,
+    user_token_account: Option<&str>,
+) -> Result<DepositorAccounts, DriftDataError> {
+    let vault = vault_pubkey_or_agent_vault(vault_address)?;
+    let authority = authority.map(|v| parse_pubkey(v, "authority")).transpose()?.unwrap_or(client.wallet().signer());
+    let user_token_account = user_token_account.map(|v| parse_pubkey(v, "user token account")).transpose()?;
+    DepositorAccounts::load(client, &vault, &authority, user_token_account).await
+}
 
 #[derive(Deserialize, Serialize)]
 pub struct DepositArgs {
-    pub amount: u64,
-    pub spot_market_index: u16,
-    pub user_token_account: String,
-    pub reduce_only: Option<bool>,
+    pub amount: f64,                        // Whole tokens of the vault's deposit asset, e.g. 1.5 = 1.5 USDC
+    pub vault_address: Option<String>,
+    pub authority: Option<String>,
+    pub user_token_account: Option<String>,
+    pub send: Option<bool>,
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct WithdrawArgs {
-    pub amount: u64,
-    pub spot_market_index: u16,
-    pub user_token_account: String,
-    pub reduce_only: Option<bool>,
+    pub vault_address: Option<String>,
+    pub authority: Option<String>,
+    pub user_token_account: Option<String>,
+    pub send: Option<bool>,
 }
 
 pub struct Deposit;
 impl Deposit {
-    pub async fn get_tx_data(args: DepositArgs) -> Result<String, DriftError> {
-        let instruction_data = InstructionData::data(&drift_idl::instructions::Deposit {
-            market_index: args.spot_market_index,
-            amount: args.amount,
-            reduce_only: args.reduce_only.unwrap_or(false),
-        });
-        Ok(hex::encode(instruction_data))
+    pub async fn get_tx_data(args: DepositArgs) -> Result<String, DriftDataError> {
+        let client = init_drift_client().await?;
+        if args.amount.is_nan() || args.amount <= 0.0 {
+            return Err(DriftDataError { message: "amount must be positive".to_string() });
+        }
+        let accounts = resolve_depositor(&client, args.vault_address.as_deref(), args.authority.as_deref(), args.user_token_account.as_deref()).await?;
+        let amount = (args.amount * 10f64.powi(accounts.decimals as i32)).round() as u64;
+        let instructions = depositor_deposit_instructions(&client, &accounts, amount).await?;
+        send_or_serialize(&client, &instructions, &accounts.authority, args.send.unwrap_or(false)).await
     }
 }
 
 impl Tool for Deposit {
     const NAME: &'static str = "deposit";
 
-    type Error = DriftError;
+    type Error = DriftDataError;
     type Args = DepositArgs;
     type Output = String;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
             name: "deposit".to_string(),
-            description: "Generate transaction data for a Drift deposit.".to_string(),
+            description: "Builds a complete transaction depositing into a Drift vault as a depositor, creating the depositor account if needed. Returns an unsigned base64 transaction for the depositor to sign, or signs and sends it when send is true and the depositor is the agent's own wallet.".to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
-                    "amount": { "type": "number", "description": "Amount to deposit" },
-                    "spot_market_index": { "type": "integer", "description": "Market index" },
-                    "user_token_account": { "type": "string", "description": "User's token account pubkey" },
-                    "reduce_only": { "type": "boolean", "description": "Reduce-only flag" }
-                }
+                    "amount": { "type": "number", "description": "Amount in whole tokens of the vault's deposit asset (e.g. 1.5 for 1.5 USDC)." },
+                    "vault_address": { "type": "string", "description": "The public key of the vault (optional, defaults to the agent vault)." },
+                    "authority": { "type": "string", "description": "Depositor wallet public key (optional, defaults to the agent's wallet)." },
+                    "user_token_account": { "type": "string", "description": "Token account to deposit from (optional, defaults to the depositor's associated token account)." },
+                    "send": { "type": "boolean", "description": "Sign and send with the agent's wallet instead of returning an unsigned transaction (default: false)." }
+                },
+                "required": ["amount"]
             }),
         }
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        Self::get_tx_data(args).await
+        let handle = tokio::task::spawn(Self::get_tx_data(args));
+        handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await deposit".to_string()
+        })?
     }
 }
 
 pub struct Withdraw;
 impl Withdraw {
-    pub async fn get_tx_data(args: WithdrawArgs) -> Result<String, DriftError> {
-        let instruction_data = InstructionData::data(&drift_idl::instructions::Withdraw {
-            market_index: args.spot_market_index,
-            amount: args.amount,
-            reduce_only: args.reduce_only.unwrap_or(false),
-        });
-        Ok(hex::encode(instruction_data))
+    pub async fn get_tx_data(args: WithdrawArgs) -> Result<String, DriftDataError> {
+        let client = init_drift_client().await?;
+        let accounts = resolve_depositor(&client, args.vault_address.as_deref(), args.authority.as_deref(), args.user_token_account.as_deref()).await?;
+        let instructions = depositor_withdraw_instructions(&client, &accounts).await?;
+        send_or_serialize(&client, &instructions, &accounts.authority, args.send.unwrap_or(false)).await
     }
 }
 
 impl Tool for Withdraw {
     const NAME: &'static str = "withdraw";
 
-    type Error = DriftError;
+    type Error = DriftDataError;
     type Args = WithdrawArgs;
     type Output = String;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
             name: "withdraw".to_string(),
-            description: "Generate transaction data for a Drift withdrawal.".to_string(),
+            description: "Builds a complete transaction withdrawing a depositor's pending withdraw request from a Drift vault once the redeem period has passed, creating the depositor's token account if needed. Returns an unsigned base64 transaction, or signs and sends it when send is true and the depositor is the agent's own wallet.".to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
-                    "amount": { "type": "number", "description": "Amount to withdraw" },
-                    "spot_market_index": { "type": "integer", "description": "Market index" },
-                    "user_token_account": { "type": "string", "description": "User's token account pubkey" },
-                    "reduce_only": { "type": "boolean", "description": "Reduce-only flag" }
+                    "vault_address": { "type": "string", "description": "The public key of the vault (optional, defaults to the agent vault)." },
+                    "authority": { "type": "string", "description": "Depositor wallet public key (optional, defaults to the agent's wallet)." },
+                    "user_token_account": { "type": "string", "description": "Token account to withdraw to (optional, defaults to the depositor's associated token account)." },
+                    "send": { "type": "boolean", "description": "Sign and send with the agent's wallet instead of returning an unsigned transaction (default: false)." }
                 }
             }),
         }
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        Self::get_tx_data(args).await
+        let handle = tokio::task::spawn(Self::get_tx_data(args));
+        handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await withdraw".to_string()
+        })?
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftDepositorWithdrawArgs {
+    pub action: String,                 // "request", "cancel", "complete" or "status"
+    pub amount: Option<f64>,            // For request
+    pub unit: Option<String>,           // "usd" (default), "shares" or "shares_percent"
+    pub vault_address: Option<String>,
+    pub authority: Option<String>,
+    pub user_token_account: Option<String>,
+    pub send: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftDepositorWithdraw;
+
+impl DriftDepositorWithdraw {
+    pub async fn execute(args: DriftDepositorWithdrawArgs) -> Result<String, DriftDataError> {
+        let client = init_drift_client().await?;
+        let accounts = resolve_depositor(&client, args.vault_address.as_deref(), args.authority.as_deref(), args.user_token_account.as_deref()).await?;
+        let send = args.send.unwrap_or(false);
+
+        let instructions = match args.action.as_str() {
+            "status" => return Self::status(&client, &accounts).await,
+            "request" => {
+                let amount = args.amount.ok_or(DriftDataError { message: "amount is required to request a withdrawal".to_string() })?;
+                let (withdraw_amount, unit) = match args.unit.as_deref().unwrap_or("usd") {
+                    "usd" => {
+                        let token_price = if accounts.state.spot_market_index == 0 {
+                            1.0
+                        } else {
+                            get_token_oracle_price(MarketId::spot(accounts.state.spot_market_index), &client).await? as f64 / PRICE_PRECISION as f64
+                        };
+                        ((amount / token_price * 10f64.powi(accounts.decimals as i32)) as u64, WithdrawUnit::Token)
+                    }
+                    "shares" => (amount as u64, WithdrawUnit::Shares),
+                    "shares_percent" => (percent_to_precision(amount) as u64, WithdrawUnit::SharesPercent),
+                    other => return Err(DriftDataError { message: format!("Unknown withdraw unit {}", other) }),
+                };
+                depositor_request_withdraw_instructions(&accounts, withdraw_amount, unit)
+            }
+            "cancel" => depositor_cancel_withdraw_request_instructions(&accounts),
+            "complete" => depositor_withdraw_instructions(&client, &accounts).await?,
+            other => return Err(DriftDataError { message: format!("Unknown withdraw action {}", other) }),
+        };
+        send_or_serialize(&client, &instructions, &accounts.authority, send).await
+    }
+
+    async fn status(client: &DriftClient, accounts: &DepositorAccounts) -> Result<String, DriftDataError> {
+        let depositor = get_vault_depositor(client, &accounts.vault, &accounts.authority).await?;
+        let redeem_period = accounts.state.redeem_period;
+        Ok(match depositor.pending_withdraw {
+            None => format!(
+                "No pending withdraw request for {} (shares: {}, current value: {:.6}). Requests can be completed {}s after they are made.",
+                accounts.authority, depositor.shares, depositor.current_value, redeem_period
+            ),
+            Some(request) => format!(
+                "Pending withdraw request for {}:\n- Shares: {}\n- Value At Request: {:.6}\n- Requested At: {}\n- {}",
+                accounts.authority,
+                request.shares,
+                request.value,
+                chrono::DateTime::from_timestamp(request.ts, 0).map_or(request.ts.to_string(), |t| t.to_rfc3339()),
+                if request.available_in_secs == 0 {
+                    "Can be completed now".to_string()
+                } else {
+                    format!("Can be completed in {}s", request.available_in_secs)
+                },
+            ),
+        })
+    }
+}
+
+impl Tool for DriftDepositorWithdraw {
+    const NAME: &'static str = "drift_depositor_withdraw";
+
+    type Error = DriftDataError;
+    type Args = DriftDepositorWithdrawArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_depositor_withdraw".to_string(),
+            description: "Manages a vault depositor's withdrawal: request a withdrawal in USD, shares or percent of shares, cancel the pending request, complete it once the vault's redeem period has passed, or show its status (amount requested and time left). Transactions are returned unsigned in base64, or signed and sent when send is true and the depositor is the agent's own wallet.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "action": { "type": "string", "enum": ["request", "cancel", "complete", "status"], "description": "Which step to perform." },
+                    "amount": { "type": "number", "description": "Amount to request, in the given unit." },
+                    "unit": { "type": "string", "enum": ["usd", "shares", "shares_percent"], "description": "Unit of amount (default: usd)." },
+                    "vault_address": { "type": "string", "description": "The public key of the vault (optional, defaults to the agent vault)." },
+                    "authority": { "type": "string", "description": "Depositor wallet public key (optional, defaults to the agent's wallet)." },
+                    "user_token_account": { "type": "string", "description": "Token account to withdraw to on complete (optional, defaults to the associated token account)." },
+                    "send": { "type": "boolean", "description": "Sign and send with the agent's wallet instead of returning an unsigned transaction (default: false)." }
+                },
+                "required": ["action"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let handle = tokio::task::spawn(Self::execute(args));
+        handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await depositor withdraw".to_string()
+        })?
     }
 }
@@ -1,15 +1,28 @@
-use std::{collections::HashMap, env, str::FromStr};
+use std::env;
 use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use rig::{
     completion::ToolDefinition,
     tool::Tool,
 };
-use drift_rs::Pubkey;
+use drift_rs::{
+    math::constants::PRICE_PRECISION,
+    types::MarketId,
+    DriftClient,
+    Pubkey,
+};
 
 use crate::tools::shared::*;
+use crate::tools::anchor::*;
+use crate::tools::margin::account_net_value_usd;
+
+/// The drift-vaults program.
+pub const DRIFT_VAULTS_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("vAuLTsyrvSfZRuRB3XgvkPwNGgYSs9YRYymVebLKoxR");
+
+/// Account types of the drift-vaults program, used to name a mismatched discriminator.
+pub const DRIFT_VAULTS_ACCOUNTS: &[&str] = &["Vault", "VaultDepositor", "TokenizedVaultDepositor", "VaultProtocol"];
 
 #[derive(BorshDeserialize, BorshSerialize, Debug)]
 pub struct ContractState {
@@ -47,7 +60,13 @@ pub struct ContractState {
     pub bump: u8,
     pub permissioned: bool,
     pub padding: [u64; 8],
-    pub extra_padding: [u8; 8],
+}
+
+impl AnchorAccount for ContractState {
+    const ACCOUNT_NAME: &'static str = "Vault";
+    const PROGRAM_ID: Pubkey = DRIFT_VAULTS_PROGRAM_ID;
+    const DATA_SIZE: usize = 528;
+    const SIBLING_ACCOUNTS: &'static [&'static str] = DRIFT_VAULTS_ACCOUNTS;
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Debug)]
@@ -57,84 +76,277 @@ pub struct WithdrawRequest {
     pub ts: i64,
 }
 
+const ONE_YEAR_SECS: f64 = 365.0 * 24.0 * 3600.0;
+/// Vault fee and hurdle rates are stored with PERCENTAGE_PRECISION (1e6 = 100%).
+pub(crate) const PERCENTAGE_PRECISION: f64 = 1_000_000.0;
+
+impl ContractState {
+    pub fn name(&self) -> String {
+        self.name.iter()
+            .filter(|&&b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+            .map(|&b| b as char)
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+}
+
+pub async fn fetch_vault_state(rpc_client: &RpcClient, vault_pubkey: &Pubkey) -> Result<ContractState, DriftDataError> {
+    fetch_anchor_account(rpc_client, vault_pubkey).await
+}
+
+/// Vault state with token amounts in whole units of the vault's spot market, rates in percent,
+/// and metrics derived from the vault's Drift account.
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultInfo {
+    pub vault_address: String,
+    pub name: String,
+    pub manager: String,
+    pub delegate: String,
+    pub liquidation_delegate: String,
+    pub token_account: String,
+    pub user: String,
+    pub user_stats: String,
+    pub spot_market_index: u16,
+    /// Token decimals of the vault's spot market.
+    pub decimals: u32,
+    pub permissioned: bool,
+    pub init_ts: i64,
+
+    pub user_shares: u128,
+    pub total_shares: u128,
+    pub shares_base: u32,
+    pub net_deposits: f64,
+    pub total_deposits: f64,
+    pub total_withdraws: f64,
+    pub manager_net_deposits: f64,
+    pub manager_total_deposits: f64,
+    pub manager_total_withdraws: f64,
+    pub total_withdraw_requested: f64,
+    pub max_tokens: f64,
+    pub min_deposit_amount: f64,
+    pub management_fee_percent: f64,
+    pub profit_share_percent: f64,
+    pub hurdle_rate_percent: f64,
+    /// How long after a withdraw request it can be completed. Redemption timing is per
+    /// depositor: each request starts its own period, reported by `get_vault_depositor`.
+    pub redeem_period_secs: i64,
+    pub last_fee_update_ts: i64,
+    pub liquidation_start_ts: i64,
+
+    /// Net value of the vault's Drift account, in vault tokens and in USD.
+    pub equity: f64,
+    pub equity_usd: f64,
+    /// Vault tokens per share unit; 1.0 at inception.
+    pub share_price: f64,
+    pub manager_shares: u128,
+    pub manager_equity: f64,
+    pub manager_equity_percent: f64,
+    /// Management fees and profit share paid to the manager to date.
+    pub fees_accrued: f64,
+    /// Management fee accrued since `last_fee_update_ts` and not yet charged.
+    pub pending_management_fee: f64,
+    /// Depositors' return on their net deposits, in percent.
+    pub depositor_return_percent: Option<f64>,
+    /// "none", "above" or "below" the hurdle rate, from the depositors' aggregate return.
+    pub hurdle_status: String,
+    pub manager_withdraw_requested: f64,
+    /// Seconds until the manager's pending withdraw request can be completed, 0 if it already can.
+    pub manager_withdraw_available_in_secs: Option<i64>,
+}
+
+/// The vault at `vault_address`, or AGENT_VAULT when none is given.
+pub(crate) fn vault_pubkey_or_agent_vault(vault_address: Option<&str>) -> Result<Pubkey, DriftDataError> {
+    match vault_address {
+        Some(vault) => parse_pubkey(vault, "vault address"),
+        None => parse_pubkey(
+            &env::var("AGENT_VAULT").map_err(|_| DriftDataError { message: "Missing AGENT_VAULT env variable".to_string() })?,
+            "AGENT_VAULT",
+        ),
+    }
+}
+
+/// Decodes a vault and derives its equity, share price, manager stake, fees and the manager's
+/// redemption timing.
+pub async fn get_vault_info(client: &DriftClient, vault_pubkey: &Pubkey) -> Result<VaultInfo, DriftDataError> {
+    let state = fetch_vault_state(&client.rpc(), vault_pubkey).await?;
+
+    let spot_market = client.get_spot_market_account(state.spot_market_index).await.map_err(|_| DriftDataError {
+        message: format!("Failed to get spot market {}", state.spot_market_index)
+    })?;
+    let token_precision = 10f64.powi(spot_market.decimals as i32);
+    let tokens = |amount: f64| amount / token_precision;
+
+    // Unweighted net account value, which is what the vault program values shares at.
+    let vault_user = client.get_user_account(&state.user).await.map_err(|_| DriftDataError {
+        message: "Failed to get the vault's user account".to_string()
+    })?;
+    let equity_usd = account_net_value_usd(client, &vault_user).await?;
+    let token_price = if state.spot_market_index == 0 {
+        1.0
+    } else {
+        get_token_oracle_price(MarketId::spot(state.spot_market_index), client).await? as f64 / PRICE_PRECISION as f64
+    };
+    let equity = if token_price > 0.0 { equity_usd / token_price } else { 0.0 };
+
+    let share_price = if state.total_shares > 0 {
+        equity * token_precision / state.total_shares as f64
+    } else {
+        1.0
+    };
+    let manager_shares = state.total_shares.saturating_sub(state.user_shares);
+    let share_of = |shares: u128| if state.total_shares > 0 { equity * shares as f64 / state.total_shares as f64 } else { 0.0 };
+    let manager_equity = share_of(manager_shares);
+
+    let now = chrono::Utc::now().timestamp();
+    let management_fee_rate = state.management_fee as f64 / PERCENTAGE_PRECISION;
+    let pending_management_fee = (equity * management_fee_rate * (now - state.last_fee_update_ts).max(0) as f64 / ONE_YEAR_SECS).max(0.0);
+
+    let depositor_net_deposits = tokens((state.net_deposits - state.manager_net_deposits) as f64);
+    let depositor_return_percent = (depositor_net_deposits > 0.0)
+        .then(|| (share_of(state.user_shares) - depositor_net_deposits) / depositor_net_deposits * 100.0);
+    let hurdle_rate_percent = state.hurdle_rate as f64 / PERCENTAGE_PRECISION * 100.0;
+    let hurdle_status = match depositor_return_percent {
+        _ if state.hurdle_rate == 0 => "none",
+        Some(ret) if ret >= hurdle_rate_percent => "above",
+        _ => "below",
+    }.to_string();
+
+    let request = &state.last_manager_withdraw_request;
+    let manager_withdraw_available_in_secs = (request.shares > 0)
+        .then(|| (request.ts + state.redeem_period - now).max(0));
+
+    Ok(VaultInfo {
+        vault_address: vault_pubkey.to_string(),
+        name: state.name(),
+        manager: state.manager.to_string(),
+        delegate: state.delegate.to_string(),
+        liquidation_delegate: state.liquidation_delegate.to_string(),
+        token_account: state.token_account.to_string(),
+        user: state.user.to_string(),
+        user_stats: state.user_stats.to_string(),
+        spot_market_index: state.spot_market_index,
+        decimals: spot_market.decimals,
+        permissioned: state.permissioned,
+        init_ts: state.init_ts,
+        user_shares: state.user_shares,
+        total_shares: state.total_shares,
+        shares_base: state.shares_base,
+        net_deposits: tokens(state.net_deposits as f64),
+        total_deposits: tokens(state.total_deposits as f64),
+        total_withdraws: tokens(state.total_withdraws as f64),
+        manager_net_deposits: tokens(state.manager_net_deposits as f64),
+        manager_total_deposits: tokens(state.manager_total_deposits as f64),
+        manager_total_withdraws: tokens(state.manager_total_withdraws as f64),
+        total_withdraw_requested: tokens(state.total_withdraw_requested as f64),
+        max_tokens: tokens(state.max_tokens as f64),
+        min_deposit_amount: tokens(state.min_deposit_amount as f64),
+        management_fee_percent: management_fee_rate * 100.0,
+        profit_share_percent: state.profit_share as f64 / PERCENTAGE_PRECISION * 100.0,
+        hurdle_rate_percent,
+        redeem_period_secs: state.redeem_period,
+        last_fee_update_ts: state.last_fee_update_ts,
+        liquidation_start_ts: state.liquidation_start_ts,
+        equity,
+        equity_usd,
+        share_price,
+        manager_shares,
+        manager_equity,
+        manager_equity_percent: if equity > 0.0 { manager_equity / equity * 100.0 } else { 0.0 },
+        fees_accrued: tokens(state.manager_total_fee as f64 + state.manager_total_profit_share as f64),
+        pending_management_fee,
+        depositor_return_percent,
+        hurdle_status,
+        manager_withdraw_requested: tokens(request.value as f64),
+        manager_withdraw_available_in_secs,
+    })
+}
+
+fn format_duration(secs: i64) -> String {
+    match secs {
+        0 => "now".to_string(),
+        s if s < 3600 => format!("{}m", s / 60),
+        s if s < 86400 => format!("{}h {}m", s / 3600, s % 3600 / 60),
+        s => format!("{}d {}h", s / 86400, s % 86400 / 3600),
+    }
+}
+
+impl VaultInfo {
+    pub fn to_text(&self) -> String {
+        let mut result = format!(
+            "**Vault {} ({}):**\n- Manager: {}\n- Delegate: {}\n- Liquidation Delegate: {}\n- Token Account: {}\n- Drift User: {}\n- Spot Market Index: {}\n- Permissioned: {}\n",
+            self.name, self.vault_address, self.manager, self.delegate, self.liquidation_delegate, self.token_account, self.user, self.spot_market_index, self.permissioned,
+        );
+        result.push_str(&format!(
+            "\n**Performance:**\n- Equity: {:.6} (${:.2})\n- Share Price: {:.6}\n- Manager Equity: {:.6} ({:.2}%)\n- Depositor Return: {}\n- Hurdle Rate: {:.2}% ({})\n- Fees Accrued: {:.6}\n- Pending Management Fee: {:.6}\n",
+            self.equity,
+            self.equity_usd,
+            self.share_price,
+            self.manager_equity,
+            self.manager_equity_percent,
+            self.depositor_return_percent.map_or("n/a".to_string(), |ret| format!("{:.2}%", ret)),
+            self.hurdle_rate_percent,
+            self.hurdle_status,
+            self.fees_accrued,
+            self.pending_management_fee,
+        ));
+        result.push_str(&format!(
+            "\n**Flows:**\n- Net Deposits: {:.6}\n- Total Deposits: {:.6}\n- Total Withdraws: {:.6}\n- Manager Net Deposits: {:.6}\n- Total Withdraw Requested: {:.6}\n- Max Tokens: {}\n- Min Deposit: {:.6}\n",
+            self.net_deposits,
+            self.total_deposits,
+            self.total_withdraws,
+            self.manager_net_deposits,
+            self.total_withdraw_requested,
+            if self.max_tokens > 0.0 { format!("{:.6}", self.max_tokens) } else { "unlimited".to_string() },
+            self.min_deposit_amount,
+        ));
+        result.push_str(&format!(
+            "\n**Terms:**\n- Management Fee: {:.2}%\n- Profit Share: {:.2}%\n- Redeem Period: {}\n- Manager Withdraw Request: {}\n",
+            self.management_fee_percent,
+            self.profit_share_percent,
+            format_duration(self.redeem_period_secs),
+            match self.manager_withdraw_available_in_secs {
+                Some(secs) => format!("{:.6}, can be completed in {}", self.manager_withdraw_requested, format_duration(secs)),
+                None => "none".to_string(),
+            },
+        ));
+        result
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct DriftVaultInfoArgs {
     pub vault_address: Option<String>,
     pub requested_fields: Option<Vec<String>>,
+    pub format: Option<String>, // "text" (default) or "json"
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct DriftVaultInfo;
 impl DriftVaultInfo {
     pub async fn fetch_vault_info(args: DriftVaultInfoArgs) -> Result<String, DriftDataError> {
-        let rpc_url = "https://api.mainnet-beta.solana.com";
-        let rpc_client = RpcClient::new(rpc_url.to_string());
-        let agent_vault: String = env::var("AGENT_VAULT").unwrap();
-        let vault = &args.vault_address.unwrap_or(agent_vault);
-        let vault_pubkey = Pubkey::from_str(vault).map_err(|_| DriftDataError { 
-            message: "Failed to get vault pubkey".to_string()
-        })?;
-        let account_data = rpc_client.get_account_data(&vault_pubkey).await.map_err(|_| DriftDataError { 
-            message: "Failed to get_account_data".to_string()
-        })?;
-        let contract_state: ContractState = ContractState::try_from_slice(&account_data)
-            .map_err(|_| DriftDataError { 
-                message: "Failed to get contract state".to_string()
-            })?;
+        let vault_pubkey = vault_pubkey_or_agent_vault(args.vault_address.as_deref())?;
+        let client = init_drift_client().await?;
+        let info = get_vault_info(&client, &vault_pubkey).await?;
 
-        let mut fields_map: HashMap<String, String> = HashMap::new();
-        let name_ascii: String = contract_state.name.iter()
-            .filter(|&&b| b.is_ascii_graphic() || b.is_ascii_whitespace())
-            .map(|&b| b as char)
-            .collect();
-
-        fields_map.insert("vault_address".to_string(), vault.clone());
-        fields_map.insert("name".to_string(), format!("{:?}", name_ascii));
-        fields_map.insert("manager".to_string(), format!("{:?}", contract_state.manager));
-        fields_map.insert("token_account".to_string(), format!("{:?}", contract_state.token_account));
-        fields_map.insert("user_stats".to_string(), format!("{:?}", contract_state.user_stats));
-        fields_map.insert("user".to_string(), format!("{:?}", contract_state.user));
-        fields_map.insert("delegate".to_string(), format!("{:?}", contract_state.delegate));
-        fields_map.insert("liquidation_delegate".to_string(), format!("{:?}", contract_state.liquidation_delegate));
-        fields_map.insert("user_shares".to_string(), contract_state.user_shares.to_string());
-        fields_map.insert("total_shares".to_string(), contract_state.total_shares.to_string());
-        fields_map.insert("last_fee_update_ts".to_string(), contract_state.last_fee_update_ts.to_string());
-        fields_map.insert("liquidation_start_ts".to_string(), contract_state.liquidation_start_ts.to_string());
-        fields_map.insert("redeem_period".to_string(), contract_state.redeem_period.to_string());
-        fields_map.insert("total_withdraw_requested".to_string(), contract_state.total_withdraw_requested.to_string());
-        fields_map.insert("max_tokens".to_string(), contract_state.max_tokens.to_string());
-        fields_map.insert("management_fee".to_string(), contract_state.management_fee.to_string());
-        fields_map.insert("init_ts".to_string(), contract_state.init_ts.to_string());
-        fields_map.insert("net_deposits".to_string(), contract_state.net_deposits.to_string());
-        fields_map.insert("manager_net_deposits".to_string(), contract_state.manager_net_deposits.to_string());
-        fields_map.insert("total_deposits".to_string(), contract_state.total_deposits.to_string());
-        fields_map.insert("total_withdraws".to_string(), contract_state.total_withdraws.to_string());
-        fields_map.insert("manager_total_deposits".to_string(), contract_state.manager_total_deposits.to_string());
-        fields_map.insert("manager_total_withdraws".to_string(), contract_state.manager_total_withdraws.to_string());
-        fields_map.insert("manager_total_fee".to_string(), contract_state.manager_total_fee.to_string());
-        fields_map.insert("manager_total_profit_share".to_string(), contract_state.manager_total_profit_share.to_string());
-        fields_map.insert("min_deposit_amount".to_string(), contract_state.min_deposit_amount.to_string());
-        fields_map.insert("shares_base".to_string(), contract_state.shares_base.to_string());
-        fields_map.insert("profit_share".to_string(), contract_state.profit_share.to_string());
-        fields_map.insert("hurdle_rate".to_string(), contract_state.hurdle_rate.to_string());
-        fields_map.insert("spot_market_index".to_string(), contract_state.spot_market_index.to_string());
-        fields_map.insert("bump".to_string(), contract_state.bump.to_string());
-        fields_map.insert("permissioned".to_string(), contract_state.permissioned.to_string());
-
-        let mut result = String::new();
-        if let Some(requested_fields) = args.requested_fields {
-            for field in requested_fields {
-                if let Some(value) = fields_map.get(&field) {
-                    result.push_str(&format!("{}: {}\n", field, value));
+        let value = serde_json::to_value(&info).map_err(|_| DriftDataError {
+            message: "Failed to serialize vault info".to_string()
+        })?;
+        let json = args.format.as_deref() == Some("json");
+        match args.requested_fields {
+            Some(requested_fields) => {
+                let fields: serde_json::Map<String, Value> = requested_fields
+                    .into_iter()
+                    .filter_map(|field| value.get(&field).map(|v| (field, v.clone())))
+                    .collect();
+                if json {
+                    return Ok(Value::Object(fields).to_string());
                 }
+                Ok(fields.iter().map(|(field, v)| format!("{}: {}\n", field, v)).collect())
             }
-        } else {
-            for (key, value) in fields_map.iter() {
-                result.push_str(&format!("{}: {}\n", key, value));
-            }
+            None if json => Ok(value.to_string()),
+            None => Ok(info.to_text()),
         }
-
-        Ok(result)
     }
 }
 
@@ -148,12 +360,13 @@ impl Tool for DriftVaultInfo {
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
             name: "drift_vault_info".to_string(),
-            description: "Fetch all Drift vault information including vault_address, name, manager, token_account, user_stats, user, delegate, liquidation_delegate, user_shares, total_shares, last_fee_update_ts, liquidation_start_ts, redeem_period, total_withdraw_requested, max_tokens, management_fee, init_ts, net_deposits, manager_net_deposits, total_deposits, total_withdraws, manager_total_deposits, manager_total_withdraws, manager_total_fee, manager_total_profit_share, min_deposit_amount, shares_base, profit_share, hurdle_rate, spot_market_index, bump, permissioned".to_string(),
+            description: "Fetch Drift vault information: addresses (vault_address, name, manager, delegate, liquidation_delegate, token_account, user, user_stats), shares and flows (user_shares, total_shares, net_deposits, total_deposits, total_withdraws, manager_net_deposits, total_withdraw_requested, max_tokens, min_deposit_amount), terms (management_fee_percent, profit_share_percent, hurdle_rate_percent, redeem_period_secs) and derived metrics (equity, equity_usd, share_price, manager_equity, manager_equity_percent, fees_accrued, pending_management_fee, depositor_return_percent, hurdle_status, manager_withdraw_available_in_secs). Redemptions are timed per depositor: a withdraw request can be completed redeem_period_secs after it is made; use drift_vault_depositor for the time left on a depositor's request. Token amounts are in whole units of the vault's spot market token.".to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
                     "vault_address": { "type": "string", "description": "The public key of the vault" },
-                    "requested_fields": { "type": "array", "items": { "type": "string" }, "description": "List of fields to retrieve" }
+                    "requested_fields": { "type": "array", "items": { "type": "string" }, "description": "List of fields to retrieve" },
+                    "format": { "type": "string", "enum": ["text", "json"], "description": "Output format (default: text)." }
                 }
             }),
         }
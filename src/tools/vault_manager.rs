@@ -0,0 +1,624 @@
+use std::env;
+use borsh::BorshSerialize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    signature::Signature,
+    system_program,
+    sysvar,
+};
+use spl_associated_token_account::get_associated_token_address;
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use drift_rs::{constants, types::Context, DriftClient, Pubkey, Wallet};
+use solana_client::nonblocking::rpc_client::RpcClient;
+
+use crate::tools::shared::*;
+use crate::tools::vault::*;
+use crate::tools::anchor::*;
+use crate::tools::depositor::vault_depositor_address;
+
+/// Loads the vault manager keypair from VAULT_MANAGER_KEYPAIR. There is deliberately no fallback
+/// to AGENT_KEYPAIR, so the trading delegate never signs as the manager by accident. The wallet is
+/// not delegated: manager instructions are signed by the manager itself.
+pub fn load_manager_wallet() -> Result<Wallet, DriftDataError> {
+    Ok(Wallet::from(load_keypair_from_env("VAULT_MANAGER_KEYPAIR")?))
+}
+
+/// Whether agents should register the manager tools, from AGENT_MANAGER_TOOLS (default false).
+/// They create and reconfigure vaults and move the manager's own funds, so they are opt-in.
+pub fn manager_tools_enabled() -> bool {
+    env::var("AGENT_MANAGER_TOOLS").map_or(false, |v| v.trim().eq_ignore_ascii_case("true"))
+}
+
+/// Creates a mainnet Drift client signing as the vault manager.
+pub async fn init_manager_drift_client() -> Result<DriftClient, DriftDataError> {
+    DriftClient::new(
+        Context::MainNet,
+        RpcClient::new(SOLANA_MAINNET_RPC_URL.to_string()),
+        load_manager_wallet()?,
+    )
+    .await
+    .map_err(|_| DriftDataError {
+        message: "Failed to init Drift client".to_string()
+    })
+}
+
+/// Vault names are stored as 32 bytes padded with spaces.
+pub fn encode_vault_name(name: &str) -> Result<[u8; 32], DriftDataError> {
+    if name.len() > 32 {
+        return Err(DriftDataError { message: format!("Vault name '{}' is longer than 32 bytes", name) });
+    }
+    let mut encoded = [b' '; 32];
+    encoded[..name.len()].copy_from_slice(name.as_bytes());
+    Ok(encoded)
+}
+
+pub fn vault_address(name: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"vault", name], &DRIFT_VAULTS_PROGRAM_ID).0
+}
+
+pub fn vault_token_account_address(vault: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"vault_token_account", vault.as_ref()], &DRIFT_VAULTS_PROGRAM_ID).0
+}
+
+/// The vault's Drift user (sub-account 0) and user stats accounts.
+pub fn vault_drift_accounts(vault: &Pubkey) -> (Pubkey, Pubkey) {
+    let user = Pubkey::find_program_address(&[b"user", vault.as_ref(), &0u16.to_le_bytes()], &constants::PROGRAM_ID).0;
+    let user_stats = Pubkey::find_program_address(&[b"user_stats", vault.as_ref()], &constants::PROGRAM_ID).0;
+    (user, user_stats)
+}
+
+pub(crate) fn drift_signer() -> Pubkey {
+    Pubkey::find_program_address(&[b"drift_signer"], &constants::PROGRAM_ID).0
+}
+
+#[derive(BorshSerialize, Debug, Clone)]
+pub struct VaultParams {
+    pub name: [u8; 32],
+    pub redeem_period: i64,
+    pub max_tokens: u64,
+    pub management_fee: i64,
+    pub min_deposit_amount: u64,
+    pub profit_share: u32,
+    pub hurdle_rate: u32,
+    pub spot_market_index: u16,
+    pub permissioned: bool,
+}
+
+#[derive(BorshSerialize, Debug, Clone, Default)]
+pub struct UpdateVaultParams {
+    pub redeem_period: Option<i64>,
+    pub max_tokens: Option<u64>,
+    pub management_fee: Option<i64>,
+    pub min_deposit_amount: Option<u64>,
+    pub profit_share: Option<u32>,
+    pub hurdle_rate: Option<u32>,
+    pub permissioned: Option<bool>,
+}
+
+#[derive(BorshSerialize, Debug, Clone, Copy, PartialEq)]
+pub enum WithdrawUnit {
+    Shares,
+    Token,
+    SharesPercent,
+}
+
+#[derive(BorshSerialize)]
+struct UpdateDelegateArgs {
+    delegate: Pubkey,
+}
+
+#[derive(BorshSerialize)]
+pub(crate) struct RequestWithdrawArgs {
+    pub(crate) withdraw_amount: u64,
+    pub(crate) withdraw_unit: WithdrawUnit,
+}
+
+/// Creates a vault owned by the client's wallet as manager, and sets its trading delegate.
+pub async fn init_vault(client: &DriftClient, params: VaultParams, delegate: Option<Pubkey>) -> Result<(Pubkey, Signature), DriftDataError> {
+    let manager = client.wallet().signer();
+    let vault = vault_address(&params.name);
+    let (drift_user, drift_user_stats) = vault_drift_accounts(&vault);
+    let spot_market = client.get_spot_market_account(params.spot_market_index).await.map_err(|_| DriftDataError {
+        message: format!("Failed to get spot market {}", params.spot_market_index)
+    })?;
+
+    let mut instructions = vec![anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "initialize_vault", &params, vec![
+        AccountMeta::new(vault, false), // Vault
+        AccountMeta::new(vault_token_account_address(&vault), false), // Vault Token Account
+        AccountMeta::new(drift_user_stats, false), // Drift User Stats
+        AccountMeta::new(drift_user, false), // Drift User
+        AccountMeta::new(*constants::state_account(), false), // Drift State
+        AccountMeta::new_readonly(constants::derive_spot_market_account(params.spot_market_index), false), // Drift Spot Market
+        AccountMeta::new_readonly(spot_market.mint, false), // Drift Spot Market Mint
+        AccountMeta::new_readonly(manager, true), // Manager
+        AccountMeta::new(manager, true), // Payer
+        AccountMeta::new_readonly(sysvar::rent::id(), false), // Rent
+        AccountMeta::new_readonly(system_program::id(), false), // System Program
+        AccountMeta::new_readonly(constants::PROGRAM_ID, false), // Drift Program
+        AccountMeta::new_readonly(constants::TOKEN_PROGRAM_ID, false), // Token Program
+    ])];
+    if let Some(delegate) = delegate {
+        instructions.push(update_delegate_instruction(&vault, &manager, delegate));
+    }
+
+    let signature = send_instructions(client, &instructions).await?;
+    Ok((vault, signature))
+}
+
+fn update_delegate_instruction(vault: &Pubkey, manager: &Pubkey, delegate: Pubkey) -> Instruction {
+    let (drift_user, _) = vault_drift_accounts(vault);
+    anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "update_delegate", &UpdateDelegateArgs { delegate }, vec![
+        AccountMeta::new(*vault, false), // Vault
+        AccountMeta::new_readonly(*manager, true), // Manager
+        AccountMeta::new(drift_user, false), // Drift User
+        AccountMeta::new_readonly(constants::PROGRAM_ID, false), // Drift Program
+    ])
+}
+
+pub async fn update_vault_delegate(client: &DriftClient, vault: &Pubkey, delegate: Pubkey) -> Result<Signature, DriftDataError> {
+    let instruction = update_delegate_instruction(vault, &client.wallet().signer(), delegate);
+    send_instructions(client, &[instruction]).await
+}
+
+pub async fn update_vault(client: &DriftClient, vault: &Pubkey, params: UpdateVaultParams) -> Result<Signature, DriftDataError> {
+    let instruction = anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "update_vault", &params, vec![
+        AccountMeta::new(*vault, false), // Vault
+        AccountMeta::new_readonly(client.wallet().signer(), true), // Manager
+    ]);
+    send_instructions(client, &[instruction]).await
+}
+
+/// Vault state plus the Drift accounts the vault program passes through to Drift.
+async fn vault_context(client: &DriftClient, vault: &Pubkey) -> Result<(ContractState, Vec<AccountMeta>), DriftDataError> {
+    let state = fetch_vault_state(&client.rpc(), vault).await?;
+    let vault_user = client.get_user_account(&state.user).await.map_err(|_| DriftDataError {
+        message: "Failed to get the vault's user account".to_string()
+    })?;
+    let remaining_accounts = user_remaining_accounts(client, &vault_user, &[state.spot_market_index], &[]).await?;
+    Ok((state, remaining_accounts))
+}
+
+/// Deposits `amount` (spot market precision) from the manager's associated token account.
+pub async fn manager_deposit(client: &DriftClient, vault: &Pubkey, amount: u64) -> Result<Signature, DriftDataError> {
+    let manager = client.wallet().signer();
+    let (state, remaining_accounts) = vault_context(client, vault).await?;
+    let spot_market = client.get_spot_market_account(state.spot_market_index).await.map_err(|_| DriftDataError {
+        message: format!("Failed to get spot market {}", state.spot_market_index)
+    })?;
+
+    let mut accounts = vec![
+        AccountMeta::new(*vault, false), // Vault
+        AccountMeta::new_readonly(manager, true), // Manager
+        AccountMeta::new(state.token_account, false), // Vault Token Account
+        AccountMeta::new(state.user_stats, false), // Drift User Stats
+        AccountMeta::new(state.user, false), // Drift User
+        AccountMeta::new_readonly(*constants::state_account(), false), // Drift State
+        AccountMeta::new(constants::derive_spot_market_vault(state.spot_market_index), false), // Drift Spot Market Vault
+        AccountMeta::new(get_associated_token_address(&manager, &spot_market.mint), false), // Manager Token Account
+        AccountMeta::new_readonly(constants::PROGRAM_ID, false), // Drift Program
+        AccountMeta::new_readonly(constants::TOKEN_PROGRAM_ID, false), // Token Program
+    ];
+    accounts.extend(remaining_accounts);
+    send_instructions(client, &[anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "manager_deposit", &amount, accounts)]).await
+}
+
+pub async fn manager_request_withdraw(client: &DriftClient, vault: &Pubkey, withdraw_amount: u64, withdraw_unit: WithdrawUnit) -> Result<Signature, DriftDataError> {
+    let (state, remaining_accounts) = vault_context(client, vault).await?;
+    let mut accounts = manager_withdraw_request_accounts(client, vault, &state);
+    accounts.extend(remaining_accounts);
+    let args = RequestWithdrawArgs { withdraw_amount, withdraw_unit };
+    send_instructions(client, &[anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "manager_request_withdraw", &args, accounts)]).await
+}
+
+pub async fn manager_cancel_withdraw_request(client: &DriftClient, vault: &Pubkey) -> Result<Signature, DriftDataError> {
+    let (state, remaining_accounts) = vault_context(client, vault).await?;
+    let mut accounts = manager_withdraw_request_accounts(client, vault, &state);
+    accounts.extend(remaining_accounts);
+    send_instructions(client, &[anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "manager_cancel_withdraw_request", &(), accounts)]).await
+}
+
+fn manager_withdraw_request_accounts(client: &DriftClient, vault: &Pubkey, state: &ContractState) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(*vault, false), // Vault
+        AccountMeta::new_readonly(client.wallet().signer(), true), // Manager
+        AccountMeta::new_readonly(state.user_stats, false), // Drift User Stats
+        AccountMeta::new_readonly(state.user, false), // Drift User
+        AccountMeta::new_readonly(*constants::state_account(), false), // Drift State
+    ]
+}
+
+/// Completes the manager's withdraw request once the redeem period has passed, paying out to
+/// the manager's associated token account.
+pub async fn manager_withdraw(client: &DriftClient, vault: &Pubkey) -> Result<Signature, DriftDataError> {
+    let manager = client.wallet().signer();
+    let (state, remaining_accounts) = vault_context(client, vault).await?;
+    let request = &state.last_manager_withdraw_request;
+    if request.shares == 0 {
+        return Err(DriftDataError { message: "The manager has no pending withdraw request".to_string() });
+    }
+    let available_in = request.ts + state.redeem_period - chrono::Utc::now().timestamp();
+    if available_in > 0 {
+        return Err(DriftDataError { message: format!("The withdraw request can be completed in {}s", available_in) });
+    }
+    let spot_market = client.get_spot_market_account(state.spot_market_index).await.map_err(|_| DriftDataError {
+        message: format!("Failed to get spot market {}", state.spot_market_index)
+    })?;
+
+    let mut accounts = vec![
+        AccountMeta::new(*vault, false), // Vault
+        AccountMeta::new_readonly(manager, true), // Manager
+        AccountMeta::new(state.token_account, false), // Vault Token Account
+        AccountMeta::new(state.user_stats, false), // Drift User Stats
+        AccountMeta::new(state.user, false), // Drift User
+        AccountMeta::new_readonly(*constants::state_account(), false), // Drift State
+        AccountMeta::new(constants::derive_spot_market_vault(state.spot_market_index), false), // Drift Spot Market Vault
+        AccountMeta::new_readonly(drift_signer(), false), // Drift Signer
+        AccountMeta::new(get_associated_token_address(&manager, &spot_market.mint), false), // Manager Token Account
+        AccountMeta::new_readonly(constants::PROGRAM_ID, false), // Drift Program
+        AccountMeta::new_readonly(constants::TOKEN_PROGRAM_ID, false), // Token Program
+    ];
+    accounts.extend(remaining_accounts);
+    send_instructions(client, &[anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "manager_withdraw", &(), accounts)]).await
+}
+
+/// Creates the VaultDepositor account for `authority`, paid for by the client's wallet.
+pub async fn init_vault_depositor(client: &DriftClient, vault: &Pubkey, authority: &Pubkey) -> Result<(Pubkey, Signature), DriftDataError> {
+    let vault_depositor = vault_depositor_address(vault, authority);
+    let instruction = anchor_instruction(DRIFT_VAULTS_PROGRAM_ID, "initialize_vault_depositor", &(), vec![
+        AccountMeta::new_readonly(*vault, false), // Vault
+        AccountMeta::new(vault_depositor, false), // Vault Depositor
+        AccountMeta::new_readonly(*authority, false), // Authority
+        AccountMeta::new(client.wallet().signer(), true), // Payer
+        AccountMeta::new_readonly(sysvar::rent::id(), false), // Rent
+        AccountMeta::new_readonly(system_program::id(), false), // System Program
+    ]);
+    let signature = send_instructions(client, &[instruction]).await?;
+    Ok((vault_depositor, signature))
+}
+
+/// Converts whole tokens to the precision of the spot market.
+async fn token_amount(client: &DriftClient, spot_market_index: u16, amount: f64) -> Result<u64, DriftDataError> {
+    let spot_market = client.get_spot_market_account(spot_market_index).await.map_err(|_| DriftDataError {
+        message: format!("Failed to get spot market {}", spot_market_index)
+    })?;
+    Ok((amount * 10f64.powi(spot_market.decimals as i32)) as u64)
+}
+
+pub(crate) fn percent_to_precision(percent: f64) -> f64 {
+    percent / 100.0 * PERCENTAGE_PRECISION
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftInitVaultArgs {
+    pub name: String,
+    pub delegate: Option<String>,
+    pub spot_market_index: Option<u16>,
+    pub redeem_period_secs: Option<i64>,
+    pub max_tokens: Option<f64>,                // Whole tokens, 0 for no cap
+    pub min_deposit_amount: Option<f64>,        // Whole tokens
+    pub management_fee_percent: Option<f64>,    // Annual
+    pub profit_share_percent: Option<f64>,
+    pub hurdle_rate_percent: Option<f64>,
+    pub permissioned: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftInitVault;
+
+impl Tool for DriftInitVault {
+    const NAME: &'static str = "drift_init_vault";
+
+    type Error = DriftDataError;
+    type Args = DriftInitVaultArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_init_vault".to_string(),
+            description: "Creates a new Drift vault managed by the manager keypair, with its fees, redeem period and deposit limits, and optionally sets its trading delegate.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "Vault name, at most 32 bytes." },
+                    "delegate": { "type": "string", "description": "Public key allowed to trade for the vault (optional)." },
+                    "spot_market_index": { "type": "integer", "description": "Spot market of the deposit token (default: 0, USDC)." },
+                    "redeem_period_secs": { "type": "integer", "description": "Seconds between a withdraw request and its completion (default: 0)." },
+                    "max_tokens": { "type": "number", "description": "Deposit cap in whole tokens (default: 0, no cap)." },
+                    "min_deposit_amount": { "type": "number", "description": "Minimum deposit in whole tokens (default: 0)." },
+                    "management_fee_percent": { "type": "number", "description": "Annual management fee in percent (default: 0)." },
+                    "profit_share_percent": { "type": "number", "description": "Manager's share of profits in percent (default: 0)." },
+                    "hurdle_rate_percent": { "type": "number", "description": "Return depositors must make before profit share is charged, in percent (default: 0)." },
+                    "permissioned": { "type": "boolean", "description": "Only the manager can create depositor accounts (default: false)." }
+                },
+                "required": ["name"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let handle = tokio::task::spawn(async move {
+            let client = init_manager_drift_client().await?;
+            let spot_market_index = args.spot_market_index.unwrap_or(0);
+            let params = VaultParams {
+                name: encode_vault_name(&args.name)?,
+                redeem_period: args.redeem_period_secs.unwrap_or(0),
+                max_tokens: token_amount(&client, spot_market_index, args.max_tokens.unwrap_or(0.0)).await?,
+                management_fee: percent_to_precision(args.management_fee_percent.unwrap_or(0.0)) as i64,
+                min_deposit_amount: token_amount(&client, spot_market_index, args.min_deposit_amount.unwrap_or(0.0)).await?,
+                profit_share: percent_to_precision(args.profit_share_percent.unwrap_or(0.0)) as u32,
+                hurdle_rate: percent_to_precision(args.hurdle_rate_percent.unwrap_or(0.0)) as u32,
+                spot_market_index,
+                permissioned: args.permissioned.unwrap_or(false),
+            };
+            let delegate = args.delegate.as_deref().map(|d| parse_pubkey(d, "delegate")).transpose()?;
+            init_vault(&client, params, delegate).await
+        });
+        let (vault, signature) = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await init_vault".to_string()
+        })??;
+
+        Ok(format!("Vault created: {}\nSignature: {}", vault, signature))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftUpdateVaultArgs {
+    pub vault_address: Option<String>,
+    pub delegate: Option<String>,
+    pub redeem_period_secs: Option<i64>,
+    pub max_tokens: Option<f64>,
+    pub min_deposit_amount: Option<f64>,
+    pub management_fee_percent: Option<f64>,
+    pub profit_share_percent: Option<f64>,
+    pub hurdle_rate_percent: Option<f64>,
+    pub permissioned: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftUpdateVault;
+
+impl Tool for DriftUpdateVault {
+    const NAME: &'static str = "drift_update_vault";
+
+    type Error = DriftDataError;
+    type Args = DriftUpdateVaultArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_update_vault".to_string(),
+            description: "Updates a Drift vault as its manager: the trading delegate and any of the redeem period, deposit cap, minimum deposit, fees, hurdle rate and permissioned flag. Only the fields given are changed; the program only allows terms to become more favorable to depositors.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "vault_address": { "type": "string", "description": "The public key of the vault (optional, defaults to the agent vault)." },
+                    "delegate": { "type": "string", "description": "New trading delegate public key." },
+                    "redeem_period_secs": { "type": "integer", "description": "Seconds between a withdraw request and its completion." },
+                    "max_tokens": { "type": "number", "description": "Deposit cap in whole tokens, 0 for no cap." },
+                    "min_deposit_amount": { "type": "number", "description": "Minimum deposit in whole tokens." },
+                    "management_fee_percent": { "type": "number", "description": "Annual management fee in percent." },
+                    "profit_share_percent": { "type": "number", "description": "Manager's share of profits in percent." },
+                    "hurdle_rate_percent": { "type": "number", "description": "Hurdle rate in percent." },
+                    "permissioned": { "type": "boolean", "description": "Only the manager can create depositor accounts." }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let vault = vault_pubkey_or_agent_vault(args.vault_address.as_deref())?;
+        let handle = tokio::task::spawn(async move {
+            let client = init_manager_drift_client().await?;
+            let mut signatures = Vec::new();
+            if let Some(delegate) = &args.delegate {
+                signatures.push(update_vault_delegate(&client, &vault, parse_pubkey(delegate, "delegate")?).await?);
+            }
+
+            let spot_market_index = fetch_vault_state(&client.rpc(), &vault).await?.spot_market_index;
+            let params = UpdateVaultParams {
+                redeem_period: args.redeem_period_secs,
+                max_tokens: match args.max_tokens {
+                    Some(amount) => Some(token_amount(&client, spot_market_index, amount).await?),
+                    None => None,
+                },
+                min_deposit_amount: match args.min_deposit_amount {
+                    Some(amount) => Some(token_amount(&client, spot_market_index, amount).await?),
+                    None => None,
+                },
+                management_fee: args.management_fee_percent.map(|p| percent_to_precision(p) as i64),
+                profit_share: args.profit_share_percent.map(|p| percent_to_precision(p) as u32),
+                hurdle_rate: args.hurdle_rate_percent.map(|p| percent_to_precision(p) as u32),
+                permissioned: args.permissioned,
+            };
+            let has_params = params.redeem_period.is_some()
+                || params.max_tokens.is_some()
+                || params.min_deposit_amount.is_some()
+                || params.management_fee.is_some()
+                || params.profit_share.is_some()
+                || params.hurdle_rate.is_some()
+                || params.permissioned.is_some();
+            if has_params {
+                signatures.push(update_vault(&client, &vault, params).await?);
+            }
+            Ok::<_, DriftDataError>(signatures)
+        });
+        let signatures = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await update_vault".to_string()
+        })??;
+
+        if signatures.is_empty() {
+            return Ok("Nothing to update.".to_string());
+        }
+        Ok(format!(
+            "Vault {} updated.\nSignatures: {}",
+            vault,
+            signatures.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(", ")
+        ))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftManagerDepositArgs {
+    pub amount: f64,    // Whole tokens
+    pub vault_address: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftManagerDeposit;
+
+impl Tool for DriftManagerDeposit {
+    const NAME: &'static str = "drift_manager_deposit";
+
+    type Error = DriftDataError;
+    type Args = DriftManagerDepositArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_manager_deposit".to_string(),
+            description: "Deposits tokens into a Drift vault from the manager's wallet, as manager shares.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "amount": { "type": "number", "description": "Amount in whole tokens of the vault's deposit token (e.g. 100 for 100 USDC)." },
+                    "vault_address": { "type": "string", "description": "The public key of the vault (optional, defaults to the agent vault)." }
+                },
+                "required": ["amount"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let vault = vault_pubkey_or_agent_vault(args.vault_address.as_deref())?;
+        let handle = tokio::task::spawn(async move {
+            let client = init_manager_drift_client().await?;
+            let spot_market_index = fetch_vault_state(&client.rpc(), &vault).await?.spot_market_index;
+            let amount = token_amount(&client, spot_market_index, args.amount).await?;
+            manager_deposit(&client, &vault, amount).await
+        });
+        let signature = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await manager_deposit".to_string()
+        })??;
+
+        Ok(format!("Deposited {} into vault {}.\nSignature: {}", args.amount, vault, signature))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftManagerWithdrawArgs {
+    pub action: String,                 // "request", "cancel" or "complete"
+    pub amount: Option<f64>,            // For request
+    pub unit: Option<String>,           // "token" (default), "shares" or "shares_percent"
+    pub vault_address: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftManagerWithdraw;
+
+impl Tool for DriftManagerWithdraw {
+    const NAME: &'static str = "drift_manager_withdraw";
+
+    type Error = DriftDataError;
+    type Args = DriftManagerWithdrawArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_manager_withdraw".to_string(),
+            description: "Withdraws the manager's funds from a Drift vault in steps: request a withdrawal, cancel a pending request, or complete it once the vault's redeem period has passed.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "action": { "type": "string", "enum": ["request", "cancel", "complete"], "description": "Which step to perform." },
+                    "amount": { "type": "number", "description": "Amount to request, in the given unit." },
+                    "unit": { "type": "string", "enum": ["token", "shares", "shares_percent"], "description": "Unit of amount: whole tokens (default), raw shares, or percent of the manager's shares." },
+                    "vault_address": { "type": "string", "description": "The public key of the vault (optional, defaults to the agent vault)." }
+                },
+                "required": ["action"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let vault = vault_pubkey_or_agent_vault(args.vault_address.as_deref())?;
+        let action = args.action.clone();
+        let handle = tokio::task::spawn(async move {
+            let client = init_manager_drift_client().await?;
+            match args.action.as_str() {
+                "request" => {
+                    let amount = args.amount.ok_or(DriftDataError { message: "amount is required to request a withdrawal".to_string() })?;
+                    let (withdraw_amount, unit) = match args.unit.as_deref().unwrap_or("token") {
+                        "token" => {
+                            let spot_market_index = fetch_vault_state(&client.rpc(), &vault).await?.spot_market_index;
+                            (token_amount(&client, spot_market_index, amount).await?, WithdrawUnit::Token)
+                        }
+                        "shares" => (amount as u64, WithdrawUnit::Shares),
+                        // Shares percent uses PERCENTAGE_PRECISION.
+                        "shares_percent" => (percent_to_precision(amount) as u64, WithdrawUnit::SharesPercent),
+                        other => return Err(DriftDataError { message: format!("Unknown withdraw unit {}", other) }),
+                    };
+                    manager_request_withdraw(&client, &vault, withdraw_amount, unit).await
+                }
+                "cancel" => manager_cancel_withdraw_request(&client, &vault).await,
+                "complete" => manager_withdraw(&client, &vault).await,
+                other => Err(DriftDataError { message: format!("Unknown withdraw action {}", other) }),
+            }
+        });
+        let signature = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await manager withdraw".to_string()
+        })??;
+
+        Ok(format!("Manager withdraw {} sent for vault {}.\nSignature: {}", action, vault, signature))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftInitVaultDepositorArgs {
+    pub authority: String,
+    pub vault_address: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DriftInitVaultDepositor;
+
+impl Tool for DriftInitVaultDepositor {
+    const NAME: &'static str = "drift_init_vault_depositor";
+
+    type Error = DriftDataError;
+    type Args = DriftInitVaultDepositorArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "drift_init_vault_depositor".to_string(),
+            description: "Creates the depositor account that lets a wallet deposit into a Drift vault. Required once per wallet before its first deposit; for permissioned vaults only the manager can do it.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "authority": { "type": "string", "description": "Wallet public key of the depositor." },
+                    "vault_address": { "type": "string", "description": "The public key of the vault (optional, defaults to the agent vault)." }
+                },
+                "required": ["authority"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, DriftDataError> {
+        let vault = vault_pubkey_or_agent_vault(args.vault_address.as_deref())?;
+        let authority = parse_pubkey(&args.authority, "authority")?;
+        let handle = tokio::task::spawn(async move {
+            let client = init_manager_drift_client().await?;
+            init_vault_depositor(&client, &vault, &authority).await
+        });
+        let (vault_depositor, signature) = handle.await.map_err(|_| DriftDataError {
+            message: "Failed to await init_vault_depositor".to_string()
+        })??;
+
+        Ok(format!("Vault depositor created: {}\nSignature: {}", vault_depositor, signature))
+    }
+}